@@ -0,0 +1,428 @@
+// Optional local HTTP API for external dashboards and test harnesses.
+// Loopback only, every request needs the token (Bearer header or ?token=).
+//
+//   GET  /api/events                        SSE stream of live request/response events
+//   GET  /api/status
+//   GET  /api/sessions
+//   GET  /api/exchanges?session_id=&offset=&limit=   recent exchanges (active session by default)
+//   GET  /api/exchanges/{id}?session_id=
+//   POST /api/proxy/start    {"addr":..,"upstream":..}
+//   POST /api/proxy/stop
+//   POST /api/capture/start  {"iface":..,"ports":[..],"hosts":[..],"bpf":..,"snaplen":..,"promisc":..}
+//   POST /api/capture/stop
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use http_body::Frame;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::Incoming as IncomingBody;
+use hyper::server::conn::http1;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::events::{CaptureEvent, EventSink, SharedSink};
+use crate::http_shared::percent_decode;
+use crate::store::STORE;
+
+const DEFAULT_API_ADDR: &str = "127.0.0.1:38081";
+const DEFAULT_RECENT_LIMIT: usize = 50;
+
+type ApiBody = BoxBody<Bytes, Infallible>;
+
+static EVENTS: Lazy<broadcast::Sender<CaptureEvent>> = Lazy::new(|| broadcast::channel(1024).0);
+
+struct RunningServer {
+    info: ApiServerInfo,
+    shutdown: oneshot::Sender<()>,
+}
+
+static SERVER: Lazy<Mutex<Option<RunningServer>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiServerInfo {
+    pub addr: String,
+    pub token: String,
+}
+
+/// Publishes events to SSE subscribers; a no-op while nobody is listening.
+pub struct ApiSink;
+
+impl EventSink for ApiSink {
+    fn emit(&self, event: &CaptureEvent) {
+        if EVENTS.receiver_count() > 0 {
+            let _ = EVENTS.send(event.clone());
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApiContext {
+    token: String,
+    // proxy / capture started through the API deliver to the same sinks as the app
+    sink: SharedSink,
+}
+
+fn random_token() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().r#gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub async fn start_api_server(
+    sink: SharedSink,
+    addr: Option<String>,
+    token: Option<String>,
+) -> Result<ApiServerInfo, String> {
+    if let Some(info) = api_server_info() {
+        return Ok(info);
+    }
+    let addr: SocketAddr = addr
+        .as_deref()
+        .unwrap_or(DEFAULT_API_ADDR)
+        .parse()
+        .map_err(|e| format!("invalid api address: {}", e))?;
+    if !addr.ip().is_loopback() {
+        return Err("api server only binds to loopback addresses".into());
+    }
+    let token = token.filter(|t| !t.is_empty()).unwrap_or_else(random_token);
+    let listener = TcpListener::bind(addr).await.map_err(|e| e.to_string())?;
+    let local = listener.local_addr().map_err(|e| e.to_string())?;
+    let info = ApiServerInfo {
+        addr: local.to_string(),
+        token: token.clone(),
+    };
+    let (tx, mut rx) = oneshot::channel();
+    *SERVER.lock().unwrap() = Some(RunningServer {
+        info: info.clone(),
+        shutdown: tx,
+    });
+
+    let ctx = ApiContext { token, sink };
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                _ = &mut rx => break,
+                res = listener.accept() => match res {
+                    Ok((stream, _)) => stream,
+                    Err(_) => continue,
+                },
+            };
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req| {
+                    let ctx = ctx.clone();
+                    async move { Ok::<_, Infallible>(handle(&ctx, req).await) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    Ok(info)
+}
+
+pub fn stop_api_server() {
+    if let Some(server) = SERVER.lock().unwrap().take() {
+        let _ = server.shutdown.send(());
+    }
+}
+
+pub fn api_server_info() -> Option<ApiServerInfo> {
+    SERVER.lock().unwrap().as_ref().map(|s| s.info.clone())
+}
+
+fn query_params(req: &Request<IncomingBody>) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    for pair in req.uri().query().unwrap_or("").split('&') {
+        if let Some((k, v)) = pair.split_once('=') {
+            out.insert(percent_decode(k), percent_decode(v));
+        }
+    }
+    out
+}
+
+fn authorized(
+    ctx: &ApiContext,
+    req: &Request<IncomingBody>,
+    params: &HashMap<String, String>,
+) -> bool {
+    let bearer = req
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    bearer
+        .or(params.get("token").map(|s| s.as_str()))
+        .is_some_and(|t| constant_time_eq(t.as_bytes(), ctx.token.as_bytes()))
+}
+
+// 逐字节异或累加，比较耗时不随首个不同字节的位置变化
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn full(status: StatusCode, content_type: &str, body: impl Into<Bytes>) -> Response<ApiBody> {
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, content_type)
+        .body(Full::new(body.into()).boxed())
+        .unwrap()
+}
+
+fn json<T: Serialize>(value: &T) -> Response<ApiBody> {
+    match serde_json::to_vec(value) {
+        Ok(v) => full(StatusCode::OK, "application/json", v),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn error(status: StatusCode, msg: &str) -> Response<ApiBody> {
+    let body = serde_json::json!({ "error": msg }).to_string();
+    full(status, "application/json", body)
+}
+
+fn result<T: Serialize>(res: Result<T, String>) -> Response<ApiBody> {
+    match res {
+        Ok(v) => json(&v),
+        Err(e) => error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+async fn read_json<T: for<'de> Deserialize<'de> + Default>(
+    req: Request<IncomingBody>,
+) -> Result<T, String> {
+    let body = req
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(&body).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StartProxyBody {
+    addr: Option<String>,
+    upstream: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StartCaptureBody {
+    iface: String,
+    #[serde(flatten)]
+    options: crate::capture::CaptureOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    proxy_running: bool,
+    capture_running: bool,
+    capture_stats: crate::capture::CaptureStats,
+    session_id: Option<String>,
+}
+
+async fn handle(ctx: &ApiContext, req: Request<IncomingBody>) -> Response<ApiBody> {
+    let params = query_params(&req);
+    if !authorized(ctx, &req, &params) {
+        return error(StatusCode::UNAUTHORIZED, "missing or invalid token");
+    }
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (req.method().clone(), segments.as_slice()) {
+        (Method::GET, ["api", "events"]) => event_stream(),
+        (Method::GET, ["api", "status"]) => json(&Status {
+            proxy_running: crate::proxy::is_proxy_running(),
+            capture_running: crate::capture::is_capture_running(),
+            capture_stats: crate::capture::capture_stats(),
+            session_id: STORE.active_session_id(),
+        }),
+        (Method::GET, ["api", "sessions"]) => result(STORE.list_sessions()),
+        (Method::GET, ["api", "usage"]) => {
+            let bound = |k: &str| params.get(k).and_then(|v| v.parse().ok());
+            let group = params
+                .get("group_by")
+                .map(|g| crate::usage::UsageGroup::parse(g))
+                .transpose();
+            result(group.map(|g| crate::usage::USAGE.totals(bound("from_ms"), bound("to_ms"), g)))
+        }
+        (Method::GET, ["api", "exchanges"]) => result(recent_exchanges(&params)),
+        (Method::GET, ["api", "exchanges", id]) => {
+            let res = session_param(&params).and_then(|sid| STORE.get_exchange(&sid, id));
+            match res {
+                Ok(None) => error(StatusCode::NOT_FOUND, "exchange not found"),
+                other => result(other),
+            }
+        }
+        (Method::POST, ["api", "proxy", "start"]) => match read_json::<StartProxyBody>(req).await {
+            Ok(b) => result(crate::start_proxy_with(ctx.sink.clone(), b.addr, b.upstream).await),
+            Err(e) => error(StatusCode::BAD_REQUEST, &e),
+        },
+        (Method::POST, ["api", "proxy", "stop"]) => {
+            crate::stop_proxy_with_session();
+            json(&())
+        }
+        (Method::POST, ["api", "capture", "start"]) => {
+            match read_json::<StartCaptureBody>(req).await {
+                Ok(b) => result(crate::start_capture_with(
+                    ctx.sink.clone(),
+                    &b.iface,
+                    &b.options,
+                )),
+                Err(e) => error(StatusCode::BAD_REQUEST, &e),
+            }
+        }
+        (Method::POST, ["api", "capture", "stop"]) => {
+            crate::stop_capture_with_session();
+            json(&())
+        }
+        _ => error(StatusCode::NOT_FOUND, "no such endpoint"),
+    }
+}
+
+fn session_param(params: &HashMap<String, String>) -> Result<String, String> {
+    params
+        .get("session_id")
+        .cloned()
+        .or_else(|| STORE.active_session_id())
+        .ok_or_else(|| "no active session; pass session_id".to_string())
+}
+
+// Without an explicit offset the newest `limit` exchanges are returned.
+fn recent_exchanges(
+    params: &HashMap<String, String>,
+) -> Result<crate::store::ExchangePage, String> {
+    let session_id = session_param(params)?;
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RECENT_LIMIT);
+    let offset = match params.get("offset").and_then(|v| v.parse().ok()) {
+        Some(o) => o,
+        None => {
+            let total = STORE.list_exchanges(&session_id, 0, 0)?.total;
+            total.saturating_sub(limit)
+        }
+    };
+    STORE.list_exchanges(&session_id, offset, limit)
+}
+
+fn sse_frame(event: &CaptureEvent) -> Option<Bytes> {
+    let data = serde_json::to_string(event).ok()?;
+    Some(Bytes::from(format!(
+        "event: {}\ndata: {}\n\n",
+        event.name(),
+        data
+    )))
+}
+
+fn event_stream() -> Response<ApiBody> {
+    let mut events = EVENTS.subscribe();
+    let (tx, rx) = mpsc::channel::<Result<Frame<Bytes>, Infallible>>(64);
+    tokio::spawn(async move {
+        // initial comment so clients see the stream open immediately
+        if tx
+            .send(Ok(Frame::data(Bytes::from_static(b": connected\n\n"))))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        loop {
+            let frame = match events.recv().await {
+                Ok(evt) => match sse_frame(&evt) {
+                    Some(f) => f,
+                    None => continue,
+                },
+                // slow subscriber: tell it how many events were dropped and keep going
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    Bytes::from(format!("event: lagged\ndata: {}\n\n", n))
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if tx.send(Ok(Frame::data(frame))).await.is_err() {
+                break;
+            }
+        }
+    });
+    Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "text/event-stream")
+        .header(hyper::header::CACHE_CONTROL, "no-cache")
+        .body(StreamBody::new(ReceiverStream::new(rx)).boxed())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get(addr: &str, path: &str, token: Option<&str>) -> String {
+        let mut s = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let auth = token
+            .map(|t| format!("Authorization: Bearer {}\r\n", t))
+            .unwrap_or_default();
+        let req = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+            path, addr, auth
+        );
+        s.write_all(req.as_bytes()).await.unwrap();
+        let mut out = String::new();
+        s.read_to_string(&mut out).await.unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn requires_token_and_rejects_public_bind() {
+        let sink: SharedSink = std::sync::Arc::new(ApiSink);
+        assert!(
+            start_api_server(sink.clone(), Some("0.0.0.0:0".into()), None)
+                .await
+                .is_err()
+        );
+        let info = start_api_server(sink, Some("127.0.0.1:0".into()), Some("secret".into()))
+            .await
+            .unwrap();
+        assert!(
+            get(&info.addr, "/api/status", None)
+                .await
+                .starts_with("HTTP/1.1 401")
+        );
+        let ok = get(&info.addr, "/api/status", Some("secret")).await;
+        assert!(ok.starts_with("HTTP/1.1 200"));
+        assert!(ok.contains("\"proxy_running\":false"));
+        let by_query = get(&info.addr, "/api/status?token=secret", None).await;
+        assert!(by_query.starts_with("HTTP/1.1 200"));
+        assert!(
+            get(&info.addr, "/api/status", Some("secreT"))
+                .await
+                .starts_with("HTTP/1.1 401")
+        );
+        stop_api_server();
+
+        // query values are percent-decoded before the comparison
+        let info = start_api_server(
+            std::sync::Arc::new(ApiSink),
+            Some("127.0.0.1:0".into()),
+            Some("s/e&c=t".into()),
+        )
+        .await
+        .unwrap();
+        let encoded = get(&info.addr, "/api/status?token=s%2Fe%26c%3Dt", None).await;
+        assert!(encoded.starts_with("HTTP/1.1 200"));
+        stop_api_server();
+        assert!(api_server_info().is_none());
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    std::process::exit(promptdumper_lib::cli::run())
+}
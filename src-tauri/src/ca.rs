@@ -6,6 +6,7 @@ use rcgen::{
     ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
 };
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::collections::HashSet;
 use std::fs;
 use std::io::Write;
@@ -207,7 +208,19 @@ fn generate_and_open_mobileconfig(cert_pem: &str) -> Result<(), String> {
     Err("需要在系统设置中点击安装已下载的描述文件".into())
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(target_os = "linux")]
+pub fn install_ca_to_system_trust(cert_pem: &str) -> Result<(), String> {
+    let path = ca_cert_path();
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    if existing != cert_pem {
+        let mut f = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+        f.write_all(cert_pem.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    LinuxTrustStore::system().install(cert_pem)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
 pub fn install_ca_to_system_trust(_cert_pem: &str) -> Result<(), String> {
     Ok(())
 }
@@ -324,7 +337,16 @@ pub fn is_ca_installed_in_system_trust() -> Result<bool, String> {
     Ok(false)
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(target_os = "linux")]
+pub fn is_ca_installed_in_system_trust() -> Result<bool, String> {
+    // 以本地 rootCA.pem 为准比对指纹；尚未生成 CA 时视为未安装
+    let Ok(cert_pem) = fs::read_to_string(ca_cert_path()) else {
+        return Ok(false);
+    };
+    LinuxTrustStore::system().is_installed(&cert_pem)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
 pub fn is_ca_installed_in_system_trust() -> Result<bool, String> {
     Ok(false)
 }
@@ -625,11 +647,328 @@ pub fn uninstall_ca_from_system_trust() -> Result<(), String> {
         .unwrap_or_else(|| "无法删除证书。可能需要手动在 钥匙串访问 中删除或需要管理员权限".into()))
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(target_os = "linux")]
+pub fn uninstall_ca_from_system_trust() -> Result<(), String> {
+    let cert_pem = fs::read_to_string(ca_cert_path()).ok();
+    LinuxTrustStore::system().uninstall(cert_pem.as_deref())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
 pub fn uninstall_ca_from_system_trust() -> Result<(), String> {
     Ok(())
 }
 
+/// 证书 DER 的 SHA-256 指纹，形如 `AB:CD:...`
+pub fn cert_fingerprint_sha256(pem: &str) -> Result<String, String> {
+    let der = pem_to_der_first_cert(pem)?;
+    let digest = Sha256::digest(&der);
+    Ok(digest
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+// ---- Linux trust stores ----
+
+#[cfg(target_os = "linux")]
+const LINUX_CA_FILE: &str = "promptdumper-root-ca.crt";
+#[cfg(target_os = "linux")]
+const NSS_NICKNAME: &str = "PromptDumper Root CA";
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnchorFlavor {
+    /// Debian/Ubuntu: update-ca-certificates
+    Debian,
+    /// Fedora/RHEL: update-ca-trust（p11-kit `trust anchor` 兜底）
+    Fedora,
+}
+
+#[cfg(target_os = "linux")]
+impl AnchorFlavor {
+    const ALL: [AnchorFlavor; 2] = [AnchorFlavor::Debian, AnchorFlavor::Fedora];
+
+    fn dir(self) -> &'static str {
+        match self {
+            AnchorFlavor::Debian => "usr/local/share/ca-certificates",
+            AnchorFlavor::Fedora => "etc/pki/ca-trust/source/anchors",
+        }
+    }
+}
+
+/// Linux 系统信任库。`root` 为文件系统根，测试时可指向临时目录；
+/// `run_commands` 关闭时只改动 anchors 目录，不调用刷新命令与 certutil。
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+pub struct LinuxTrustStore {
+    pub root: PathBuf,
+    /// NSS 数据库目录（`~/.pki/nssdb`），不存在或未装 certutil 时跳过
+    pub nss_db: Option<PathBuf>,
+    pub run_commands: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl LinuxTrustStore {
+    pub fn system() -> Self {
+        let nss_db = std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".pki/nssdb"));
+        Self {
+            nss_db,
+            run_commands: true,
+            ..Self::at("/")
+        }
+    }
+
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            nss_db: None,
+            run_commands: false,
+        }
+    }
+
+    fn anchor_dirs(&self) -> Vec<(AnchorFlavor, PathBuf)> {
+        AnchorFlavor::ALL
+            .iter()
+            .map(|f| (*f, self.root.join(f.dir())))
+            .filter(|(_, d)| d.is_dir())
+            .collect()
+    }
+
+    /// 比对 anchors 目录下的证书文件与 NSS 库中的同名证书
+    pub fn is_installed(&self, cert_pem: &str) -> Result<bool, String> {
+        let want = cert_fingerprint_sha256(cert_pem)?;
+        for (_, dir) in self.anchor_dirs() {
+            if !matching_anchor_files(&dir, &want).is_empty() {
+                return Ok(true);
+            }
+        }
+        Ok(self
+            .nss_pem()
+            .is_some_and(|pem| cert_fingerprint_sha256(&pem).ok().as_ref() == Some(&want)))
+    }
+
+    pub fn install(&self, cert_pem: &str) -> Result<(), String> {
+        pem_to_der_first_cert(cert_pem)?;
+        let Some((flavor, dir)) = self.anchor_dirs().into_iter().next() else {
+            return Err(format!(
+                "未找到系统信任库目录（/{} 或 /{}），请手动安装 {}",
+                AnchorFlavor::Debian.dir(),
+                AnchorFlavor::Fedora.dir(),
+                ca_cert_path().display()
+            ));
+        };
+        let target = dir.join(LINUX_CA_FILE);
+        match fs::write(&target, cert_pem) {
+            Ok(()) => self.refresh(flavor, &target, false)?,
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && self.run_commands => {
+                // 非 root：写入与刷新合并为一次 pkexec 提权
+                let src = tempfile::NamedTempFile::new().map_err(|e| e.to_string())?;
+                fs::write(src.path(), cert_pem).map_err(|e| e.to_string())?;
+                let script = format!(
+                    "install -m 644 {} {} && {}",
+                    sh_quote(&src.path().to_string_lossy()),
+                    sh_quote(&target.to_string_lossy()),
+                    refresh_script(flavor, &target, false)
+                );
+                run_pkexec(&script).map_err(|err| {
+                    format!(
+                        "{}。可手动执行:\n  sudo cp '{}' '{}' && sudo {}",
+                        err,
+                        ca_cert_path().display(),
+                        target.display(),
+                        refresh_script(flavor, &target, false)
+                    )
+                })?;
+            }
+            Err(e) => return Err(format!("write {}: {}", target.display(), e)),
+        }
+        self.nss_install(cert_pem);
+        Ok(())
+    }
+
+    /// 删除 anchors 目录中本工具写入的文件以及指纹与 `cert_pem` 相同的证书
+    pub fn uninstall(&self, cert_pem: Option<&str>) -> Result<(), String> {
+        let want = cert_pem.and_then(|p| cert_fingerprint_sha256(p).ok());
+        for (flavor, dir) in self.anchor_dirs() {
+            let mut files = want
+                .as_deref()
+                .map(|w| matching_anchor_files(&dir, w))
+                .unwrap_or_default();
+            let ours = dir.join(LINUX_CA_FILE);
+            if ours.exists() && !files.contains(&ours) {
+                files.push(ours);
+            }
+            if files.is_empty() {
+                continue;
+            }
+            let mut denied = Vec::new();
+            for f in &files {
+                match fs::remove_file(f) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                        denied.push(f.clone())
+                    }
+                    Err(e) => return Err(format!("remove {}: {}", f.display(), e)),
+                }
+            }
+            if denied.is_empty() {
+                self.refresh(flavor, &files[0], true)?;
+            } else if self.run_commands {
+                let rm = denied
+                    .iter()
+                    .map(|f| sh_quote(&f.to_string_lossy()))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let script = format!(
+                    "rm -f {} && {}",
+                    rm,
+                    refresh_script(flavor, &files[0], true)
+                );
+                run_pkexec(&script)?;
+            } else {
+                return Err(format!("permission denied: {}", denied[0].display()));
+            }
+        }
+        self.nss_uninstall();
+        Ok(())
+    }
+
+    fn refresh(
+        &self,
+        flavor: AnchorFlavor,
+        anchor: &std::path::Path,
+        removing: bool,
+    ) -> Result<(), String> {
+        if !self.run_commands {
+            return Ok(());
+        }
+        let st = Command::new("sh")
+            .arg("-c")
+            .arg(refresh_script(flavor, anchor, removing))
+            .status()
+            .map_err(|e| e.to_string())?;
+        if st.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "刷新系统信任库失败：{}",
+                refresh_script(flavor, anchor, removing)
+            ))
+        }
+    }
+
+    fn nss_db(&self) -> Option<String> {
+        if !self.run_commands {
+            return None;
+        }
+        let db = self.nss_db.as_ref().filter(|d| d.is_dir())?;
+        Some(format!("sql:{}", db.display()))
+    }
+
+    fn nss_pem(&self) -> Option<String> {
+        let db = self.nss_db()?;
+        let out = Command::new("certutil")
+            .args(["-d", &db, "-L", "-n", NSS_NICKNAME, "-a"])
+            .output()
+            .ok()?;
+        out.status
+            .success()
+            .then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    // NSS（Chrome/Firefox）为尽力而为，失败不影响系统信任库的结果
+    fn nss_install(&self, cert_pem: &str) {
+        let Some(db) = self.nss_db() else {
+            return;
+        };
+        self.nss_uninstall();
+        let Ok(tmp) = tempfile::NamedTempFile::new() else {
+            return;
+        };
+        if fs::write(tmp.path(), cert_pem).is_err() {
+            return;
+        }
+        let _ = Command::new("certutil")
+            .args(["-d", &db, "-A", "-t", "C,,", "-n", NSS_NICKNAME, "-i"])
+            .arg(tmp.path())
+            .status();
+    }
+
+    fn nss_uninstall(&self) {
+        let Some(db) = self.nss_db() else {
+            return;
+        };
+        // 同名证书可能有多份，逐个删除
+        for _ in 0..8 {
+            let ok = Command::new("certutil")
+                .args(["-d", &db, "-D", "-n", NSS_NICKNAME])
+                .status()
+                .is_ok_and(|s| s.success());
+            if !ok {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn refresh_script(flavor: AnchorFlavor, anchor: &std::path::Path, removing: bool) -> String {
+    match (flavor, removing) {
+        (AnchorFlavor::Debian, false) => "update-ca-certificates".into(),
+        (AnchorFlavor::Debian, true) => "update-ca-certificates --fresh".into(),
+        (AnchorFlavor::Fedora, false) => format!(
+            "update-ca-trust extract || trust anchor --store {}",
+            sh_quote(&anchor.to_string_lossy())
+        ),
+        (AnchorFlavor::Fedora, true) => "update-ca-trust extract".into(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn matching_anchor_files(dir: &std::path::Path, fingerprint: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            fs::read_to_string(p)
+                .ok()
+                .and_then(|pem| cert_fingerprint_sha256(&pem).ok())
+                .is_some_and(|fp| fp == fingerprint)
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(target_os = "linux")]
+fn run_pkexec(script: &str) -> Result<(), String> {
+    let out = Command::new("pkexec")
+        .arg("sh")
+        .arg("-c")
+        .arg(script)
+        .output()
+        .map_err(|e| format!("需要 root 权限（pkexec 不可用: {}）", e))?;
+    if out.status.success() {
+        return Ok(());
+    }
+    // pkexec: 126 = 用户取消授权
+    if out.status.code() == Some(126) {
+        return Err("操作已取消：用户在授权对话框中点击了取消".into());
+    }
+    Err(format!(
+        "需要 root 权限: {}",
+        String::from_utf8_lossy(&out.stderr).trim()
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -655,4 +994,38 @@ mod tests {
             "certificate validity window out of expected bounds"
         );
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_trust_store_roundtrip_by_fingerprint() {
+        let self_signed = |cn: &str| {
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            params.distinguished_name.push(DnType::CommonName, cn);
+            Certificate::from_params(params)
+                .unwrap()
+                .serialize_pem()
+                .unwrap()
+        };
+        let ours = self_signed("PromptDumper Root CA");
+        let other = self_signed("PromptDumper Root CA");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let store = LinuxTrustStore::at(tmp.path());
+        assert!(store.install(&ours).is_err(), "no anchors dir yet");
+
+        let anchors = tmp.path().join("etc/pki/ca-trust/source/anchors");
+        fs::create_dir_all(&anchors).unwrap();
+        assert!(!store.is_installed(&ours).unwrap());
+        store.install(&ours).unwrap();
+        assert!(store.is_installed(&ours).unwrap());
+        // 同名但不同密钥的证书不算已安装
+        assert!(!store.is_installed(&other).unwrap());
+
+        // 手动以其他文件名放入的同一证书也能被识别和移除
+        fs::write(anchors.join("manual.pem"), &ours).unwrap();
+        store.uninstall(Some(&ours)).unwrap();
+        assert!(!store.is_installed(&ours).unwrap());
+        assert_eq!(fs::read_dir(&anchors).unwrap().count(), 0);
+    }
 }
@@ -0,0 +1,244 @@
+// HTTP/1.1 message body framing (RFC 9112 §6): how long a body is, and
+// incremental chunked decoding for bodies that arrive over several packets.
+use crate::http_shared::Header;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BodyFraming {
+    None,
+    Length(usize),
+    Chunked,
+    // read until the server closes the connection
+    Close,
+}
+
+fn header<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.trim())
+}
+
+// Transfer-Encoding wins over Content-Length; `None` if neither is usable.
+fn declared_framing(headers: &[Header]) -> Option<BodyFraming> {
+    if let Some(te) = header(headers, "transfer-encoding") {
+        let last = te.rsplit(',').next().unwrap_or("").trim();
+        return Some(if last.eq_ignore_ascii_case("chunked") {
+            BodyFraming::Chunked
+        } else {
+            BodyFraming::Close
+        });
+    }
+    header(headers, "content-length")
+        .and_then(|v| v.parse().ok())
+        .map(BodyFraming::Length)
+}
+
+pub(crate) fn request_framing(headers: &[Header]) -> BodyFraming {
+    match declared_framing(headers) {
+        // a request body can't be delimited by close
+        Some(BodyFraming::Close) | None => BodyFraming::None,
+        Some(f) => f,
+    }
+}
+
+/// `method` is the request this response answers, when known.
+pub(crate) fn response_framing(
+    method: Option<&str>,
+    status: u16,
+    headers: &[Header],
+) -> BodyFraming {
+    if (100..200).contains(&status) || status == 204 || status == 304 {
+        return BodyFraming::None;
+    }
+    match method {
+        Some(m) if m.eq_ignore_ascii_case("HEAD") => return BodyFraming::None,
+        Some(m) if m.eq_ignore_ascii_case("CONNECT") && (200..300).contains(&status) => {
+            return BodyFraming::None;
+        }
+        _ => {}
+    }
+    declared_framing(headers).unwrap_or(BodyFraming::Close)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChunkState {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+}
+
+#[derive(Debug)]
+pub(crate) struct InvalidChunk;
+
+/// Incremental body decoder. Feed it the buffered bytes after the head; it
+/// reports how many it consumed, so partial chunk lines stay buffered.
+#[derive(Debug)]
+pub(crate) struct BodyDecoder {
+    framing: BodyFraming,
+    remaining: usize,
+    chunk: ChunkState,
+    done: bool,
+    pub trailers: Vec<Header>,
+}
+
+impl BodyDecoder {
+    pub fn new(framing: BodyFraming) -> Self {
+        let remaining = match framing {
+            BodyFraming::Length(n) => n,
+            _ => 0,
+        };
+        Self {
+            framing,
+            remaining,
+            chunk: ChunkState::Size,
+            done: matches!(framing, BodyFraming::None | BodyFraming::Length(0)),
+            trailers: Vec::new(),
+        }
+    }
+
+    /// Whole message body seen.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Decodes what it can from `buf`, appending body bytes to `out`; returns the
+    /// number of bytes of `buf` consumed.
+    pub fn decode(&mut self, buf: &[u8], out: &mut Vec<u8>) -> Result<usize, InvalidChunk> {
+        if self.done {
+            return Ok(0);
+        }
+        match self.framing {
+            BodyFraming::None => Ok(0),
+            BodyFraming::Close => {
+                out.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            BodyFraming::Length(_) => {
+                let n = self.remaining.min(buf.len());
+                out.extend_from_slice(&buf[..n]);
+                self.remaining -= n;
+                self.done = self.remaining == 0;
+                Ok(n)
+            }
+            BodyFraming::Chunked => self.decode_chunked(buf, out),
+        }
+    }
+
+    fn decode_chunked(&mut self, buf: &[u8], out: &mut Vec<u8>) -> Result<usize, InvalidChunk> {
+        let mut pos = 0;
+        while !self.done {
+            let rest = &buf[pos..];
+            match self.chunk {
+                ChunkState::Data(n) => {
+                    if rest.is_empty() {
+                        break;
+                    }
+                    let take = n.min(rest.len());
+                    out.extend_from_slice(&rest[..take]);
+                    pos += take;
+                    self.chunk = if take == n {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(n - take)
+                    };
+                }
+                _ => {
+                    let Some(end) = rest.iter().position(|&b| b == b'\n') else {
+                        break;
+                    };
+                    let line = std::str::from_utf8(&rest[..end])
+                        .map_err(|_| InvalidChunk)?
+                        .trim_end_matches('\r');
+                    pos += end + 1;
+                    self.on_line(line)?;
+                }
+            }
+        }
+        Ok(pos)
+    }
+
+    // A size line, the CRLF after chunk data, or a trailer field.
+    fn on_line(&mut self, line: &str) -> Result<(), InvalidChunk> {
+        match self.chunk {
+            ChunkState::Size => {
+                // chunk extensions after `;` are ignored
+                let hex = line.split(';').next().unwrap_or("").trim();
+                let size = usize::from_str_radix(hex, 16).map_err(|_| InvalidChunk)?;
+                self.chunk = if size == 0 {
+                    ChunkState::Trailers
+                } else {
+                    ChunkState::Data(size)
+                };
+            }
+            ChunkState::DataEnd if line.is_empty() => self.chunk = ChunkState::Size,
+            ChunkState::Trailers if line.is_empty() => self.done = true,
+            ChunkState::Trailers => {
+                let (name, value) = line.split_once(':').ok_or(InvalidChunk)?;
+                self.trailers.push(Header {
+                    name: name.trim().to_string(),
+                    value: value.trim().to_string(),
+                });
+            }
+            ChunkState::DataEnd | ChunkState::Data(_) => return Err(InvalidChunk),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<Header> {
+        pairs
+            .iter()
+            .map(|(n, v)| Header {
+                name: n.to_string(),
+                value: v.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn framing_rules() {
+        let cl = headers(&[("Content-Length", "5")]);
+        let te = headers(&[
+            ("Transfer-Encoding", "gzip, chunked"),
+            ("Content-Length", "5"),
+        ]);
+        assert_eq!(
+            response_framing(Some("GET"), 200, &cl),
+            BodyFraming::Length(5)
+        );
+        assert_eq!(
+            response_framing(Some("GET"), 200, &te),
+            BodyFraming::Chunked
+        );
+        assert_eq!(response_framing(Some("HEAD"), 200, &cl), BodyFraming::None);
+        assert_eq!(response_framing(None, 304, &cl), BodyFraming::None);
+        assert_eq!(response_framing(None, 100, &[]), BodyFraming::None);
+        assert_eq!(response_framing(None, 200, &[]), BodyFraming::Close);
+        assert_eq!(request_framing(&[]), BodyFraming::None);
+    }
+
+    #[test]
+    fn chunked_across_splits_with_trailers() {
+        let wire = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Checksum: abc\r\n\r\nHTTP/1.1";
+        // feed byte by byte, keeping unconsumed bytes buffered like the caller does
+        let mut dec = BodyDecoder::new(BodyFraming::Chunked);
+        let (mut buf, mut out) = (Vec::new(), Vec::new());
+        for &b in wire.iter() {
+            buf.push(b);
+            let used = dec.decode(&buf, &mut out).unwrap();
+            buf.drain(..used);
+        }
+        assert!(dec.is_done());
+        assert_eq!(out, b"hello world");
+        assert_eq!(buf, b"HTTP/1.1", "next message stays buffered");
+        assert_eq!(dec.trailers[0].name, "X-Checksum");
+
+        let mut bad = BodyDecoder::new(BodyFraming::Chunked);
+        assert!(bad.decode(b"zz\r\n", &mut Vec::new()).is_err());
+    }
+}
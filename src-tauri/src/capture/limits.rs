@@ -0,0 +1,91 @@
+// Bounds on the per-connection capture state, and the counters reported for
+// evictions and parse failures.
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CaptureLimits {
+    /// Connections without packets for this long are dropped (in `mono_us` units).
+    pub idle_timeout_us: u64,
+    /// Buffered bytes of one connection: parse buffers plus out-of-order segments.
+    pub max_conn_bytes: usize,
+    /// All connections together; least recently seen ones go first.
+    pub memory_budget: usize,
+    /// How often idle and budget checks run (in `mono_us` units).
+    pub sweep_interval_us: u64,
+}
+
+impl Default for CaptureLimits {
+    fn default() -> Self {
+        Self {
+            idle_timeout_us: 300 * 1_000_000,
+            max_conn_bytes: 64 << 20,
+            memory_budget: 512 << 20,
+            sweep_interval_us: 1_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CaptureStats {
+    pub packets: u64,
+    /// Connections currently tracked.
+    pub connections: usize,
+    /// Bytes held by tracked connections at the last sweep.
+    pub buffered_bytes: usize,
+    /// Closed by FIN on both sides or RST.
+    pub closed: u64,
+    pub evicted_idle: u64,
+    pub evicted_oversize: u64,
+    pub evicted_memory: u64,
+    /// Buffers dropped because they did not parse as HTTP/1.x.
+    pub parse_failures: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CaptureCounters {
+    pub packets: AtomicU64,
+    pub closed: AtomicU64,
+    pub evicted_idle: AtomicU64,
+    pub evicted_oversize: AtomicU64,
+    pub evicted_memory: AtomicU64,
+    pub parse_failures: AtomicU64,
+    pub buffered_bytes: AtomicUsize,
+    pub last_sweep_us: AtomicU64,
+}
+
+pub(crate) fn bump(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+impl CaptureCounters {
+    pub fn snapshot(&self, connections: usize) -> CaptureStats {
+        let get = |c: &AtomicU64| c.load(Ordering::Relaxed);
+        CaptureStats {
+            packets: get(&self.packets),
+            connections,
+            buffered_bytes: self.buffered_bytes.load(Ordering::Relaxed),
+            closed: get(&self.closed),
+            evicted_idle: get(&self.evicted_idle),
+            evicted_oversize: get(&self.evicted_oversize),
+            evicted_memory: get(&self.evicted_memory),
+            parse_failures: get(&self.parse_failures),
+        }
+    }
+
+    pub fn reset(&self) {
+        for c in [
+            &self.packets,
+            &self.closed,
+            &self.evicted_idle,
+            &self.evicted_oversize,
+            &self.evicted_memory,
+            &self.parse_failures,
+            &self.last_sweep_us,
+        ] {
+            c.store(0, Ordering::Relaxed);
+        }
+        self.buffered_bytes.store(0, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,1136 @@
+pub(crate) mod framing;
+mod limits;
+mod reassembly;
+#[cfg(test)]
+mod tests;
+
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::yield_now;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::events::{CaptureEvent, EventSink, FanoutSink, SharedSink};
+use crate::http_shared::{
+    Header, HttpRequestEvent, HttpResponseEvent, ResponseCompleteEvent, gen_id, mono_us,
+    now_rfc3339,
+};
+use crate::llm::ExchangeTracker;
+use crate::llm_rules::{LlmRules, load_llm_rules};
+use crate::process_lookup::{ConnKey, cached_process_info, try_lookup_process};
+use base64::{Engine as _, engine::general_purpose};
+use dashmap::DashMap;
+use etherparse::{InternetSlice, SlicedPacket, TransportSlice};
+use framing::{BodyDecoder, BodyFraming, InvalidChunk, request_framing, response_framing};
+pub use limits::CaptureStats;
+use limits::{CaptureCounters, CaptureLimits, bump};
+use once_cell::sync::Lazy;
+use pcap::Error as PcapError;
+use pcap::{Activated, Active, Capture, Device, Linktype};
+use reassembly::{Segment, StreamReassembler};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("pcap error: {0}")]
+    Pcap(String),
+    #[error("device not found: {0}")]
+    DeviceNotFound(String),
+    #[error("invalid capture filter `{0}`: {1}")]
+    Filter(String, String),
+}
+
+/// What `start_capture` listens to. Unset fields fall back to the defaults:
+/// TCP on the LLM ports, 65535-byte snaplen, promiscuous mode.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CaptureOptions {
+    /// Server ports to capture; an empty list means every TCP port.
+    #[serde(default)]
+    pub ports: Option<Vec<u16>>,
+    /// Only traffic to or from these hosts.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Raw BPF expression; replaces the filter built from `ports` and `hosts`.
+    #[serde(default)]
+    pub bpf: Option<String>,
+    #[serde(default)]
+    pub snaplen: Option<i32>,
+    #[serde(default)]
+    pub promisc: Option<bool>,
+}
+
+// Ollama, LM Studio and plain HTTP, on top of the ports named in llm_rules.json
+const DEFAULT_CAPTURE_PORTS: [u16; 4] = [11434, 1234, 80, 8080];
+
+fn capture_filter(opts: &CaptureOptions, llm_rules: &LlmRules) -> String {
+    if let Some(bpf) = opts.bpf.as_deref().map(str::trim)
+        && !bpf.is_empty()
+    {
+        return bpf.to_string();
+    }
+    let mut ports = match &opts.ports {
+        Some(ports) => ports.clone(),
+        None => [llm_rules.ports().as_slice(), &DEFAULT_CAPTURE_PORTS].concat(),
+    };
+    ports.sort_unstable();
+    ports.dedup();
+    let ports: Vec<String> = ports.iter().map(|p| format!("port {p}")).collect();
+    let hosts: Vec<String> = opts.hosts.iter().map(|h| format!("host {h}")).collect();
+    let mut filter = String::from("tcp");
+    for terms in [ports, hosts] {
+        if !terms.is_empty() {
+            filter.push_str(&format!(" and ({})", terms.join(" or ")));
+        }
+    }
+    filter
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterfaceInfo {
+    pub name: String,
+    pub desc: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ConnectionKey {
+    a: String,
+    b: String,
+}
+
+impl ConnectionKey {
+    fn new(src_ip: &str, src_port: u16, dst_ip: &str, dst_port: u16) -> Self {
+        let left = format!("{}:{}", src_ip, src_port);
+        let right = format!("{}:{}", dst_ip, dst_port);
+        if left <= right {
+            Self { a: left, b: right }
+        } else {
+            Self { a: right, b: left }
+        }
+    }
+
+    // index into `ConnectionBuffers::streams` for data sent from this endpoint
+    fn side(&self, ip: &str, port: u16) -> usize {
+        usize::from(self.a != format!("{}:{}", ip, port))
+    }
+}
+
+impl std::hash::Hash for ConnectionKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.a.hash(state);
+        self.b.hash(state);
+    }
+}
+
+impl PartialEq for ConnectionKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.a == other.a && self.b == other.b
+    }
+}
+
+impl Eq for ConnectionKey {}
+
+#[derive(Debug, Default)]
+struct ConnectionBuffers {
+    req_buf: Vec<u8>,
+    resp_buf: Vec<u8>,
+    pending_requests: VecDeque<PendingRequest>,
+    client_endpoint: Option<(String, u16)>,
+    server_endpoint: Option<(String, u16)>,
+    // Cache client-side process info to avoid repeated lookups on the same connection
+    client_process_name: Option<String>,
+    client_pid: Option<i32>,
+    // response whose body is still arriving
+    response: Option<ResponseInProgress>,
+    // after `101 Switching Protocols` or a CONNECT tunnel the bytes are not HTTP
+    upgraded: bool,
+    // TCP reassembly per direction, indexed by `ConnectionKey::side`
+    streams: [StreamReassembler; 2],
+    last_seen_us: u64,
+}
+
+// Request still waiting for its response, in pipeline order.
+#[derive(Debug)]
+struct PendingRequest {
+    id: String,
+    method: String,
+    llm_provider: Option<String>,
+}
+
+#[derive(Debug)]
+struct ResponseInProgress {
+    // head event; streamed body pieces are sent as copies of it
+    evt: HttpResponseEvent,
+    body: BodyDecoder,
+    // streamed responses emit each decoded piece, others one event with the full body
+    streaming: bool,
+    collected: Vec<u8>,
+    // response to a request the capture never saw: framed but not emitted
+    orphan: bool,
+}
+
+impl ResponseInProgress {
+    fn chunk_event(&self, data: &[u8], at: &PacketTime) -> HttpResponseEvent {
+        let mut evt = self.evt.clone();
+        evt.timestamp = at.wall.clone();
+        evt.mono_us = Some(at.mono_us);
+        evt.body_base64 = Some(general_purpose::STANDARD.encode(data));
+        evt.body_len = data.len();
+        evt
+    }
+}
+
+impl ConnectionBuffers {
+    fn buffered_bytes(&self) -> usize {
+        self.req_buf.len()
+            + self.resp_buf.len()
+            + self.response.as_ref().map_or(0, |r| r.collected.len())
+            + self
+                .streams
+                .iter()
+                .map(|s| s.pending_bytes())
+                .sum::<usize>()
+    }
+}
+
+static CONNECTIONS: Lazy<DashMap<ConnectionKey, ConnectionBuffers>> = Lazy::new(|| DashMap::new());
+
+static CAPTURE_THREAD: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+static CAPTURE_RUNNING: AtomicBool = AtomicBool::new(false);
+static LIVE_STATS: Lazy<CaptureCounters> = Lazy::new(CaptureCounters::default);
+
+// Process lookup cache and logic are centralized in `process_lookup.rs`
+
+// LLM rules parsing lives in crate::llm_rules; capture.rs only consumes the shared API.
+
+pub fn list_network_interfaces() -> Result<Vec<NetworkInterfaceInfo>, CaptureError> {
+    let devices = Device::list().map_err(|e| CaptureError::Pcap(e.to_string()))?;
+    Ok(devices
+        .into_iter()
+        .filter_map(|d| {
+            // prefer first IPv4 if present, otherwise first address
+            let ip = if let Some(v4) = d.addresses.iter().find_map(|a| match a.addr {
+                IpAddr::V4(v4) => Some(v4.to_string()),
+                _ => None,
+            }) {
+                Some(v4)
+            } else if let Some(any_ip) = d.addresses.iter().map(|a| a.addr.to_string()).next() {
+                Some(any_ip)
+            } else {
+                None
+            };
+
+            match ip {
+                Some(ip) => Some(NetworkInterfaceInfo {
+                    name: d.name,
+                    desc: d.desc,
+                    ip: Some(ip),
+                }),
+                // Linux pseudo-device capturing on every interface at once
+                None if d.name == "any" => Some(NetworkInterfaceInfo {
+                    name: d.name,
+                    desc: d.desc,
+                    ip: None,
+                }),
+                None => None, // hide interfaces without IP
+            }
+        })
+        .collect())
+}
+
+fn get_linktype<T: Activated + ?Sized>(cap: &Capture<T>) -> Linktype {
+    cap.get_datalink()
+}
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+// Ethernet frames are returned whole (VLAN tags are handled by etherparse);
+// every other linktype is stripped down to the IP header.
+fn extract_l3_payload<'a>(linktype: Linktype, data: &'a [u8]) -> Option<&'a [u8]> {
+    let ip_version = data.first().map(|b| b >> 4);
+    match linktype {
+        // DLT_EN10MB (Ethernet)
+        Linktype(1) => Some(data),
+        // DLT_NULL / DLT_LOOP: 4-byte address family
+        Linktype(0) | Linktype(108) => data.get(4..),
+        // 12 is DLT_RAW almost everywhere but DLT_LOOP on OpenBSD
+        Linktype(12) if !matches!(ip_version, Some(4 | 6)) => data.get(4..),
+        // DLT_RAW (14 on OpenBSD), LINKTYPE_RAW, LINKTYPE_IPV4/IPV6: tun, wireguard
+        Linktype(12) | Linktype(14) | Linktype(101) | Linktype(228) | Linktype(229) => Some(data),
+        // LINUX_SLL (`any` device): 16-byte header, protocol in the last two bytes
+        Linktype(113) => ip_by_ethertype(data.get(14..16)?, data.get(16..)?),
+        // LINUX_SLL2: 20-byte header, protocol first
+        Linktype(276) => ip_by_ethertype(data.get(..2)?, data.get(20..)?),
+        _ => Some(data),
+    }
+}
+
+fn ip_by_ethertype<'a>(proto: &[u8], payload: &'a [u8]) -> Option<&'a [u8]> {
+    match u16::from_be_bytes([proto[0], proto[1]]) {
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => Some(payload),
+        _ => None,
+    }
+}
+
+// One direction of a TCP connection, as seen on a packet.
+#[derive(Debug, Clone)]
+struct Flow {
+    src_ip: String,
+    src_port: u16,
+    dst_ip: String,
+    dst_port: u16,
+}
+
+impl Flow {
+    fn reversed(&self) -> Flow {
+        Flow {
+            src_ip: self.dst_ip.clone(),
+            src_port: self.dst_port,
+            dst_ip: self.src_ip.clone(),
+            dst_port: self.src_port,
+        }
+    }
+}
+
+struct TcpPacket {
+    flow: Flow,
+    seq: u32,
+    // acknowledgment number when the ACK flag is set
+    ack: Option<u32>,
+    syn: bool,
+    fin: bool,
+    rst: bool,
+    payload: Vec<u8>,
+}
+
+fn parse_tcp_packet(l3: &[u8], linktype: Linktype) -> Option<TcpPacket> {
+    // IPv6 extension headers (hop-by-hop, routing, destination options, ...)
+    // are walked by etherparse; fragments carry no TCP header and are skipped
+    let sliced = if linktype == Linktype(1) {
+        SlicedPacket::from_ethernet(l3).ok()?
+    } else {
+        SlicedPacket::from_ip(l3).ok()?
+    };
+
+    let (src_ip, dst_ip) = match sliced.net {
+        Some(InternetSlice::Ipv4(h)) => (
+            IpAddr::V4(Ipv4Addr::from(h.header().source())),
+            IpAddr::V4(Ipv4Addr::from(h.header().destination())),
+        ),
+        Some(InternetSlice::Ipv6(h)) => (
+            IpAddr::V6(Ipv6Addr::from(h.header().source())),
+            IpAddr::V6(Ipv6Addr::from(h.header().destination())),
+        ),
+        _ => return None,
+    };
+    let Some(TransportSlice::Tcp(tcp)) = sliced.transport else {
+        return None;
+    };
+    Some(TcpPacket {
+        flow: Flow {
+            src_ip: src_ip.to_string(),
+            src_port: tcp.source_port(),
+            dst_ip: dst_ip.to_string(),
+            dst_port: tcp.destination_port(),
+        },
+        seq: tcp.sequence_number(),
+        ack: tcp.ack().then(|| tcp.acknowledgment_number()),
+        syn: tcp.syn(),
+        fin: tcp.fin(),
+        rst: tcp.rst(),
+        payload: tcp.payload().to_vec(),
+    })
+}
+
+fn guess_is_request_from_prefix(payload: &[u8]) -> Option<bool> {
+    let max = payload.len().min(64);
+    let line_end = payload[..max]
+        .iter()
+        .position(|&b| b == b'\n')
+        .unwrap_or(max);
+    let head = std::str::from_utf8(&payload[..line_end])
+        .ok()?
+        .trim_start_matches(['\r', '\n'])
+        .trim_start();
+    if head.starts_with("HTTP/") {
+        return Some(false);
+    }
+    const METHODS: [&str; 9] = [
+        "GET ", "POST ", "PUT ", "DELETE ", "HEAD ", "OPTIONS ", "PATCH ", "CONNECT ", "TRACE ",
+    ];
+    if METHODS.iter().any(|m| head.starts_with(m)) {
+        return Some(true);
+    }
+    None
+}
+
+// Buffered bytes that can't be an HTTP/1.x message; the buffer is dropped.
+#[derive(Debug)]
+struct NotHttp;
+
+impl From<httparse::Error> for NotHttp {
+    fn from(_: httparse::Error) -> Self {
+        NotHttp
+    }
+}
+
+impl From<InvalidChunk> for NotHttp {
+    fn from(_: InvalidChunk) -> Self {
+        NotHttp
+    }
+}
+
+// Ok(None) while the message is incomplete; Err when the bytes are not HTTP/1.x.
+fn parse_http_request(
+    buf: &[u8],
+    timestamp: &str,
+) -> Result<Option<(usize, HttpRequestEvent)>, NotHttp> {
+    // Use a larger header buffer to avoid dropping headers in verbose clients
+    let mut headers = [httparse::EMPTY_HEADER; 256];
+    let mut req = httparse::Request::new(&mut headers);
+    let status = req.parse(buf)?;
+    if !status.is_complete() {
+        return Ok(None);
+    }
+    let header_len = status.unwrap();
+    // always set once parsing is complete
+    let method = req.method.unwrap_or_default().to_string();
+    let path = req.path.unwrap_or_default().to_string();
+    let version = format!("1.{}", req.version.unwrap_or(1));
+    let headers_vec: Vec<Header> = req
+        .headers
+        .iter()
+        .map(|h| Header {
+            name: h.name.to_string(),
+            value: String::from_utf8_lossy(h.value).to_string(),
+        })
+        .collect();
+    // requests are emitted whole: wait until the body is complete
+    let mut body = Vec::new();
+    let consumed = match request_framing(&headers_vec) {
+        BodyFraming::Length(n) => {
+            if buf.len() < header_len + n {
+                return Ok(None);
+            }
+            body.extend_from_slice(&buf[header_len..header_len + n]);
+            header_len + n
+        }
+        BodyFraming::Chunked => {
+            let mut dec = BodyDecoder::new(BodyFraming::Chunked);
+            let used = dec.decode(&buf[header_len..], &mut body)?;
+            if !dec.is_done() {
+                return Ok(None);
+            }
+            header_len + used
+        }
+        BodyFraming::None | BodyFraming::Close => header_len,
+    };
+    let body_slice = body.as_slice();
+    let body_b64 = if !body_slice.is_empty() {
+        Some(general_purpose::STANDARD.encode(body_slice))
+    } else {
+        None
+    };
+    let evt = HttpRequestEvent {
+        id: gen_id(),
+        timestamp: timestamp.to_string(),
+        src_ip: String::new(),
+        src_port: 0,
+        dst_ip: String::new(),
+        dst_port: 0,
+        method,
+        path,
+        version,
+        headers: headers_vec,
+        body_base64: body_b64,
+        body_len: body_slice.len(),
+        process_name: None,
+        pid: None,
+        process: None,
+        // 是否算 LLM 流量由调用方按 llm_rules 判定
+        is_llm: false,
+        llm_provider: None,
+        mono_us: None,
+        conn_id: None,
+        conn_seq: None,
+    };
+    Ok(Some((consumed, evt)))
+}
+
+// Parses a response head only; the body is framed by the caller, which knows
+// the request method. Returns the head length.
+fn parse_http_response(
+    buf: &[u8],
+    timestamp: &str,
+) -> Result<Option<(usize, HttpResponseEvent)>, NotHttp> {
+    // Use a larger header buffer to avoid dropping headers
+    let mut headers = [httparse::EMPTY_HEADER; 256];
+    let mut resp = httparse::Response::new(&mut headers);
+    let status = resp.parse(buf)?;
+    if !status.is_complete() {
+        return Ok(None);
+    }
+    let header_len = status.unwrap();
+    let code = resp.code.unwrap_or_default();
+    let version = format!("1.{}", resp.version.unwrap_or(1));
+    let headers_vec = resp
+        .headers
+        .iter()
+        .map(|h| Header {
+            name: h.name.to_string(),
+            value: String::from_utf8_lossy(h.value).to_string(),
+        })
+        .collect();
+    let evt = HttpResponseEvent {
+        id: String::new(),
+        timestamp: timestamp.to_string(),
+        src_ip: String::new(),
+        src_port: 0,
+        dst_ip: String::new(),
+        dst_port: 0,
+        status_code: code,
+        reason: resp.reason.filter(|r| !r.is_empty()).map(str::to_string),
+        version,
+        headers: headers_vec,
+        body_base64: None,
+        body_len: 0,
+        process_name: None,
+        pid: None,
+        process: None,
+        is_llm: false,
+        llm_provider: None,
+        mono_us: None,
+    };
+    Ok(Some((header_len, evt)))
+}
+
+fn enrich_req_with_endpoints(
+    mut evt: HttpRequestEvent,
+    src_ip: &str,
+    src_port: u16,
+    dst_ip: &str,
+    dst_port: u16,
+) -> HttpRequestEvent {
+    evt.src_ip = src_ip.to_string();
+    evt.src_port = src_port;
+    evt.dst_ip = dst_ip.to_string();
+    evt.dst_port = dst_port;
+    evt
+}
+
+fn enrich_resp_with_endpoints(
+    mut evt: HttpResponseEvent,
+    src_ip: &str,
+    src_port: u16,
+    dst_ip: &str,
+    dst_port: u16,
+) -> HttpResponseEvent {
+    evt.src_ip = src_ip.to_string();
+    evt.src_port = src_port;
+    evt.dst_ip = dst_ip.to_string();
+    evt.dst_port = dst_port;
+    evt
+}
+
+// Where dissected events go and which state they use. Live capture and offline
+// files share the same path with their own connection tables.
+struct PacketContext<'a> {
+    sink: &'a dyn EventSink,
+    llm_rules: &'a LlmRules,
+    connections: &'a DashMap<ConnectionKey, ConnectionBuffers>,
+    limits: &'a CaptureLimits,
+    stats: &'a CaptureCounters,
+    // 离线文件的四元组与本机进程无关，不做进程归属
+    process_lookup: bool,
+}
+
+impl PacketContext<'_> {
+    // 进程归属按客户端一侧的四元组查找：local 为客户端地址
+    fn lookup_process(
+        &self,
+        local_ip: &str,
+        local_port: u16,
+        remote_ip: &str,
+        remote_port: u16,
+        is_server_side: bool,
+    ) -> (Option<String>, Option<i32>) {
+        if !self.process_lookup {
+            return (None, None);
+        }
+        match ConnKey::parse(local_ip, local_port, remote_ip, remote_port) {
+            Some(key) => try_lookup_process(key, is_server_side),
+            None => (None, None),
+        }
+    }
+
+    fn emit_request(&self, evt: HttpRequestEvent) {
+        self.sink.emit(&CaptureEvent::Request(evt));
+    }
+
+    fn emit_response(&self, evt: HttpResponseEvent) {
+        self.sink.emit(&CaptureEvent::Response(evt));
+    }
+
+    // Drops a connection, completing any stream it still had open.
+    fn evict(&self, key: &ConnectionKey, timestamp: &str) {
+        if let Some((_, mut state)) = self.connections.remove(key) {
+            finish_response(self, &mut state, timestamp);
+        }
+    }
+
+    // Idle timeout and global memory budget, checked once per sweep interval.
+    fn maybe_sweep(&self, at: &PacketTime) {
+        let last = self.stats.last_sweep_us.load(Ordering::Relaxed);
+        if last != 0 && at.mono_us.saturating_sub(last) < self.limits.sweep_interval_us {
+            return;
+        }
+        self.stats
+            .last_sweep_us
+            .store(at.mono_us, Ordering::Relaxed);
+        let mut live: Vec<(ConnectionKey, u64, usize)> = self
+            .connections
+            .iter()
+            .map(|e| (e.key().clone(), e.last_seen_us, e.buffered_bytes()))
+            .collect();
+        let mut total: usize = live.iter().map(|c| c.2).sum();
+        // least recently seen first
+        live.sort_by_key(|c| c.1);
+        for (key, seen, bytes) in live {
+            let counter = if at.mono_us.saturating_sub(seen) >= self.limits.idle_timeout_us {
+                &self.stats.evicted_idle
+            } else if total > self.limits.memory_budget {
+                &self.stats.evicted_memory
+            } else {
+                break;
+            };
+            self.evict(&key, &at.wall);
+            bump(counter);
+            total -= bytes;
+        }
+        self.stats.buffered_bytes.store(total, Ordering::Relaxed);
+    }
+
+    fn emit_complete(&self, id: &str, timestamp: &str, trailers: Vec<Header>) {
+        self.sink
+            .emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent {
+                id: id.to_string(),
+                timestamp: timestamp.to_string(),
+                usage: None,
+                timings: None,
+                trailers,
+            }));
+    }
+}
+
+// pcap stores wall-clock seconds + microseconds; field widths differ per platform
+#[allow(clippy::unnecessary_cast)]
+fn packet_timestamp(header: &pcap::PacketHeader) -> PacketTime {
+    let micros = header.ts.tv_sec as i128 * 1_000_000 + header.ts.tv_usec as i128;
+    PacketTime {
+        wall: OffsetDateTime::from_unix_timestamp_nanos(micros * 1000)
+            .ok()
+            .and_then(|t| t.format(&Rfc3339).ok())
+            .unwrap_or_else(now_rfc3339),
+        mono_us: micros.max(0) as u64,
+    }
+}
+
+// When a packet was seen: wall clock for display, `mono_us` for latency math
+// (process clock when live, packet time when reading a file).
+struct PacketTime {
+    wall: String,
+    mono_us: u64,
+}
+
+impl PacketTime {
+    fn now() -> Self {
+        Self {
+            wall: now_rfc3339(),
+            mono_us: mono_us(),
+        }
+    }
+}
+
+fn process_packet(ctx: &PacketContext<'_>, linktype: Linktype, data: &[u8], at: &PacketTime) {
+    let Some(l3) = extract_l3_payload(linktype, data) else {
+        return;
+    };
+    let Some(pkt) = parse_tcp_packet(l3, linktype) else {
+        return;
+    };
+    bump(&ctx.stats.packets);
+    let flow = &pkt.flow;
+    let key = ConnectionKey::new(&flow.src_ip, flow.src_port, &flow.dst_ip, flow.dst_port);
+    let side = key.side(&flow.src_ip, flow.src_port);
+    let mut state = ctx.connections.entry(key.clone()).or_default();
+    state.last_seen_us = at.mono_us;
+    // a bare SYN tells us who the client is
+    if pkt.syn && pkt.ack.is_none() {
+        state
+            .client_endpoint
+            .get_or_insert((flow.src_ip.clone(), flow.src_port));
+        state
+            .server_endpoint
+            .get_or_insert((flow.dst_ip.clone(), flow.dst_port));
+    }
+    if pkt.rst {
+        drop(state);
+        ctx.evict(&key, &at.wall);
+        bump(&ctx.stats.closed);
+        return;
+    }
+    // this ACK may release the other direction past bytes the capture lost
+    if let Some(ack) = pkt.ack {
+        let released = state.streams[1 - side].on_peer_ack(ack);
+        if !released.is_empty() {
+            handle_stream_data(ctx, &mut state, &flow.reversed(), &released, at);
+        }
+    }
+    let data = state.streams[side].push(&Segment {
+        seq: pkt.seq,
+        syn: pkt.syn,
+        fin: pkt.fin,
+        payload: &pkt.payload,
+    });
+    if !data.is_empty() {
+        handle_stream_data(ctx, &mut state, flow, &data, at);
+    }
+    // server FIN ends a close-delimited body; drop the entry once both sides closed
+    if state.streams[side].is_finished()
+        && state
+            .server_endpoint
+            .as_ref()
+            .is_some_and(|(ip, port)| *ip == flow.src_ip && *port == flow.src_port)
+    {
+        finish_response(ctx, &mut state, &at.wall);
+    }
+    let closed = state.streams.iter().all(|s| s.is_finished());
+    let oversize = state.buffered_bytes() > ctx.limits.max_conn_bytes;
+    drop(state);
+    if closed {
+        ctx.evict(&key, &at.wall);
+        bump(&ctx.stats.closed);
+    } else if oversize {
+        ctx.evict(&key, &at.wall);
+        bump(&ctx.stats.evicted_oversize);
+    }
+    ctx.maybe_sweep(at);
+}
+
+// The connection is going away: whatever body arrived so far is the whole response.
+fn finish_response(ctx: &PacketContext<'_>, state: &mut ConnectionBuffers, timestamp: &str) {
+    if let Some(resp) = state.response.take() {
+        complete_response(ctx, resp, timestamp);
+    }
+    state.resp_buf.clear();
+}
+
+// End of message: a buffered body goes out in one event, then the completion.
+fn complete_response(ctx: &PacketContext<'_>, resp: ResponseInProgress, timestamp: &str) {
+    if resp.orphan {
+        return;
+    }
+    let id = resp.evt.id.clone();
+    if !resp.streaming {
+        let mut evt = resp.evt;
+        if !resp.collected.is_empty() {
+            evt.body_base64 = Some(general_purpose::STANDARD.encode(&resp.collected));
+        }
+        evt.body_len = resp.collected.len();
+        ctx.emit_response(evt);
+    }
+    ctx.emit_complete(&id, timestamp, resp.body.trailers);
+}
+
+// Handles in-order bytes of one direction after reassembly.
+fn handle_stream_data(
+    ctx: &PacketContext<'_>,
+    state: &mut ConnectionBuffers,
+    flow: &Flow,
+    payload: &[u8],
+    at: &PacketTime,
+) {
+    let timestamp = at.wall.as_str();
+    let (src_ip, src_port, dst_ip, dst_port) = (
+        flow.src_ip.clone(),
+        flow.src_port,
+        flow.dst_ip.clone(),
+        flow.dst_port,
+    );
+    // Prefer direction by known endpoints; fallback to payload prefix guess
+    let dir_is_req = if let (Some(client), Some(server)) =
+        (&state.client_endpoint, &state.server_endpoint)
+    {
+        if src_ip == server.0 && src_port == server.1 && dst_ip == client.0 && dst_port == client.1
+        {
+            false
+        } else if src_ip == client.0
+            && src_port == client.1
+            && dst_ip == server.0
+            && dst_port == server.1
+        {
+            true
+        } else {
+            guess_is_request_from_prefix(payload).unwrap_or(true)
+        }
+    } else {
+        guess_is_request_from_prefix(payload).unwrap_or(true)
+    };
+    if dir_is_req {
+        state
+            .client_endpoint
+            .get_or_insert((src_ip.clone(), src_port));
+        state
+            .server_endpoint
+            .get_or_insert((dst_ip.clone(), dst_port));
+        state.req_buf.extend_from_slice(payload);
+        loop {
+            if state.upgraded {
+                state.req_buf.clear();
+                break;
+            }
+            let (consumed, mut evt) = match parse_http_request(&state.req_buf, timestamp) {
+                Ok(Some(parsed)) => parsed,
+                Ok(None) => break,
+                Err(_) => {
+                    bump(&ctx.stats.parse_failures);
+                    state.req_buf.clear();
+                    break;
+                }
+            };
+            evt.mono_us = Some(at.mono_us);
+            // Prefer cached client process info on the connection
+            if state.client_process_name.is_none() && state.client_pid.is_none() {
+                let (pname0, pid0) =
+                    ctx.lookup_process(&src_ip, src_port, &dst_ip, dst_port, false);
+                state.client_process_name = pname0;
+                state.client_pid = pid0;
+            }
+            evt = enrich_req_with_endpoints(evt, &src_ip, src_port, &dst_ip, dst_port);
+            // Apply request rules
+            if let Some(provider) = ctx.llm_rules.match_request(&evt) {
+                evt.is_llm = true;
+                evt.llm_provider = Some(provider.clone());
+            }
+            state.pending_requests.push_back(PendingRequest {
+                id: evt.id.clone(),
+                method: evt.method.clone(),
+                llm_provider: evt.llm_provider.clone(),
+            });
+            evt.process_name = state.client_process_name.clone();
+            evt.pid = state.client_pid;
+            evt.process = cached_process_info(evt.pid);
+            if consumed <= state.req_buf.len() {
+                state.req_buf.drain(0..consumed);
+            } else {
+                state.req_buf.clear();
+            }
+            ctx.emit_request(evt);
+        }
+    } else {
+        state
+            .client_endpoint
+            .get_or_insert((dst_ip.clone(), dst_port));
+        state
+            .server_endpoint
+            .get_or_insert((src_ip.clone(), src_port));
+        state.resp_buf.extend_from_slice(payload);
+        loop {
+            if let Some(resp) = state.response.as_mut() {
+                let mut data = Vec::new();
+                match resp.body.decode(&state.resp_buf, &mut data) {
+                    Ok(used) => {
+                        state.resp_buf.drain(..used);
+                    }
+                    Err(InvalidChunk) => {
+                        bump(&ctx.stats.parse_failures);
+                        finish_response(ctx, state, timestamp);
+                        break;
+                    }
+                }
+                if !data.is_empty() && !resp.orphan {
+                    // If provider unknown yet, try textual match on the body
+                    if !resp.evt.is_llm
+                        && let Ok(text) = std::str::from_utf8(&data)
+                        && let Some(provider) = ctx.llm_rules.match_text_only(text)
+                    {
+                        resp.evt.is_llm = true;
+                        resp.evt.llm_provider = Some(provider.clone());
+                    }
+                    if resp.streaming {
+                        ctx.emit_response(resp.chunk_event(&data, at));
+                    } else {
+                        resp.collected.extend_from_slice(&data);
+                    }
+                }
+                if !resp.body.is_done() {
+                    break;
+                }
+                // keep-alive: the next response head may already be buffered
+                if let Some(resp) = state.response.take() {
+                    complete_response(ctx, resp, timestamp);
+                }
+                continue;
+            }
+            if state.upgraded {
+                state.resp_buf.clear();
+                break;
+            }
+            let (header_len, mut evt) = match parse_http_response(&state.resp_buf, timestamp) {
+                Ok(Some(parsed)) => parsed,
+                Ok(None) => break,
+                Err(_) => {
+                    bump(&ctx.stats.parse_failures);
+                    state.resp_buf.clear();
+                    break;
+                }
+            };
+            state.resp_buf.drain(..header_len);
+            evt.mono_us = Some(at.mono_us);
+            // interim 1xx (100 Continue, 103 Early Hints) precede the final response
+            if (100..200).contains(&evt.status_code) && evt.status_code != 101 {
+                continue;
+            }
+            let req = state.pending_requests.pop_front();
+            let method = req.as_ref().map(|r| r.method.as_str());
+            let framing = response_framing(method, evt.status_code, &evt.headers);
+            if evt.status_code == 101
+                || (method == Some("CONNECT") && (200..300).contains(&evt.status_code))
+            {
+                state.upgraded = true;
+            }
+            let orphan = req.is_none();
+            if let Some(req) = req {
+                evt.id = req.id;
+                // Prefer request side decision, but also try response rules
+                evt.is_llm = req.llm_provider.is_some();
+                evt.llm_provider = req.llm_provider;
+            }
+            // Response direction prefers server side; but reuse cached
+            let (pname, pid) = if state.client_process_name.is_some() || state.client_pid.is_some()
+            {
+                (state.client_process_name.clone(), state.client_pid)
+            } else {
+                ctx.lookup_process(&dst_ip, dst_port, &src_ip, src_port, true)
+            };
+            evt = enrich_resp_with_endpoints(evt, &src_ip, src_port, &dst_ip, dst_port);
+            evt.process_name = pname;
+            evt.pid = pid;
+            evt.process = cached_process_info(evt.pid);
+            if !evt.is_llm
+                && let Some(provider) = ctx.llm_rules.match_response(&evt)
+            {
+                evt.is_llm = true;
+                evt.llm_provider = Some(provider.clone());
+            }
+            // SSE, chunked (e.g. NDJSON) and close-delimited bodies are shown as they arrive
+            let is_sse = evt.headers.iter().any(|h| {
+                h.name.eq_ignore_ascii_case("content-type")
+                    && h.value.to_ascii_lowercase().contains("text/event-stream")
+            });
+            let streaming = is_sse || matches!(framing, BodyFraming::Chunked | BodyFraming::Close);
+            if streaming && !orphan {
+                ctx.emit_response(evt.clone());
+            }
+            state.response = Some(ResponseInProgress {
+                evt,
+                body: BodyDecoder::new(framing),
+                streaming,
+                collected: Vec::new(),
+                orphan,
+            });
+        }
+    }
+}
+
+pub fn start_capture(
+    sink: SharedSink,
+    iface: &str,
+    opts: &CaptureOptions,
+) -> Result<(), CaptureError> {
+    if CAPTURE_RUNNING.swap(true, Ordering::SeqCst) {
+        return Ok(()); // already running
+    }
+
+    // Load LLM rules once at start
+    let llm_rules = load_llm_rules();
+
+    let mut cap = match open_device(iface, opts, &llm_rules) {
+        Ok(cap) => cap,
+        Err(e) => {
+            CAPTURE_RUNNING.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+
+    let linktype = get_linktype(&cap);
+    let llm_rules_for_thread = llm_rules.clone();
+    LIVE_STATS.reset();
+
+    let handle = thread::spawn(move || {
+        let limits = CaptureLimits::default();
+        let ctx = PacketContext {
+            sink: sink.as_ref(),
+            llm_rules: &llm_rules_for_thread,
+            connections: &CONNECTIONS,
+            limits: &limits,
+            stats: &LIVE_STATS,
+            process_lookup: true,
+        };
+        while CAPTURE_RUNNING.load(Ordering::SeqCst) {
+            match cap.next_packet() {
+                Ok(packet) => {
+                    process_packet(&ctx, linktype, packet.data, &PacketTime::now());
+                }
+                Err(err) => match err {
+                    PcapError::NoMorePackets => yield_now(),
+                    PcapError::TimeoutExpired => yield_now(),
+                    _ => std::thread::sleep(Duration::from_millis(1)),
+                },
+            }
+        }
+    });
+
+    {
+        let mut g = CAPTURE_THREAD.lock().unwrap();
+        *g = Some(handle);
+    }
+
+    Ok(())
+}
+
+fn open_device(
+    iface: &str,
+    opts: &CaptureOptions,
+    llm_rules: &LlmRules,
+) -> Result<Capture<Active>, CaptureError> {
+    let device = Device::list()
+        .map_err(|e| CaptureError::Pcap(e.to_string()))?
+        .into_iter()
+        .find(|d| d.name == iface)
+        .ok_or_else(|| CaptureError::DeviceNotFound(iface.to_string()))?;
+
+    let mut cap = Capture::from_device(device)
+        .map_err(|e| CaptureError::Pcap(e.to_string()))?
+        .promisc(opts.promisc.unwrap_or(true))
+        .snaplen(opts.snaplen.unwrap_or(65535))
+        .immediate_mode(true)
+        .open()
+        .map_err(|e| CaptureError::Pcap(e.to_string()))?;
+    // Use non-blocking to allow graceful stop without hanging on next_packet
+    cap = cap
+        .setnonblock()
+        .map_err(|e| CaptureError::Pcap(e.to_string()))?;
+
+    let filter = capture_filter(opts, llm_rules);
+    cap.filter(&filter, true)
+        .map_err(|e| CaptureError::Filter(filter, e.to_string()))?;
+    Ok(cap)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PcapFileSummary {
+    pub packets: u64,
+    pub linktype: i32,
+    pub session_id: Option<String>,
+    pub stats: CaptureStats,
+}
+
+// Saved files default to every TCP port; `ports`, `hosts` and `bpf` narrow it as
+// for live capture.
+fn offline_filter(opts: &CaptureOptions, llm_rules: &LlmRules) -> String {
+    let opts = CaptureOptions {
+        ports: Some(opts.ports.clone().unwrap_or_default()),
+        ..opts.clone()
+    };
+    capture_filter(&opts, llm_rules)
+}
+
+/// Dissects a saved .pcap/.pcapng file through the same pipeline as live capture.
+/// Events keep the original packet timestamps and are recorded into a new session
+/// in addition to `sink`; completed LLM exchanges are derived as for live traffic.
+/// Only the filter fields of `options` apply.
+pub fn analyze_pcap_file(
+    sink: SharedSink,
+    path: &str,
+    options: &CaptureOptions,
+) -> Result<PcapFileSummary, CaptureError> {
+    let llm_rules = load_llm_rules();
+    let mut cap = Capture::from_file(path).map_err(|e| CaptureError::Pcap(e.to_string()))?;
+    let filter = offline_filter(options, &llm_rules);
+    cap.filter(&filter, true)
+        .map_err(|e| CaptureError::Filter(filter.clone(), e.to_string()))?;
+    let linktype = get_linktype(&cap);
+
+    let session = crate::store::STORE.create_session("pcap").ok();
+    let session_id = session.as_ref().map(|w| w.id().to_string());
+    let sink: SharedSink = match session {
+        Some(w) => Arc::new(FanoutSink::new(vec![Arc::new(Mutex::new(w)), sink])),
+        None => sink,
+    };
+    // tracked here so the session file also gets usage and timings
+    let sink: SharedSink = Arc::new(ExchangeTracker::new(sink));
+    let connections = DashMap::new();
+    let limits = CaptureLimits::default();
+    let stats = CaptureCounters::default();
+    let ctx = PacketContext {
+        sink: sink.as_ref(),
+        llm_rules: &llm_rules,
+        connections: &connections,
+        limits: &limits,
+        stats: &stats,
+        process_lookup: false,
+    };
+    let mut packets = 0u64;
+    let mut last_wall = None;
+    loop {
+        match cap.next_packet() {
+            Ok(packet) => {
+                packets += 1;
+                let ts = packet_timestamp(packet.header);
+                process_packet(&ctx, linktype, packet.data, &ts);
+                last_wall = Some(ts.wall);
+            }
+            Err(PcapError::NoMorePackets) => break,
+            Err(e) => return Err(CaptureError::Pcap(e.to_string())),
+        }
+    }
+    // end of file: complete whatever is still open (not counted as evictions)
+    let end = last_wall.unwrap_or_else(now_rfc3339);
+    let open: Vec<ConnectionKey> = connections.iter().map(|e| e.key().clone()).collect();
+    for key in &open {
+        ctx.evict(key, &end);
+    }
+    Ok(PcapFileSummary {
+        packets,
+        linktype: linktype.0,
+        session_id,
+        stats: stats.snapshot(open.len()),
+    })
+}
+
+pub fn stop_capture() {
+    if !CAPTURE_RUNNING.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    if let Some(handle) = CAPTURE_THREAD.lock().unwrap().take() {
+        let _ = handle.join();
+    }
+    CONNECTIONS.clear();
+    crate::process_lookup::clear_process_lookup();
+}
+
+/// Counters of the live capture since it was last started.
+pub fn capture_stats() -> CaptureStats {
+    LIVE_STATS.snapshot(CONNECTIONS.len())
+}
+
+pub fn is_capture_running() -> bool {
+    CAPTURE_RUNNING.load(Ordering::SeqCst)
+}
@@ -0,0 +1,170 @@
+// Per-direction TCP reassembly for the pcap path: orders segments by sequence
+// number, drops duplicates and retransmissions, and tracks FIN.
+
+// Signed distance a - b in sequence space (RFC 1982 style, handles wraparound).
+fn seq_diff(a: u32, b: u32) -> i32 {
+    a.wrapping_sub(b) as i32
+}
+
+pub(crate) struct Segment<'a> {
+    pub seq: u32,
+    pub syn: bool,
+    pub fin: bool,
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StreamReassembler {
+    // Next expected sequence number. Set from the SYN, or from the first data
+    // segment when the capture joined mid-connection.
+    next_seq: Option<u32>,
+    // Segments ahead of `next_seq`, waiting for the hole before them.
+    pending: Vec<(u32, Vec<u8>)>,
+    fin_seq: Option<u32>,
+    finished: bool,
+}
+
+impl StreamReassembler {
+    /// Feeds one segment and returns the bytes that became contiguous.
+    pub fn push(&mut self, seg: &Segment<'_>) -> Vec<u8> {
+        // SYN occupies one sequence number; data (TFO) starts after it
+        let start = if seg.syn {
+            seg.seq.wrapping_add(1)
+        } else {
+            seg.seq
+        };
+        let next = *self.next_seq.get_or_insert(start);
+        if seg.fin {
+            self.fin_seq = Some(start.wrapping_add(seg.payload.len() as u32));
+        }
+        let mut out = Vec::new();
+        if !seg.payload.is_empty() {
+            let d = seq_diff(start, next);
+            if d > 0 {
+                self.pending.push((start, seg.payload.to_vec()));
+            } else {
+                // retransmission: keep only the part past what we already have
+                let skip = d.unsigned_abs() as usize;
+                if skip < seg.payload.len() {
+                    out.extend_from_slice(&seg.payload[skip..]);
+                    self.next_seq = Some(next.wrapping_add((seg.payload.len() - skip) as u32));
+                }
+            }
+        }
+        self.drain_pending(&mut out);
+        out
+    }
+
+    /// The peer acknowledged `ack`. Bytes before it that we never saw were lost by
+    /// the capture (the sender won't retransmit them), so jump over the hole
+    /// instead of buffering forever.
+    pub fn on_peer_ack(&mut self, ack: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        let Some(next) = self.next_seq else {
+            return out;
+        };
+        if seq_diff(ack, next) <= 0 {
+            return out;
+        }
+        let target = self
+            .pending
+            .iter()
+            .map(|(s, _)| *s)
+            .filter(|s| seq_diff(*s, ack) < 0)
+            .min_by_key(|s| seq_diff(*s, next))
+            .unwrap_or(ack);
+        self.next_seq = Some(target);
+        self.drain_pending(&mut out);
+        out
+    }
+
+    fn drain_pending(&mut self, out: &mut Vec<u8>) {
+        let Some(mut next) = self.next_seq else {
+            return;
+        };
+        loop {
+            // segments entirely before `next` are duplicates
+            self.pending
+                .retain(|(s, d)| seq_diff(s.wrapping_add(d.len() as u32), next) > 0);
+            let Some(i) = self
+                .pending
+                .iter()
+                .position(|(s, _)| seq_diff(*s, next) <= 0)
+            else {
+                break;
+            };
+            let (s, d) = self.pending.swap_remove(i);
+            let skip = seq_diff(next, s) as usize;
+            out.extend_from_slice(&d[skip..]);
+            next = next.wrapping_add((d.len() - skip) as u32);
+        }
+        self.next_seq = Some(next);
+        if self.fin_seq.is_some_and(|f| seq_diff(next, f) >= 0) {
+            self.finished = true;
+        }
+    }
+
+    /// Bytes held back waiting for a hole to fill.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending.iter().map(|(_, d)| d.len()).sum()
+    }
+
+    /// FIN seen and every byte before it delivered.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(seq: u32, payload: &[u8]) -> Segment<'_> {
+        Segment {
+            seq,
+            syn: false,
+            fin: false,
+            payload,
+        }
+    }
+
+    #[test]
+    fn reorders_and_drops_duplicates() {
+        let mut r = StreamReassembler::default();
+        let syn = Segment {
+            seq: u32::MAX - 2,
+            syn: true,
+            fin: false,
+            payload: b"",
+        };
+        assert!(r.push(&syn).is_empty());
+        // sequence numbers wrap between the segments
+        let base = u32::MAX - 1;
+        assert!(r.push(&seg(base.wrapping_add(6), b"world")).is_empty());
+        assert_eq!(r.pending.len(), 1);
+        assert_eq!(r.push(&seg(base, b"hello ")), b"hello world");
+        // full and partial retransmissions contribute only new bytes
+        assert!(r.push(&seg(base, b"hello ")).is_empty());
+        assert_eq!(r.push(&seg(base.wrapping_add(8), b"rld!")), b"!");
+
+        let fin = Segment {
+            seq: base.wrapping_add(12),
+            syn: false,
+            fin: true,
+            payload: b"",
+        };
+        assert!(r.push(&fin).is_empty());
+        assert!(r.is_finished());
+    }
+
+    #[test]
+    fn peer_ack_skips_lost_bytes() {
+        let mut r = StreamReassembler::default();
+        // joined mid-stream: first data segment sets the expected sequence
+        assert_eq!(r.push(&seg(1000, b"abc")), b"abc");
+        assert!(r.push(&seg(1010, b"xyz")).is_empty());
+        assert!(r.on_peer_ack(1005).is_empty(), "ack inside the hole");
+        assert_eq!(r.on_peer_ack(1013), b"xyz");
+        assert!(r.pending.is_empty());
+    }
+}
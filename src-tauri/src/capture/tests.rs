@@ -0,0 +1,377 @@
+use super::*;
+use crate::events::tests::CollectSink;
+use etherparse::PacketBuilder;
+
+const CLIENT: [u8; 4] = [10, 0, 0, 1];
+const SERVER: [u8; 4] = [10, 0, 0, 2];
+
+enum Flag {
+    Syn,
+    Fin,
+    Rst,
+    None,
+}
+
+// Raw IPv4/TCP packet; `ack` sets the ACK flag.
+fn packet(from_client: bool, seq: u32, ack: Option<u32>, flag: Flag, payload: &[u8]) -> Vec<u8> {
+    let (src, dst, sport, dport) = if from_client {
+        (CLIENT, SERVER, 50000, 11434)
+    } else {
+        (SERVER, CLIENT, 11434, 50000)
+    };
+    let mut b = PacketBuilder::ipv4(src, dst, 64).tcp(sport, dport, seq, 65535);
+    if let Some(n) = ack {
+        b = b.ack(n);
+    }
+    b = match flag {
+        Flag::Syn => b.syn(),
+        Flag::Fin => b.fin(),
+        Flag::Rst => b.rst(),
+        Flag::None => b,
+    };
+    let mut out = Vec::new();
+    b.write(&mut out, payload).unwrap();
+    out
+}
+
+fn feed(ctx: &PacketContext<'_>, packets: &[Vec<u8>]) {
+    for p in packets {
+        process_packet(ctx, Linktype(101), p, &PacketTime::now());
+    }
+}
+
+fn names(sink: &CollectSink) -> Vec<&'static str> {
+    sink.0.lock().unwrap().iter().map(|e| e.name()).collect()
+}
+
+#[test]
+fn out_of_order_and_duplicate_segments_are_reassembled() {
+    let sink = CollectSink::default();
+    let rules = load_llm_rules();
+    let connections = DashMap::new();
+    let (limits, stats) = (CaptureLimits::default(), CaptureCounters::default());
+    let ctx = PacketContext {
+        sink: &sink,
+        llm_rules: &rules,
+        connections: &connections,
+        limits: &limits,
+        stats: &stats,
+        process_lookup: false,
+    };
+    let req = b"POST /api/chat HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n{}";
+    let (head, tail) = req.split_at(20);
+    let resp = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+    feed(
+        &ctx,
+        &[
+            packet(true, 100, None, Flag::Syn, b""),
+            packet(false, 5000, Some(101), Flag::Syn, b""),
+            packet(true, 121, Some(5001), Flag::None, tail),
+            packet(true, 101, Some(5001), Flag::None, head),
+            packet(true, 101, Some(5001), Flag::None, head),
+            packet(false, 5001, Some(101 + req.len() as u32), Flag::None, resp),
+        ],
+    );
+    assert_eq!(
+        names(&sink),
+        ["onHttpRequest", "onHttpResponse", "onHttpResponseComplete"]
+    );
+    let events = sink.0.lock().unwrap().clone();
+    let CaptureEvent::Request(r) = &events[0] else {
+        panic!("expected request");
+    };
+    assert_eq!(r.path, "/api/chat");
+    assert_eq!(r.body_base64.as_deref(), Some("e30="));
+    assert_eq!((r.src_port, r.dst_port), (50000, 11434));
+
+    let end = 101 + req.len() as u32;
+    feed(
+        &ctx,
+        &[
+            packet(true, end, Some(5001 + resp.len() as u32), Flag::Fin, b""),
+            packet(
+                false,
+                5001 + resp.len() as u32,
+                Some(end + 1),
+                Flag::Fin,
+                b"",
+            ),
+        ],
+    );
+    assert!(connections.is_empty(), "closed connection is dropped");
+}
+
+#[test]
+fn rst_completes_open_stream() {
+    let sink = CollectSink::default();
+    let rules = load_llm_rules();
+    let connections = DashMap::new();
+    let (limits, stats) = (CaptureLimits::default(), CaptureCounters::default());
+    let ctx = PacketContext {
+        sink: &sink,
+        llm_rules: &rules,
+        connections: &connections,
+        limits: &limits,
+        stats: &stats,
+        process_lookup: false,
+    };
+    let req = b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let head = b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\ndata: 1\n\n";
+    feed(
+        &ctx,
+        &[
+            packet(true, 100, Some(7000), Flag::None, req),
+            packet(false, 7000, Some(100 + req.len() as u32), Flag::None, head),
+            packet(false, 7000 + head.len() as u32, None, Flag::Rst, b""),
+        ],
+    );
+    assert_eq!(
+        names(&sink),
+        [
+            "onHttpRequest",
+            "onHttpResponse",
+            // first `data:` chunk
+            "onHttpResponse",
+            "onHttpResponseComplete"
+        ]
+    );
+    assert!(connections.is_empty());
+}
+
+#[test]
+fn limits_evict_and_count() {
+    let sink = CollectSink::default();
+    let rules = load_llm_rules();
+    let connections = DashMap::new();
+    let limits = CaptureLimits {
+        max_conn_bytes: 64,
+        ..CaptureLimits::default()
+    };
+    let stats = CaptureCounters::default();
+    let ctx = PacketContext {
+        sink: &sink,
+        llm_rules: &rules,
+        connections: &connections,
+        limits: &limits,
+        stats: &stats,
+        process_lookup: false,
+    };
+    // not HTTP: dropped and counted instead of buffered forever
+    feed(
+        &ctx,
+        &[packet(
+            true,
+            100,
+            None,
+            Flag::None,
+            b"GET /\x01\x02 junk\r\n\r\n",
+        )],
+    );
+    // a request head that never completes outgrows the per-connection cap
+    let big = format!(
+        "POST /v1/chat/completions HTTP/1.1\r\nX-Pad: {}",
+        "a".repeat(80)
+    );
+    feed(&ctx, &[packet(true, 200, None, Flag::None, big.as_bytes())]);
+    let s = stats.snapshot(connections.len());
+    assert_eq!(s.packets, 2);
+    assert_eq!(s.parse_failures, 1);
+    assert_eq!(s.evicted_oversize, 1);
+    assert_eq!(s.connections, 0);
+
+    // idle connections go at the next sweep
+    let at = |mono_us| PacketTime {
+        wall: now_rfc3339(),
+        mono_us,
+    };
+    process_packet(
+        &ctx,
+        Linktype(101),
+        &packet(true, 300, None, Flag::None, b"GET"),
+        &at(1),
+    );
+    let idle = limits.idle_timeout_us + 2;
+    // any packet of another connection triggers the sweep
+    let mut other = Vec::new();
+    PacketBuilder::ipv4(CLIENT, SERVER, 64)
+        .tcp(50001, 11434, 1, 65535)
+        .write(&mut other, b"")
+        .unwrap();
+    process_packet(&ctx, Linktype(101), &other, &at(idle));
+    assert_eq!(stats.snapshot(0).evicted_idle, 1);
+    assert_eq!(connections.len(), 1, "only the fresh connection is left");
+}
+
+#[test]
+fn keep_alive_responses_are_framed() {
+    let sink = CollectSink::default();
+    let rules = load_llm_rules();
+    let connections = DashMap::new();
+    let (limits, stats) = (CaptureLimits::default(), CaptureCounters::default());
+    let ctx = PacketContext {
+        sink: &sink,
+        llm_rules: &rules,
+        connections: &connections,
+        limits: &limits,
+        stats: &stats,
+        process_lookup: false,
+    };
+    let reqs = b"GET /a HTTP/1.1\r\nHost: h\r\n\r\nHEAD /b HTTP/1.1\r\nHost: h\r\n\r\nDELETE /c HTTP/1.1\r\nHost: h\r\n\r\n";
+    let resps: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n\
+        HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nX-Trace: 1\r\n\r\n\
+        HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n\
+        HTTP/1.1 204 No Content\r\n\r\n";
+    feed(
+        &ctx,
+        &[
+            packet(true, 100, None, Flag::Syn, b""),
+            packet(true, 101, None, Flag::None, reqs),
+            packet(
+                false,
+                5000,
+                Some(101 + reqs.len() as u32),
+                Flag::None,
+                resps,
+            ),
+        ],
+    );
+    assert_eq!(
+        names(&sink),
+        [
+            "onHttpRequest",
+            "onHttpRequest",
+            "onHttpRequest",
+            "onHttpResponse",
+            // dechunked body
+            "onHttpResponse",
+            "onHttpResponseComplete",
+            "onHttpResponse",
+            "onHttpResponseComplete",
+            "onHttpResponse",
+            "onHttpResponseComplete",
+        ]
+    );
+    let events = sink.0.lock().unwrap().clone();
+    let CaptureEvent::Response(chunk) = &events[4] else {
+        panic!("expected body chunk");
+    };
+    assert_eq!(chunk.body_base64.as_deref(), Some("aGVsbG8="));
+    let CaptureEvent::ResponseComplete(done) = &events[5] else {
+        panic!("expected completion");
+    };
+    assert_eq!(done.trailers[0].name, "X-Trace");
+    let CaptureEvent::Response(head) = &events[6] else {
+        panic!("expected HEAD response");
+    };
+    assert_eq!(head.body_len, 0);
+    let CaptureEvent::Response(no_content) = &events[8] else {
+        panic!("expected 204 response");
+    };
+    assert_eq!(no_content.status_code, 204);
+    assert_eq!(stats.snapshot(0).parse_failures, 0);
+}
+
+#[test]
+fn linux_cooked_and_ipv6_extension_headers() {
+    let req = b"GET /api/tags HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let mut v6 = Vec::new();
+    PacketBuilder::ipv6([0; 16], Ipv6Addr::LOCALHOST.octets(), 64)
+        .tcp(50000, 11434, 1, 65535)
+        .write(&mut v6, req)
+        .unwrap();
+    // splice in a hop-by-hop options header (8 bytes of PadN) before TCP
+    v6[6] = 0;
+    let len = u16::from_be_bytes([v6[4], v6[5]]) + 8;
+    v6[4..6].copy_from_slice(&len.to_be_bytes());
+    v6.splice(40..40, [6, 0, 1, 4, 0, 0, 0, 0]);
+    // SLL2: protocol first, 20-byte header
+    let mut sll2 = vec![0x86, 0xdd];
+    sll2.resize(20, 0);
+    sll2.extend_from_slice(&v6);
+
+    let mut v4 = packet(true, 100, None, Flag::None, req);
+    // SLL: protocol in bytes 14..16
+    let mut sll = vec![0; 14];
+    sll.extend_from_slice(&[0x08, 0x00]);
+    sll.append(&mut v4);
+
+    let t = PacketTime::now();
+    let mut hosts = Vec::new();
+    for (linktype, frame) in [(Linktype(276), sll2), (Linktype(113), sll)] {
+        let sink = CollectSink::default();
+        let rules = load_llm_rules();
+        let connections = DashMap::new();
+        let (limits, stats) = (CaptureLimits::default(), CaptureCounters::default());
+        let ctx = PacketContext {
+            sink: &sink,
+            llm_rules: &rules,
+            connections: &connections,
+            limits: &limits,
+            stats: &stats,
+            process_lookup: false,
+        };
+        process_packet(&ctx, linktype, &frame, &t);
+        let events = sink.0.lock().unwrap().clone();
+        let [CaptureEvent::Request(r)] = events.as_slice() else {
+            panic!("expected one request for {linktype:?}");
+        };
+        hosts.push(r.dst_ip.clone());
+    }
+    assert_eq!(hosts, ["::1", "10.0.0.2"]);
+}
+
+#[test]
+fn capture_filter_defaults_and_overrides() {
+    let rules = load_llm_rules();
+    let default = capture_filter(&CaptureOptions::default(), &rules);
+    assert_eq!(
+        default,
+        "tcp and (port 80 or port 1234 or port 8080 or port 11434)"
+    );
+    let scoped = CaptureOptions {
+        ports: Some(vec![]),
+        hosts: vec!["127.0.0.1".into(), "api.openai.com".into()],
+        ..Default::default()
+    };
+    assert_eq!(
+        capture_filter(&scoped, &rules),
+        "tcp and (host 127.0.0.1 or host api.openai.com)"
+    );
+    let raw = CaptureOptions {
+        bpf: Some(" udp port 53 ".into()),
+        ..scoped
+    };
+    assert_eq!(capture_filter(&raw, &rules), "udp port 53");
+
+    // saved files: every TCP port unless narrowed
+    assert_eq!(offline_filter(&CaptureOptions::default(), &rules), "tcp");
+    let ports = CaptureOptions {
+        ports: Some(vec![443]),
+        ..Default::default()
+    };
+    assert_eq!(offline_filter(&ports, &rules), "tcp and (port 443)");
+}
+
+#[test]
+fn llm_detection_is_left_to_rules() {
+    let body = b"{\"model\":\"gpt-4.1\",\"input\":\"hi\"}";
+    let raw = [
+        format!(
+            "POST /v1/responses HTTP/1.1\r\nHost: api.openai.com\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .as_bytes(),
+        body,
+    ]
+    .concat();
+    let (_, evt) = parse_http_request(&raw, "2025-01-01T00:00:00Z")
+        .unwrap()
+        .unwrap();
+    assert!(!evt.is_llm && evt.llm_provider.is_none());
+    assert_eq!(
+        load_llm_rules().match_request(&evt).as_deref(),
+        Some("openai_compatible")
+    );
+    let custom = crate::llm_rules::load_llm_rules_from_json_str("{\"rules\":[]}").unwrap();
+    assert_eq!(custom.match_request(&evt), None);
+}
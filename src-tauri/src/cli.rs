@@ -0,0 +1,388 @@
+// Headless entry point: drives the proxy / capture without a webview and prints
+// every event as one JSON line.
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::events::{CaptureEvent, EventSink, FanoutSink, SharedSink};
+use crate::http_shared::HttpRequestEvent;
+use crate::llm::ExchangeTracker;
+use crate::store::{STORE, StoreSink};
+use crate::usage::UsageSink;
+use crate::{api, ca, capture, llm_rules, proxy};
+
+const USAGE: &str = "\
+promptdumper-cli <command> [options]
+
+commands:
+  proxy    [--addr 127.0.0.1:38080] [--upstream URL] [--output FILE] [API]
+  capture  --iface NAME [--ports 80,11434] [--hosts H1,H2] [--bpf EXPR]
+           [--snaplen N] [--no-promisc] [--output FILE] [API]
+  capture  --file PATH.pcap [--ports ..] [--hosts ..] [--bpf EXPR] [--output FILE]
+  capture  --list
+  ca       export | install | status | uninstall
+  rules    show | check FILE | match REQUEST_EVENT.json
+
+API: [--api 127.0.0.1:38081] [--api-token TOKEN] also serves the local event API
+--data-dir DIR stores sessions under DIR/sessions (default: the current directory)
+
+events are written as JSON lines ({\"event\":..,\"payload\":..}) to stdout or --output";
+
+/// Writes `{"event":..,"payload":..}` lines, flushing after each event.
+pub struct JsonlSink {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonlSink {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out: Mutex::new(out),
+        }
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn emit(&self, event: &CaptureEvent) {
+        let line = event_line(event);
+        let mut w = self.out.lock().unwrap();
+        let _ = writeln!(w, "{}", line);
+        let _ = w.flush();
+    }
+}
+
+pub fn run() -> i32 {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run_with_args(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn run_with_args(mut args: Vec<String>) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(format!("missing command\n\n{}", USAGE));
+    }
+    let cmd = args.remove(0);
+    if let Some(dir) = take_opt(&mut args, "data-dir")? {
+        crate::store::set_data_dir(dir.into())?;
+    }
+    match cmd.as_str() {
+        "proxy" => cmd_proxy(args),
+        "capture" => cmd_capture(args),
+        "ca" => cmd_ca(args),
+        "rules" => cmd_rules(args),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("unknown command `{}`\n\n{}", other, USAGE)),
+    }
+}
+
+// Removes `--name value` / `--name=value` from args.
+fn take_opt(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    let Some(i) = args
+        .iter()
+        .position(|a| *a == flag || a.starts_with(&prefix))
+    else {
+        return Ok(None);
+    };
+    let a = args.remove(i);
+    if let Some(v) = a.strip_prefix(&prefix) {
+        return Ok(Some(v.to_string()));
+    }
+    if i < args.len() && !args[i].starts_with("--") {
+        return Ok(Some(args.remove(i)));
+    }
+    Err(format!("{} requires a value", flag))
+}
+
+fn take_switch(args: &mut Vec<String>, name: &str) -> bool {
+    let flag = format!("--{}", name);
+    match args.iter().position(|a| *a == flag) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+fn ensure_no_extra(args: &[String]) -> Result<(), String> {
+    match args.first() {
+        Some(a) => Err(format!("unexpected argument `{}`", a)),
+        None => Ok(()),
+    }
+}
+
+fn open_output(path: Option<String>) -> Result<Arc<JsonlSink>, String> {
+    let w: Box<dyn Write + Send> = match path {
+        Some(p) => Box::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&p)
+                .map_err(|e| format!("open {}: {}", p, e))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+    Ok(Arc::new(JsonlSink::new(w)))
+}
+
+fn event_line(event: &CaptureEvent) -> String {
+    serde_json::json!({ "event": event.name(), "payload": event }).to_string()
+}
+
+// Live runs also persist into the active session and feed API subscribers, like
+// the desktop app.
+fn live_sink(out: Arc<JsonlSink>) -> SharedSink {
+    std::thread::spawn(crate::usage::load_persisted_usage);
+    Arc::new(ExchangeTracker::new(Arc::new(FanoutSink::new(vec![
+        Arc::new(StoreSink),
+        Arc::new(api::ApiSink),
+        Arc::new(UsageSink),
+        out,
+    ]))))
+}
+
+struct ApiOpts {
+    addr: Option<String>,
+    token: Option<String>,
+}
+
+impl ApiOpts {
+    fn take(args: &mut Vec<String>) -> Result<Self, String> {
+        Ok(Self {
+            addr: take_opt(args, "api")?,
+            token: take_opt(args, "api-token")?,
+        })
+    }
+
+    fn is_set(&self) -> bool {
+        self.addr.is_some() || self.token.is_some()
+    }
+
+    async fn start(self, sink: SharedSink) -> Result<(), String> {
+        let Some(addr) = self.addr else {
+            return Ok(());
+        };
+        let info = api::start_api_server(sink, Some(addr), self.token).await?;
+        eprintln!(
+            "api listening on http://{} (token {})",
+            info.addr, info.token
+        );
+        Ok(())
+    }
+}
+
+// Plain tokio runtime, so the CLI runs without tauri linked in.
+fn block_on<T>(fut: impl std::future::Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::runtime::Runtime::new()
+        .map_err(|e| e.to_string())?
+        .block_on(fut)
+}
+
+fn cmd_proxy(mut args: Vec<String>) -> Result<(), String> {
+    let addr = take_opt(&mut args, "addr")?.unwrap_or_else(|| "127.0.0.1:38080".into());
+    let upstream = take_opt(&mut args, "upstream")?;
+    let out = open_output(take_opt(&mut args, "output")?)?;
+    let api_opts = ApiOpts::take(&mut args)?;
+    ensure_no_extra(&args)?;
+
+    STORE.begin_session("proxy")?;
+    let sink = live_sink(out);
+    let res = block_on(async move {
+        proxy::start_proxy(sink.clone(), addr.clone(), upstream).await?;
+        eprintln!("proxy listening on {} (ctrl-c to stop)", addr);
+        api_opts.start(sink).await?;
+        let _ = tokio::signal::ctrl_c().await;
+        Ok::<(), String>(())
+    });
+    proxy::stop_proxy();
+    STORE.end_session();
+    res
+}
+
+// Comma separated; `--ports=` (empty) captures every TCP port.
+fn take_capture_options(args: &mut Vec<String>) -> Result<capture::CaptureOptions, String> {
+    let list = |v: String| -> Vec<String> {
+        v.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let ports = match take_opt(args, "ports")? {
+        Some(v) => Some(
+            list(v)
+                .iter()
+                .map(|p| p.parse().map_err(|_| format!("invalid port: {}", p)))
+                .collect::<Result<Vec<u16>, String>>()?,
+        ),
+        None => None,
+    };
+    let snaplen = match take_opt(args, "snaplen")? {
+        Some(v) => Some(v.parse().map_err(|_| format!("invalid snaplen: {}", v))?),
+        None => None,
+    };
+    Ok(capture::CaptureOptions {
+        ports,
+        hosts: take_opt(args, "hosts")?.map(list).unwrap_or_default(),
+        bpf: take_opt(args, "bpf")?,
+        snaplen,
+        promisc: take_switch(args, "no-promisc").then_some(false),
+    })
+}
+
+fn cmd_capture(mut args: Vec<String>) -> Result<(), String> {
+    if take_switch(&mut args, "list") {
+        ensure_no_extra(&args)?;
+        for iface in capture::list_network_interfaces().map_err(|e| e.to_string())? {
+            println!(
+                "{}",
+                serde_json::to_string(&iface).map_err(|e| e.to_string())?
+            );
+        }
+        return Ok(());
+    }
+    let iface = take_opt(&mut args, "iface")?;
+    let file = take_opt(&mut args, "file")?;
+    let options = take_capture_options(&mut args)?;
+    let out = open_output(take_opt(&mut args, "output")?)?;
+    let api_opts = ApiOpts::take(&mut args)?;
+    ensure_no_extra(&args)?;
+
+    match (iface, file) {
+        (Some(iface), None) => {
+            STORE.begin_session("capture")?;
+            let sink = live_sink(out);
+            let res =
+                capture::start_capture(sink.clone(), &iface, &options).map_err(|e| e.to_string());
+            let res = match res {
+                Ok(()) => block_on(async move {
+                    eprintln!("capturing on {} (ctrl-c to stop)", iface);
+                    api_opts.start(sink).await?;
+                    let _ = tokio::signal::ctrl_c().await;
+                    Ok(())
+                }),
+                Err(e) => Err(e),
+            };
+            capture::stop_capture();
+            STORE.end_session();
+            res
+        }
+        (None, Some(file)) => {
+            if options.snaplen.is_some() || options.promisc.is_some() || api_opts.is_set() {
+                return Err(
+                    "--snaplen, --no-promisc and --api only apply to live capture (--iface)".into(),
+                );
+            }
+            let summary =
+                capture::analyze_pcap_file(out, &file, &options).map_err(|e| e.to_string())?;
+            eprintln!(
+                "{} packets analyzed, session {}",
+                summary.packets,
+                summary.session_id.as_deref().unwrap_or("-")
+            );
+            Ok(())
+        }
+        _ => Err("capture needs exactly one of --iface or --file".into()),
+    }
+}
+
+fn cmd_ca(args: Vec<String>) -> Result<(), String> {
+    let [sub] = args.as_slice() else {
+        return Err("usage: ca export | install | status | uninstall".into());
+    };
+    match sub.as_str() {
+        "export" => {
+            let (cert, _key) = ca::ensure_ca_exists()?;
+            print!("{}", cert);
+            Ok(())
+        }
+        "install" => {
+            let (cert, _key) = ca::ensure_ca_exists()?;
+            ca::install_ca_to_system_trust(&cert)
+        }
+        "status" => {
+            let status = match ca::is_ca_installed_in_system_trust()? {
+                true => "installed",
+                false => "not installed",
+            };
+            println!("{}", status);
+            Ok(())
+        }
+        "uninstall" => ca::uninstall_ca_from_system_trust(),
+        other => Err(format!("unknown ca command `{}`", other)),
+    }
+}
+
+fn cmd_rules(args: Vec<String>) -> Result<(), String> {
+    match args.as_slice() {
+        [sub] if sub == "show" => {
+            println!("{}", llm_rules::effective_llm_rules_json());
+            Ok(())
+        }
+        [sub, path] if sub == "check" => {
+            let text = fs::read_to_string(path).map_err(|e| format!("read {}: {}", path, e))?;
+            let rules = llm_rules::load_llm_rules_from_json_str(&text)
+                .ok_or_else(|| format!("{} is not a valid rules file", path))?;
+            println!("ok: {}", rules.providers().join(", "));
+            Ok(())
+        }
+        [sub, path] if sub == "match" => {
+            let text = fs::read_to_string(path).map_err(|e| format!("read {}: {}", path, e))?;
+            let evt: HttpRequestEvent = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+            let provider = llm_rules::load_llm_rules().match_request(&evt);
+            println!("{}", provider.as_deref().unwrap_or("none"));
+            Ok(())
+        }
+        _ => Err("usage: rules show | check FILE | match REQUEST_EVENT.json".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_accept_both_forms() {
+        let mut args: Vec<String> = ["--addr=0.0.0.0:1", "--output", "out.jsonl", "--list"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            take_opt(&mut args, "addr").unwrap().as_deref(),
+            Some("0.0.0.0:1")
+        );
+        assert_eq!(
+            take_opt(&mut args, "output").unwrap().as_deref(),
+            Some("out.jsonl")
+        );
+        assert_eq!(take_opt(&mut args, "upstream").unwrap(), None);
+        assert!(take_switch(&mut args, "list"));
+        assert!(args.is_empty());
+
+        let mut missing = vec!["--iface".to_string(), "--output".to_string()];
+        assert!(take_opt(&mut missing, "iface").is_err());
+    }
+
+    #[test]
+    fn event_lines_embed_payload_json() {
+        let evt: HttpRequestEvent = serde_json::from_str(
+            r#"{"id":"a","timestamp":"t","src_ip":"127.0.0.1","src_port":5000,
+                "dst_ip":"127.0.0.1","dst_port":80,"method":"GET","path":"/",
+                "version":"1.1","headers":[],"body_len":0,"is_llm":false}"#,
+        )
+        .unwrap();
+        let line = event_line(&CaptureEvent::Request(evt));
+        let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["event"], "onHttpRequest");
+        assert_eq!(v["payload"]["id"], "a");
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::http_shared::{
+    HttpRequestEvent, HttpResponseEvent, ResponseCompleteEvent, WebSocketMessageEvent,
+};
+use crate::llm::LlmExchange;
+
+// Everything the proxy and capture pipelines report. Each variant maps to the
+// event name the frontend listens on.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum CaptureEvent {
+    Request(HttpRequestEvent),
+    Response(HttpResponseEvent),
+    ResponseComplete(ResponseCompleteEvent),
+    WebSocketMessage(WebSocketMessageEvent),
+    LlmExchange(Box<LlmExchange>),
+}
+
+impl CaptureEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CaptureEvent::Request(_) => "onHttpRequest",
+            CaptureEvent::Response(_) => "onHttpResponse",
+            CaptureEvent::ResponseComplete(_) => "onHttpResponseComplete",
+            CaptureEvent::WebSocketMessage(_) => "onWebSocketMessage",
+            CaptureEvent::LlmExchange(_) => "onLlmExchange",
+        }
+    }
+}
+
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &CaptureEvent);
+}
+
+pub type SharedSink = Arc<dyn EventSink>;
+
+/// Delivers every event to each inner sink in order.
+#[derive(Clone, Default)]
+pub struct FanoutSink {
+    sinks: Vec<SharedSink>,
+}
+
+impl FanoutSink {
+    pub fn new(sinks: Vec<SharedSink>) -> Self {
+        Self { sinks }
+    }
+
+    pub fn with(mut self, sink: SharedSink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+}
+
+impl EventSink for FanoutSink {
+    fn emit(&self, event: &CaptureEvent) {
+        for sink in &self.sinks {
+            sink.emit(event);
+        }
+    }
+}
+
+/// Forwards events to the webview through `tauri::Emitter`.
+#[cfg(feature = "gui")]
+pub struct TauriSink<R: tauri::Runtime> {
+    app: tauri::AppHandle<R>,
+}
+
+#[cfg(feature = "gui")]
+impl<R: tauri::Runtime> TauriSink<R> {
+    pub fn new(app: tauri::AppHandle<R>) -> Self {
+        Self { app }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl<R: tauri::Runtime> EventSink for TauriSink<R> {
+    fn emit(&self, event: &CaptureEvent) {
+        use tauri::Emitter;
+        // untagged: the payload serializes as the inner event
+        let _ = self.app.emit(event.name(), event);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Collects events in memory; shared with other modules' tests.
+    #[derive(Default)]
+    pub(crate) struct CollectSink(pub(crate) Mutex<Vec<CaptureEvent>>);
+
+    impl EventSink for CollectSink {
+        fn emit(&self, event: &CaptureEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn fanout_delivers_to_every_sink() {
+        let a = Arc::new(CollectSink::default());
+        let b = Arc::new(CollectSink::default());
+        let fanout = FanoutSink::new(vec![a.clone()]).with(b.clone());
+        let evt = HttpResponseEvent {
+            id: "x".into(),
+            timestamp: "t".into(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 80,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 1234,
+            status_code: 200,
+            reason: None,
+            version: "1.1".into(),
+            headers: vec![],
+            body_base64: None,
+            body_len: 0,
+            process_name: None,
+            pid: None,
+            process: None,
+            is_llm: false,
+            llm_provider: None,
+            mono_us: None,
+        };
+        fanout.emit(&CaptureEvent::Response(evt));
+        for sink in [&a, &b] {
+            let got = sink.0.lock().unwrap();
+            assert_eq!(got.len(), 1);
+            assert_eq!(got[0].name(), "onHttpResponse");
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// Tauri commands and the desktop entry point; the CLI builds without them.
+use std::sync::Arc;
+
+use crate::api::{self, ApiServerInfo, ApiSink};
+use crate::capture::{
+    self, CaptureOptions, CaptureStats, NetworkInterfaceInfo, PcapFileSummary,
+    list_network_interfaces as list_ifaces_impl,
+};
+use crate::events::{FanoutSink, SharedSink, TauriSink};
+use crate::llm::ExchangeTracker;
+use crate::store::{Exchange, ExchangePage, STORE, SessionSummary, StoreSink};
+use crate::usage::{USAGE, UsageGroup, UsageSink, UsageTotals};
+use crate::{
+    ca, har, llm_rules, proxy, start_capture_with, start_proxy_with, stop_capture_with_session,
+    stop_proxy_with_session,
+};
+
+// Live events are persisted to the active session, forwarded to the webview and
+// published to local API subscribers; completed LLM exchanges are added on top.
+fn live_sink(app: tauri::AppHandle) -> SharedSink {
+    Arc::new(ExchangeTracker::new(Arc::new(FanoutSink::new(vec![
+        Arc::new(StoreSink),
+        Arc::new(TauriSink::new(app)),
+        Arc::new(ApiSink),
+        Arc::new(UsageSink),
+    ]))))
+}
+
+#[tauri::command]
+fn list_network_interfaces() -> Result<Vec<NetworkInterfaceInfo>, String> {
+    list_ifaces_impl().map_err(|e| e.to_string())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StartCaptureArgs {
+    iface: String,
+    #[serde(flatten)]
+    options: CaptureOptions,
+}
+
+#[tauri::command]
+fn start_capture(app: tauri::AppHandle, args: StartCaptureArgs) -> Result<(), String> {
+    start_capture_with(live_sink(app), &args.iface, &args.options)
+}
+
+#[tauri::command]
+fn stop_capture() {
+    stop_capture_with_session();
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct AnalyzePcapFileArgs {
+    path: String,
+    // optional filter, as for start_capture
+    #[serde(default)]
+    ports: Option<Vec<u16>>,
+    #[serde(default)]
+    hosts: Vec<String>,
+    #[serde(default)]
+    bpf: Option<String>,
+}
+
+#[tauri::command]
+async fn analyze_pcap_file(
+    app: tauri::AppHandle,
+    args: AnalyzePcapFileArgs,
+) -> Result<PcapFileSummary, String> {
+    let sink: SharedSink = Arc::new(FanoutSink::new(vec![
+        Arc::new(TauriSink::new(app)),
+        Arc::new(UsageSink),
+    ]));
+    let options = CaptureOptions {
+        ports: args.ports,
+        hosts: args.hosts,
+        bpf: args.bpf,
+        ..Default::default()
+    };
+    tokio::task::spawn_blocking(move || capture::analyze_pcap_file(sink, &args.path, &options))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn capture_stats() -> CaptureStats {
+    capture::capture_stats()
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StartProxyCmdArgs {
+    addr: Option<String>,
+    upstream: Option<String>,
+}
+
+#[tauri::command]
+async fn start_proxy(app: tauri::AppHandle, args: StartProxyCmdArgs) -> Result<(), String> {
+    start_proxy_with(live_sink(app), args.addr, args.upstream).await
+}
+
+#[tauri::command]
+fn stop_proxy() {
+    stop_proxy_with_session();
+}
+
+#[tauri::command]
+fn get_proxy_routes() -> proxy::RoutingTable {
+    proxy::routing_table()
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SetProxyRoutesArgs {
+    table: proxy::RoutingTable,
+}
+
+#[tauri::command]
+fn set_proxy_routes(args: SetProxyRoutesArgs) -> Result<(), String> {
+    proxy::set_routing_table(args.table)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ResolveProxyRouteArgs {
+    host: String,
+    port: u16,
+}
+
+// Which upstream a destination would use right now, for checking a table.
+#[tauri::command]
+fn resolve_proxy_route(args: ResolveProxyRouteArgs) -> proxy::RouteDecision {
+    proxy::route_for(&args.host, args.port)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StartApiServerArgs {
+    addr: Option<String>,
+    token: Option<String>,
+}
+
+#[tauri::command]
+async fn start_api_server(
+    app: tauri::AppHandle,
+    args: StartApiServerArgs,
+) -> Result<ApiServerInfo, String> {
+    api::start_api_server(live_sink(app), args.addr, args.token).await
+}
+
+#[tauri::command]
+fn stop_api_server() {
+    api::stop_api_server();
+}
+
+#[tauri::command]
+fn api_server_info() -> Option<ApiServerInfo> {
+    api::api_server_info()
+}
+
+#[tauri::command]
+fn ensure_ca() -> Result<(), String> {
+    let (cert, _key) = ca::ensure_ca_exists()?;
+    match ca::install_ca_to_system_trust(&cert) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[tauri::command]
+fn is_ca_installed() -> Result<bool, String> {
+    ca::is_ca_installed_in_system_trust()
+}
+
+#[tauri::command]
+fn uninstall_ca() -> Result<(), String> {
+    ca::uninstall_ca_from_system_trust()
+}
+
+#[tauri::command]
+fn list_sessions() -> Result<Vec<SessionSummary>, String> {
+    STORE.list_sessions()
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ListSessionEventsArgs {
+    session_id: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[tauri::command]
+fn list_session_events(args: ListSessionEventsArgs) -> Result<ExchangePage, String> {
+    STORE.list_exchanges(
+        &args.session_id,
+        args.offset.unwrap_or(0),
+        args.limit.unwrap_or(100),
+    )
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GetExchangeArgs {
+    session_id: String,
+    id: String,
+}
+
+#[tauri::command]
+fn get_exchange(args: GetExchangeArgs) -> Result<Option<Exchange>, String> {
+    STORE.get_exchange(&args.session_id, &args.id)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeleteSessionArgs {
+    session_id: String,
+}
+
+#[tauri::command]
+fn delete_session(args: DeleteSessionArgs) -> Result<(), String> {
+    STORE.delete_session(&args.session_id)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ExportSessionHarArgs {
+    session_id: String,
+}
+
+#[tauri::command]
+fn export_session_har(args: ExportSessionHarArgs) -> Result<String, String> {
+    let exchanges = STORE.get_exchanges(&args.session_id)?;
+    serde_json::to_string_pretty(&har::exchanges_to_har(&exchanges)).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ImportHarArgs {
+    har: String,
+}
+
+#[tauri::command]
+fn import_har(args: ImportHarArgs) -> Result<String, String> {
+    let exchanges = har::har_to_exchanges(&args.har, &llm_rules::load_llm_rules())?;
+    STORE.import_session("har", &exchanges)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UsageTotalsArgs {
+    // unix millis; either bound may be left open
+    from_ms: Option<i64>,
+    to_ms: Option<i64>,
+    // provider / model / process / host; the full key when absent
+    group_by: Option<UsageGroup>,
+}
+
+#[tauri::command]
+fn usage_totals(args: UsageTotalsArgs) -> Vec<UsageTotals> {
+    USAGE.totals(args.from_ms, args.to_ms, args.group_by)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        // launched from Finder or a .desktop file the working directory is `/`
+        .setup(|app| {
+            use tauri::Manager;
+            crate::store::set_data_dir(app.path().app_data_dir()?)?;
+            std::thread::spawn(crate::usage::load_persisted_usage);
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            list_network_interfaces,
+            start_capture,
+            stop_capture,
+            analyze_pcap_file,
+            capture_stats,
+            usage_totals,
+            start_proxy,
+            stop_proxy,
+            get_proxy_routes,
+            set_proxy_routes,
+            resolve_proxy_route,
+            start_api_server,
+            stop_api_server,
+            api_server_info,
+            ensure_ca,
+            is_ca_installed,
+            uninstall_ca,
+            list_sessions,
+            list_session_events,
+            get_exchange,
+            delete_session,
+            export_session_har,
+            import_har
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,673 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::http_shared::{Header, HttpRequestEvent, HttpResponseEvent, gen_id, now_rfc3339};
+use crate::llm_rules::LlmRules;
+use crate::store::Exchange;
+
+// HAR 1.2 (http://www.softwareishard.com/blog/har-12-spec/). Fields prefixed with
+// `_` are custom extensions allowed by the spec and carry our LLM/process tags.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    #[serde(default)]
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    #[serde(default)]
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    #[serde(default)]
+    pub timings: HarTimings,
+    #[serde(
+        rename = "serverIPAddress",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub server_ip_address: Option<String>,
+    #[serde(rename = "_is_llm", default)]
+    pub is_llm: bool,
+    #[serde(
+        rename = "_llm_provider",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub llm_provider: Option<String>,
+    #[serde(
+        rename = "_process_name",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub process_name: Option<String>,
+    #[serde(rename = "_pid", default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub headers: Vec<HarNameValue>,
+    #[serde(default)]
+    pub query_string: Vec<HarNameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub text: String,
+    // "base64" when the body is not UTF-8; HAR has no standard field for this
+    #[serde(rename = "_encoding", default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL", default)]
+    pub redirect_url: String,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    #[serde(default)]
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HarTimings {
+    #[serde(default)]
+    pub send: f64,
+    #[serde(default)]
+    pub wait: f64,
+    #[serde(default)]
+    pub receive: f64,
+}
+
+fn unknown_size() -> i64 {
+    -1
+}
+
+fn header_value<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+fn to_har_headers(headers: &[Header]) -> Vec<HarNameValue> {
+    headers
+        .iter()
+        .map(|h| HarNameValue {
+            name: h.name.clone(),
+            value: h.value.clone(),
+        })
+        .collect()
+}
+
+fn from_har_headers(headers: &[HarNameValue]) -> Vec<Header> {
+    headers
+        .iter()
+        .map(|h| Header {
+            name: h.name.clone(),
+            value: h.value.clone(),
+        })
+        .collect()
+}
+
+fn decode_b64(b64: &Option<String>) -> Vec<u8> {
+    b64.as_ref()
+        .and_then(|s| general_purpose::STANDARD.decode(s).ok())
+        .unwrap_or_default()
+}
+
+fn encode_b64(bytes: &[u8]) -> Option<String> {
+    if bytes.is_empty() {
+        None
+    } else {
+        Some(general_purpose::STANDARD.encode(bytes))
+    }
+}
+
+fn parse_ts(s: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(s, &Rfc3339).ok()
+}
+
+fn millis_between(a: &str, b: &str) -> f64 {
+    match (parse_ts(a), parse_ts(b)) {
+        (Some(a), Some(b)) => ((b - a).as_seconds_f64() * 1000.0).max(0.0),
+        _ => 0.0,
+    }
+}
+
+// Same heuristic as the frontend "copy as curl": MITM traffic lands on 443.
+fn request_url(req: &HttpRequestEvent) -> String {
+    let host = header_value(&req.headers, "host")
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| format!("{}:{}", req.dst_ip, req.dst_port));
+    let path = if req.path.starts_with('/') {
+        req.path.clone()
+    } else {
+        format!("/{}", req.path)
+    };
+    let forwarded_https = header_value(&req.headers, "x-forwarded-proto")
+        .map(|v| v.eq_ignore_ascii_case("https"))
+        .unwrap_or(false);
+    let scheme = if forwarded_https || req.dst_port == 443 {
+        "https"
+    } else {
+        "http"
+    };
+    format!("{}://{}{}", scheme, host, path)
+}
+
+fn query_pairs(path: &str) -> Vec<HarNameValue> {
+    let query = match path.split_once('?') {
+        Some((_, q)) => q,
+        None => return Vec::new(),
+    };
+    query
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            HarNameValue {
+                name: k.to_string(),
+                value: v.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn http_version_label(v: &str) -> String {
+    format!("HTTP/{}", v)
+}
+
+fn version_from_har(v: &str) -> String {
+    let v = v.trim();
+    let stripped = v
+        .strip_prefix("HTTP/")
+        .or_else(|| v.strip_prefix("http/"))
+        .unwrap_or(v);
+    match stripped {
+        "h2" | "2.0" => "2".into(),
+        "h3" | "3.0" => "3".into(),
+        "" => "1.1".into(),
+        other => other.to_string(),
+    }
+}
+
+// UTF-8 bodies are stored as text, anything else as base64.
+fn body_text(body: &[u8]) -> (String, Option<String>) {
+    match std::str::from_utf8(body) {
+        Ok(s) => (s.to_string(), None),
+        Err(_) => (
+            general_purpose::STANDARD.encode(body),
+            Some("base64".into()),
+        ),
+    }
+}
+
+fn entry_from_exchange(ex: &Exchange) -> Option<HarEntry> {
+    let req = ex.request.as_ref()?;
+    let req_body = decode_b64(&req.body_base64);
+    let post_data = if req_body.is_empty() {
+        None
+    } else {
+        let (text, encoding) = body_text(&req_body);
+        Some(HarPostData {
+            mime_type: header_value(&req.headers, "content-type")
+                .unwrap_or_default()
+                .to_string(),
+            text,
+            encoding,
+        })
+    };
+
+    let (response, timings) = match &ex.response {
+        Some(resp) => {
+            let body = decode_b64(&resp.body_base64);
+            let (text, encoding) = body_text(&body);
+            let finished = ex
+                .response_finished_at
+                .clone()
+                .unwrap_or_else(|| resp.timestamp.clone());
+            // prefer the monotonic figures recorded at completion over wall-clock differences
+            let mono = ex
+                .timings
+                .as_ref()
+                .and_then(|t| Some((t.ttfb_ms?, t.total_ms?)));
+            let timings = match mono {
+                Some((ttfb, total)) => HarTimings {
+                    send: 0.0,
+                    wait: ttfb,
+                    receive: (total - ttfb).max(0.0),
+                },
+                None => HarTimings {
+                    send: 0.0,
+                    wait: millis_between(&req.timestamp, &resp.timestamp),
+                    receive: millis_between(&resp.timestamp, &finished),
+                },
+            };
+            (
+                HarResponse {
+                    status: resp.status_code,
+                    status_text: resp.reason.clone().unwrap_or_default(),
+                    http_version: http_version_label(&resp.version),
+                    cookies: Vec::new(),
+                    headers: to_har_headers(&resp.headers),
+                    content: HarContent {
+                        size: body.len() as i64,
+                        mime_type: header_value(&resp.headers, "content-type")
+                            .unwrap_or_default()
+                            .to_string(),
+                        text: if body.is_empty() { None } else { Some(text) },
+                        encoding,
+                    },
+                    redirect_url: header_value(&resp.headers, "location")
+                        .unwrap_or_default()
+                        .to_string(),
+                    headers_size: -1,
+                    body_size: body.len() as i64,
+                },
+                timings,
+            )
+        }
+        // HAR requires a response object; status 0 marks "no response captured".
+        None => (
+            HarResponse {
+                status: 0,
+                status_text: String::new(),
+                http_version: String::new(),
+                cookies: Vec::new(),
+                headers: Vec::new(),
+                content: HarContent {
+                    size: 0,
+                    mime_type: String::new(),
+                    text: None,
+                    encoding: None,
+                },
+                redirect_url: String::new(),
+                headers_size: -1,
+                body_size: -1,
+            },
+            HarTimings::default(),
+        ),
+    };
+
+    Some(HarEntry {
+        started_date_time: req.timestamp.clone(),
+        time: timings.send + timings.wait + timings.receive,
+        request: HarRequest {
+            method: req.method.clone(),
+            url: request_url(req),
+            http_version: http_version_label(&req.version),
+            cookies: Vec::new(),
+            headers: to_har_headers(&req.headers),
+            query_string: query_pairs(&req.path),
+            post_data,
+            headers_size: -1,
+            body_size: req_body.len() as i64,
+        },
+        response,
+        cache: serde_json::json!({}),
+        timings,
+        server_ip_address: Some(req.dst_ip.clone()),
+        is_llm: req.is_llm,
+        llm_provider: req.llm_provider.clone(),
+        process_name: req.process_name.clone(),
+        pid: req.pid,
+    })
+}
+
+pub fn exchanges_to_har(exchanges: &[Exchange]) -> Har {
+    Har {
+        log: HarLog {
+            version: "1.2".into(),
+            creator: HarCreator {
+                name: "promptdumper".into(),
+                version: env!("CARGO_PKG_VERSION").into(),
+            },
+            entries: exchanges.iter().filter_map(entry_from_exchange).collect(),
+        },
+    }
+}
+
+fn exchange_from_entry(entry: &HarEntry, llm_rules: &LlmRules) -> Option<Exchange> {
+    let uri = entry.request.url.parse::<http::Uri>().ok()?;
+    let https = uri.scheme_str() == Some("https");
+    let host = uri.host().unwrap_or_default().to_string();
+    let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+    let path = uri
+        .path_and_query()
+        .map(|pq| pq.as_str().to_string())
+        .unwrap_or_else(|| "/".into());
+    let mut headers = from_har_headers(&entry.request.headers);
+    if header_value(&headers, "host").is_none() && !host.is_empty() {
+        headers.push(Header {
+            name: "host".into(),
+            value: uri.authority().map(|a| a.as_str()).unwrap_or(&host).into(),
+        });
+    }
+    let req_body = entry
+        .request
+        .post_data
+        .as_ref()
+        .map(|p| match p.encoding.as_deref() {
+            Some("base64") => general_purpose::STANDARD
+                .decode(&p.text)
+                .unwrap_or_default(),
+            _ => p.text.as_bytes().to_vec(),
+        })
+        .unwrap_or_default();
+    let started = parse_ts(&entry.started_date_time);
+    let id = gen_id();
+
+    let mut request = HttpRequestEvent {
+        id: id.clone(),
+        timestamp: started
+            .and_then(|t| t.format(&Rfc3339).ok())
+            .unwrap_or_else(now_rfc3339),
+        src_ip: String::new(),
+        src_port: 0,
+        dst_ip: entry
+            .server_ip_address
+            .clone()
+            .unwrap_or_else(|| host.clone()),
+        dst_port: port,
+        method: entry.request.method.clone(),
+        path,
+        version: version_from_har(&entry.request.http_version),
+        headers,
+        body_base64: encode_b64(&req_body),
+        body_len: req_body.len(),
+        process_name: entry.process_name.clone(),
+        pid: entry.pid,
+        process: None,
+        is_llm: false,
+        llm_provider: None,
+        mono_us: None,
+        conn_id: None,
+        conn_seq: None,
+    };
+    if let Some(provider) = llm_rules.match_request(&request) {
+        request.is_llm = true;
+        request.llm_provider = Some(provider);
+    } else if entry.llm_provider.is_some() {
+        // keep tags from our own exports when local rules don't know the provider
+        request.is_llm = entry.is_llm;
+        request.llm_provider = entry.llm_provider.clone();
+    }
+
+    // status 0 is how browsers record aborted/blocked requests
+    if entry.response.status == 0 {
+        return Some(Exchange {
+            request: Some(request),
+            response: None,
+            response_finished_at: None,
+            usage: None,
+            timings: None,
+            websocket: Vec::new(),
+        });
+    }
+    let content = &entry.response.content;
+    let resp_body = match (&content.text, content.encoding.as_deref()) {
+        (Some(t), Some("base64")) => general_purpose::STANDARD.decode(t).unwrap_or_default(),
+        (Some(t), _) => t.as_bytes().to_vec(),
+        (None, _) => Vec::new(),
+    };
+    let offset_ms = |ms: f64| -> String {
+        started
+            .map(|t| t + time::Duration::seconds_f64(ms.max(0.0) / 1000.0))
+            .and_then(|t| t.format(&Rfc3339).ok())
+            .unwrap_or_else(now_rfc3339)
+    };
+    let head_ms = entry.timings.send.max(0.0) + entry.timings.wait.max(0.0);
+    let mut response = HttpResponseEvent {
+        id,
+        timestamp: offset_ms(head_ms),
+        src_ip: request.dst_ip.clone(),
+        src_port: port,
+        dst_ip: String::new(),
+        dst_port: 0,
+        status_code: entry.response.status,
+        reason: if entry.response.status_text.is_empty() {
+            None
+        } else {
+            Some(entry.response.status_text.clone())
+        },
+        version: version_from_har(&entry.response.http_version),
+        headers: from_har_headers(&entry.response.headers),
+        body_base64: encode_b64(&resp_body),
+        body_len: resp_body.len(),
+        process_name: request.process_name.clone(),
+        pid: request.pid,
+        process: None,
+        is_llm: request.is_llm,
+        llm_provider: request.llm_provider.clone(),
+        mono_us: None,
+    };
+    if !response.is_llm
+        && let Some(provider) = llm_rules.match_response(&response)
+    {
+        response.is_llm = true;
+        response.llm_provider = Some(provider);
+    }
+    Some(Exchange {
+        request: Some(request),
+        response: Some(response),
+        response_finished_at: Some(offset_ms(head_ms + entry.timings.receive.max(0.0))),
+        usage: None,
+        timings: None,
+        websocket: Vec::new(),
+    })
+}
+
+/// Parses a HAR document (from a browser, another proxy or our own export) and
+/// re-classifies every entry with the given rules.
+pub fn har_to_exchanges(text: &str, llm_rules: &LlmRules) -> Result<Vec<Exchange>, String> {
+    let har: Har = serde_json::from_str(text).map_err(|e| format!("invalid HAR: {}", e))?;
+    Ok(har
+        .log
+        .entries
+        .iter()
+        .filter_map(|e| exchange_from_entry(e, llm_rules))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_rules::load_llm_rules_from_json_str;
+
+    const RULES: &str = include_str!("../../llm_rules.json");
+
+    fn header(name: &str, value: &str) -> Header {
+        Header {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn export_then_import_round_trips() {
+        let body = br#"{"model":"llama3","messages":[{"role":"user","content":"hi"}]}"#;
+        let req = HttpRequestEvent {
+            id: "r1".into(),
+            timestamp: "2025-01-01T00:00:00Z".into(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 50123,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 11434,
+            method: "POST".into(),
+            path: "/api/chat?stream=true".into(),
+            version: "1.1".into(),
+            headers: vec![
+                header("Host", "127.0.0.1:11434"),
+                header("Content-Type", "application/json"),
+            ],
+            body_base64: encode_b64(body),
+            body_len: body.len(),
+            process_name: Some("python3".into()),
+            pid: Some(42),
+            process: None,
+            is_llm: true,
+            llm_provider: Some("ollama".into()),
+            mono_us: None,
+            conn_id: None,
+            conn_seq: None,
+        };
+        let resp_body = b"{\"message\":{\"content\":\"hello\"}}\n";
+        let resp = HttpResponseEvent {
+            id: "r1".into(),
+            timestamp: "2025-01-01T00:00:00.250Z".into(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 11434,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 50123,
+            status_code: 200,
+            reason: Some("OK".into()),
+            version: "1.1".into(),
+            headers: vec![header("Content-Type", "application/x-ndjson")],
+            body_base64: encode_b64(resp_body),
+            body_len: resp_body.len(),
+            process_name: None,
+            pid: None,
+            process: None,
+            is_llm: true,
+            llm_provider: Some("ollama".into()),
+            mono_us: None,
+        };
+        let ex = Exchange {
+            request: Some(req),
+            response: Some(resp),
+            response_finished_at: Some("2025-01-01T00:00:01Z".into()),
+            usage: None,
+            timings: None,
+            websocket: Vec::new(),
+        };
+        let har = exchanges_to_har(&[ex]);
+        let entry = &har.log.entries[0];
+        assert_eq!(
+            entry.request.url,
+            "http://127.0.0.1:11434/api/chat?stream=true"
+        );
+        assert_eq!(entry.request.query_string[0].name, "stream");
+        assert_eq!(entry.timings.wait, 250.0);
+        assert_eq!(entry.timings.receive, 750.0);
+        assert_eq!(entry.llm_provider.as_deref(), Some("ollama"));
+
+        let text = serde_json::to_string(&har).unwrap();
+        assert!(text.contains("\"_is_llm\":true"));
+        let rules = load_llm_rules_from_json_str(RULES).unwrap();
+        let back = har_to_exchanges(&text, &rules).unwrap();
+        let req = back[0].request.as_ref().unwrap();
+        assert_eq!(req.path, "/api/chat?stream=true");
+        assert_eq!(req.dst_port, 11434);
+        assert_eq!(req.llm_provider.as_deref(), Some("ollama"));
+        assert_eq!(req.process_name.as_deref(), Some("python3"));
+        let resp = back[0].response.as_ref().unwrap();
+        assert_eq!(decode_b64(&resp.body_base64), resp_body);
+        assert_eq!(resp.timestamp, "2025-01-01T00:00:00.25Z");
+
+        // non-UTF-8 request bodies survive as base64
+        let binary = [0x00u8, 0xff, 0xfe, b'x'];
+        let mut ex = back[0].clone();
+        ex.request.as_mut().unwrap().body_base64 = encode_b64(&binary);
+        let har = exchanges_to_har(&[ex]);
+        let post = har.log.entries[0].request.post_data.as_ref().unwrap();
+        assert_eq!(post.encoding.as_deref(), Some("base64"));
+        let text = serde_json::to_string(&har).unwrap();
+        assert!(text.contains("\"_encoding\":\"base64\""));
+        let back = har_to_exchanges(&text, &rules).unwrap();
+        let req = back[0].request.as_ref().unwrap();
+        assert_eq!(decode_b64(&req.body_base64), binary);
+    }
+
+    #[test]
+    fn browser_har_is_classified_by_rules() {
+        let har = r#"{"log":{"version":"1.2","creator":{"name":"WebInspector","version":"537.36"},
+          "entries":[{"startedDateTime":"2025-01-01T00:00:00.000Z","time":12.5,
+            "request":{"method":"POST","url":"https://api.example.com/v1/chat/completions",
+              "httpVersion":"h2","headers":[{"name":"content-type","value":"application/json"}],
+              "queryString":[],"cookies":[],"headersSize":-1,"bodySize":40,
+              "postData":{"mimeType":"application/json","text":"{\"model\":\"gpt\",\"messages\":[]}"}},
+            "response":{"status":200,"statusText":"","httpVersion":"h2","headers":[],"cookies":[],
+              "content":{"size":2,"mimeType":"application/json","text":"e30=","encoding":"base64"},
+              "redirectURL":"","headersSize":-1,"bodySize":-1},
+            "cache":{},"timings":{"blocked":-1,"send":0.5,"wait":10,"receive":2}}]}}"#;
+        let rules = load_llm_rules_from_json_str(RULES).unwrap();
+        let exchanges = har_to_exchanges(har, &rules).unwrap();
+        let req = exchanges[0].request.as_ref().unwrap();
+        assert_eq!(req.dst_port, 443);
+        assert_eq!(req.version, "2");
+        assert_eq!(req.llm_provider.as_deref(), Some("openai_compatible"));
+        let resp = exchanges[0].response.as_ref().unwrap();
+        assert_eq!(decode_b64(&resp.body_base64), b"{}");
+        assert!(resp.is_llm);
+    }
+}
@@ -1,14 +1,21 @@
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
 use rand::{Rng, distributions::Alphanumeric};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-#[derive(Debug, Clone, Serialize)]
+use crate::process_lookup::ProcessInfo;
+use crate::timing::ExchangeTimings;
+use crate::usage::TokenUsage;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Header {
     pub name: String,
     pub value: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpRequestEvent {
     pub id: String,
     pub timestamp: String,
@@ -24,11 +31,22 @@ pub struct HttpRequestEvent {
     pub body_len: usize,
     pub process_name: Option<String>,
     pub pid: Option<i32>,
+    // exe / argv / cwd / user / parent chain of `pid`, when known
+    #[serde(default)]
+    pub process: Option<ProcessInfo>,
     pub is_llm: bool,
     pub llm_provider: Option<String>,
+    // monotonic microseconds (see `mono_us`); packet time for offline captures
+    #[serde(default)]
+    pub mono_us: Option<u64>,
+    // proxy connection the request arrived on, and its 1-based position there
+    #[serde(default)]
+    pub conn_id: Option<u64>,
+    #[serde(default)]
+    pub conn_seq: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpResponseEvent {
     pub id: String,
     pub timestamp: String,
@@ -44,8 +62,67 @@ pub struct HttpResponseEvent {
     pub body_len: usize,
     pub process_name: Option<String>,
     pub pid: Option<i32>,
+    // exe / argv / cwd / user / parent chain of `pid`, when known
+    #[serde(default)]
+    pub process: Option<ProcessInfo>,
     pub is_llm: bool,
     pub llm_provider: Option<String>,
+    // monotonic microseconds (see `mono_us`); packet time for offline captures
+    #[serde(default)]
+    pub mono_us: Option<u64>,
+}
+
+// Marks the end of a response body (framing complete, or the connection closed).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseCompleteEvent {
+    pub id: String,
+    pub timestamp: String,
+    // filled in for LLM responses once the body has been parsed
+    pub usage: Option<TokenUsage>,
+    #[serde(default)]
+    pub timings: Option<ExchangeTimings>,
+    // chunked trailer fields, when the capture saw any
+    #[serde(default)]
+    pub trailers: Vec<Header>,
+}
+
+impl ResponseCompleteEvent {
+    pub fn now(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            timestamp: now_rfc3339(),
+            usage: None,
+            timings: None,
+            trailers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WsDirection {
+    ClientToServer,
+    ServerToClient,
+}
+
+// One WebSocket message (fragments joined) or control frame on an upgraded exchange.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketMessageEvent {
+    // id of the upgrade request
+    pub id: String,
+    pub timestamp: String,
+    pub direction: WsDirection,
+    pub opcode: u8,
+    // text / binary / close / ping / pong
+    pub kind: String,
+    // UTF-8 payload of text messages
+    pub text: Option<String>,
+    pub body_base64: Option<String>,
+    pub body_len: usize,
+    pub close_code: Option<u16>,
+    pub close_reason: Option<String>,
+    #[serde(default)]
+    pub mono_us: Option<u64>,
 }
 
 pub fn gen_id() -> String {
@@ -61,3 +138,37 @@ pub fn now_rfc3339() -> String {
         .format(&time::format_description::well_known::Rfc3339)
         .unwrap_or_else(|_| "".into())
 }
+
+static MONO_START: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Microseconds on a process-wide monotonic clock; used for latency math, unlike
+/// the RFC3339 `timestamp` fields which follow the wall clock.
+pub fn mono_us() -> u64 {
+    MONO_START.elapsed().as_micros() as u64
+}
+
+// `%XX` escapes only; malformed escapes are kept as-is.
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // from_str_radix alone would take a sign, e.g. `%+1`
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -1,87 +1,71 @@
+pub mod api;
 mod ca;
-mod capture;
+pub mod capture;
+pub mod cli;
+pub mod events;
+#[cfg(feature = "gui")]
+mod gui;
+pub mod har;
 pub mod http_shared;
+pub mod llm;
 mod llm_rules;
 mod process_lookup;
-mod proxy;
+pub mod proxy;
+pub mod store;
+pub mod timing;
+pub mod usage;
 
 use capture::{
-    NetworkInterfaceInfo, list_network_interfaces as list_ifaces_impl,
-    start_capture as start_capture_impl, stop_capture as stop_capture_impl,
+    CaptureOptions, is_capture_running, start_capture as start_capture_impl,
+    stop_capture as stop_capture_impl,
 };
+use events::SharedSink;
+use store::STORE;
 
-#[tauri::command]
-fn list_network_interfaces() -> Result<Vec<NetworkInterfaceInfo>, String> {
-    list_ifaces_impl().map_err(|e| e.to_string())
-}
+#[cfg(feature = "gui")]
+pub use gui::run;
 
-#[derive(Debug, serde::Deserialize)]
-struct StartCaptureArgs {
-    iface: String,
+// A session stays open while either the proxy or the capture is running.
+fn end_session_if_idle() {
+    if !is_capture_running() && !proxy::is_proxy_running() {
+        STORE.end_session();
+    }
 }
 
-#[tauri::command]
-fn start_capture(app: tauri::AppHandle, args: StartCaptureArgs) -> Result<(), String> {
-    start_capture_impl(app, &args.iface).map_err(|e| e.to_string())
+// Shared by the Tauri commands and the local API server.
+pub(crate) fn start_capture_with(
+    sink: SharedSink,
+    iface: &str,
+    options: &CaptureOptions,
+) -> Result<(), String> {
+    STORE.begin_session("capture")?;
+    let res = start_capture_impl(sink, iface, options).map_err(|e| e.to_string());
+    if res.is_err() {
+        end_session_if_idle();
+    }
+    res
 }
 
-#[tauri::command]
-fn stop_capture() {
+pub(crate) fn stop_capture_with_session() {
     stop_capture_impl();
+    end_session_if_idle();
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct StartProxyCmdArgs {
+pub(crate) async fn start_proxy_with(
+    sink: SharedSink,
     addr: Option<String>,
     upstream: Option<String>,
-}
-
-#[tauri::command]
-async fn start_proxy(app: tauri::AppHandle, args: StartProxyCmdArgs) -> Result<(), String> {
-    let addr = args.addr.unwrap_or_else(|| "127.0.0.1:38080".into());
-    proxy::start_proxy::<tauri::Wry, _>(app, addr, args.upstream)
-        .await
-        .map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn stop_proxy() {
-    proxy::stop_proxy();
-}
-
-#[tauri::command]
-fn ensure_ca() -> Result<(), String> {
-    let (cert, _key) = ca::ensure_ca_exists()?;
-    match ca::install_ca_to_system_trust(&cert) {
-        Ok(()) => Ok(()),
-        Err(e) => Err(e),
+) -> Result<(), String> {
+    let addr = addr.unwrap_or_else(|| "127.0.0.1:38080".into());
+    STORE.begin_session("proxy")?;
+    let res = proxy::start_proxy(sink, addr, upstream).await;
+    if res.is_err() {
+        end_session_if_idle();
     }
+    res
 }
 
-#[tauri::command]
-fn is_ca_installed() -> Result<bool, String> {
-    ca::is_ca_installed_in_system_trust()
-}
-
-#[tauri::command]
-fn uninstall_ca() -> Result<(), String> {
-    ca::uninstall_ca_from_system_trust()
-}
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![
-            list_network_interfaces,
-            start_capture,
-            stop_capture,
-            start_proxy,
-            stop_proxy,
-            ensure_ca,
-            is_ca_installed,
-            uninstall_ca
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+pub(crate) fn stop_proxy_with_session() {
+    proxy::stop_proxy();
+    end_session_if_idle();
 }
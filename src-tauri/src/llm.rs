@@ -0,0 +1,1481 @@
+// Rust side of the LLM traffic model: turns a request and its reassembled response
+// into a typed `LlmExchange`. Response parsing follows `parseLlmMarkdown` in
+// src/utils/llm.ts (SSE, NDJSON, chunked framing, single JSON) so both views agree.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use base64::{Engine as _, engine::general_purpose};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::events::{CaptureEvent, EventSink, SharedSink};
+use crate::http_shared::{HttpRequestEvent, HttpResponseEvent, ResponseCompleteEvent};
+use crate::process_lookup::{ProcessInfo, cached_process_info};
+use crate::timing::{ExchangeTimings, TimingMarks};
+use crate::usage::TokenUsage;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ToolCall {
+    pub id: Option<String>,
+    pub index: Option<u64>,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: Option<String>,
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LlmMessage {
+    pub role: String,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SamplingParams {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u64>,
+    pub max_tokens: Option<u64>,
+    pub stop: Vec<String>,
+    pub seed: Option<i64>,
+    pub frequency_penalty: Option<f64>,
+    pub presence_penalty: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LlmRequest {
+    pub model: Option<String>,
+    pub messages: Vec<LlmMessage>,
+    pub params: SamplingParams,
+    pub stream: bool,
+    pub tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LlmResponse {
+    pub model: Option<String>,
+    pub reasoning: String,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LlmExchange {
+    pub id: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub request_timestamp: Option<String>,
+    pub completed_at: String,
+    pub status_code: Option<u16>,
+    pub process_name: Option<String>,
+    pub pid: Option<i32>,
+    pub process: Option<ProcessInfo>,
+    pub host: Option<String>,
+    pub messages: Vec<LlmMessage>,
+    pub params: SamplingParams,
+    pub stream: bool,
+    pub tools: Vec<String>,
+    pub reasoning: String,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+    pub timings: Option<ExchangeTimings>,
+}
+
+fn str_field(obj: &Map<String, Value>, key: &str) -> Option<String> {
+    obj.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+// Tool call arguments are a JSON string for OpenAI and an object for Ollama.
+fn arguments_text(v: Option<&Value>) -> String {
+    match v {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// ---- response ----
+
+#[derive(Clone, Copy)]
+enum Buf {
+    Reasoning,
+    Content,
+}
+
+#[derive(Default)]
+struct ResponseAcc {
+    out: LlmResponse,
+    // streaming tool call fragments keyed by index / id, merged at the end
+    deltas: Vec<(String, ToolCall)>,
+}
+
+impl ResponseAcc {
+    fn buf(&mut self, b: Buf) -> &mut String {
+        match b {
+            Buf::Reasoning => &mut self.out.reasoning,
+            Buf::Content => &mut self.out.content,
+        }
+    }
+
+    fn push_text(&mut self, v: Option<&Value>, b: Buf) {
+        match v {
+            Some(Value::String(s)) => self.buf(b).push_str(s),
+            Some(Value::Array(items)) => {
+                for item in items {
+                    match item {
+                        Value::String(s) => self.buf(b).push_str(s),
+                        Value::Object(o) => {
+                            let text = ["text", "content", "value"]
+                                .iter()
+                                .find_map(|k| o.get(*k).and_then(|v| v.as_str()));
+                            if let Some(t) = text {
+                                self.buf(b).push_str(t);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Some(Value::Object(o)) => {
+                if let Some(Value::Array(parts)) = o.get("content") {
+                    for x in parts {
+                        match x {
+                            Value::String(s) => self.out.content.push_str(s),
+                            Value::Object(p) => {
+                                if let Some(t) = p.get("text").and_then(|v| v.as_str()) {
+                                    self.out.content.push_str(t);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                } else {
+                    for k in ["text", "content", "value"] {
+                        if let Some(t) = o.get(k).and_then(|v| v.as_str()) {
+                            self.buf(b).push_str(t);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_tool_call_delta(&mut self, tc: &Value) {
+        let Some(tc) = tc.as_object() else {
+            return;
+        };
+        let index = tc.get("index").and_then(|v| v.as_u64());
+        let id = str_field(tc, "id");
+        let key = match (index, &id) {
+            (Some(i), _) => format!("idx:{}", i),
+            (None, Some(id)) => format!("id:{}", id),
+            (None, None) => "one".to_string(),
+        };
+        let pos = match self.deltas.iter().position(|(k, _)| *k == key) {
+            Some(p) => p,
+            None => {
+                let kind = str_field(tc, "type").unwrap_or_else(|| "function".into());
+                self.deltas.push((
+                    key,
+                    ToolCall {
+                        id,
+                        index,
+                        kind,
+                        ..Default::default()
+                    },
+                ));
+                self.deltas.len() - 1
+            }
+        };
+        let func = tc.get("function").and_then(|v| v.as_object());
+        let name = func
+            .and_then(|f| str_field(f, "name"))
+            .or_else(|| str_field(tc, "name"));
+        let args = func
+            .and_then(|f| f.get("arguments"))
+            .or_else(|| tc.get("arguments"))
+            .and_then(|v| v.as_str());
+        let cur = &mut self.deltas[pos].1;
+        if cur.name.is_none() {
+            cur.name = name.filter(|n| !n.is_empty());
+        }
+        if let Some(a) = args {
+            cur.arguments.push_str(a);
+        }
+    }
+
+    fn add_tool_call_full(&mut self, tc: &Value) {
+        let Some(obj) = tc.as_object() else {
+            return;
+        };
+        if let Some(f) = obj.get("function").and_then(|v| v.as_object()) {
+            self.out.tool_calls.push(ToolCall {
+                id: str_field(obj, "id"),
+                index: obj.get("index").and_then(|v| v.as_u64()),
+                kind: str_field(obj, "type").unwrap_or_else(|| "function".into()),
+                name: str_field(f, "name"),
+                arguments: arguments_text(f.get("arguments")),
+            });
+            return;
+        }
+        if obj.contains_key("name") || obj.contains_key("arguments") {
+            self.out.tool_calls.push(ToolCall {
+                id: str_field(obj, "id"),
+                kind: "function".into(),
+                name: str_field(obj, "name"),
+                arguments: arguments_text(obj.get("arguments")),
+                ..Default::default()
+            });
+            return;
+        }
+        if let Some(Value::Array(inner)) = obj.get("tool_calls") {
+            for t in inner {
+                self.add_tool_call_full(t);
+            }
+            return;
+        }
+        self.out.tool_calls.push(ToolCall {
+            kind: "function".into(),
+            arguments: tc.to_string(),
+            ..Default::default()
+        });
+    }
+
+    fn add_function_call(&mut self, f: Option<&Value>, delta: bool) {
+        let Some(f) = f.filter(|v| v.is_object()) else {
+            return;
+        };
+        let mut tc = Map::new();
+        tc.insert("type".into(), Value::String("function".into()));
+        tc.insert("function".into(), f.clone());
+        if delta {
+            tc.insert("index".into(), Value::from(0));
+            self.add_tool_call_delta(&Value::Object(tc));
+        } else {
+            self.add_tool_call_full(&Value::Object(tc));
+        }
+    }
+
+    fn set_finish_reason(&mut self, v: Option<&Value>) {
+        if let Some(r) = v.and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+            self.out.finish_reason = Some(r.to_string());
+        }
+    }
+
+    fn add_from_obj(&mut self, obj: &Value) {
+        let Some(o) = obj.as_object() else {
+            return;
+        };
+        if self.out.model.is_none() {
+            self.out.model = str_field(o, "model");
+        }
+        if let Some(u) = TokenUsage::from_json(o) {
+            self.out.usage.get_or_insert_default().merge(u);
+        }
+        if self.add_anthropic(o) || self.add_gemini(o) || self.add_responses_api(o) {
+            return;
+        }
+        if let Some(m) = o.get("message").and_then(|v| v.as_object()) {
+            self.push_text(m.get("thinking"), Buf::Reasoning);
+            self.push_text(m.get("content"), Buf::Content);
+            if let Some(Value::Array(tcs)) = m.get("tool_calls") {
+                for tc in tcs {
+                    self.add_tool_call_full(tc);
+                }
+            }
+            self.add_function_call(m.get("function_call"), false);
+        }
+        self.push_text(o.get("reasoning"), Buf::Reasoning);
+        self.push_text(o.get("reasoning_content"), Buf::Reasoning);
+        if let Some(Value::Array(choices)) = o.get("choices") {
+            for c in choices {
+                if let Some(d) = c.get("delta") {
+                    self.push_text(d.get("reasoning"), Buf::Reasoning);
+                    self.push_text(d.get("reasoning_content"), Buf::Reasoning);
+                    self.push_text(d.get("content"), Buf::Content);
+                    if let Some(Value::Array(tcs)) = d.get("tool_calls") {
+                        for tc in tcs {
+                            self.add_tool_call_delta(tc);
+                        }
+                    }
+                    self.add_function_call(d.get("function_call"), true);
+                }
+                if let Some(m) = c.get("message") {
+                    self.push_text(m.get("reasoning"), Buf::Reasoning);
+                    self.push_text(m.get("reasoning_content"), Buf::Reasoning);
+                    self.push_text(m.get("content"), Buf::Content);
+                    if let Some(Value::Array(tcs)) = m.get("tool_calls") {
+                        for tc in tcs {
+                            self.add_tool_call_full(tc);
+                        }
+                    }
+                    self.add_function_call(m.get("function_call"), false);
+                }
+                self.push_text(c.get("reasoning"), Buf::Reasoning);
+                self.push_text(c.get("reasoning_content"), Buf::Reasoning);
+                self.push_text(c.get("text"), Buf::Content);
+                self.push_text(c.get("content"), Buf::Content);
+                self.set_finish_reason(c.get("finish_reason"));
+            }
+        }
+        for key in ["tool_calls", "parallel_tool_calls"] {
+            if let Some(Value::Array(tcs)) = o.get(key) {
+                for tc in tcs {
+                    self.add_tool_call_full(tc);
+                }
+            }
+        }
+        self.add_function_call(o.get("function_call"), false);
+        self.push_text(o.get("content"), Buf::Content);
+        self.push_text(o.get("text"), Buf::Content);
+        // Ollama /api/generate streams plain strings at the top level
+        if let Some(v @ Value::String(_)) = o.get("response") {
+            self.push_text(Some(v), Buf::Content);
+        }
+        if let Some(v @ Value::String(_)) = o.get("thinking") {
+            self.push_text(Some(v), Buf::Reasoning);
+        }
+        // Ollama reports why generation ended on the final object
+        self.set_finish_reason(o.get("done_reason"));
+    }
+
+    // Anthropic Messages API: typed content blocks, and named stream events that
+    // carry deltas per block index. Returns false for anything else.
+    fn add_anthropic(&mut self, o: &Map<String, Value>) -> bool {
+        match o.get("type").and_then(|v| v.as_str()) {
+            Some("message_start") => {
+                if self.out.model.is_none() {
+                    self.out.model = o
+                        .get("message")
+                        .and_then(|m| m.get("model"))
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string());
+                }
+            }
+            Some("content_block_start") => {
+                let index = o.get("index").and_then(|v| v.as_u64());
+                if let Some(block) = o.get("content_block") {
+                    self.add_anthropic_block(block, index, true);
+                }
+            }
+            Some("content_block_delta") => {
+                let Some(d) = o.get("delta") else {
+                    return true;
+                };
+                match d.get("type").and_then(|v| v.as_str()) {
+                    Some("text_delta") => self.push_text(d.get("text"), Buf::Content),
+                    Some("thinking_delta") => self.push_text(d.get("thinking"), Buf::Reasoning),
+                    Some("input_json_delta") => {
+                        let mut tc = Map::new();
+                        if let Some(i) = o.get("index") {
+                            tc.insert("index".into(), i.clone());
+                        }
+                        if let Some(pj) = d.get("partial_json") {
+                            tc.insert("arguments".into(), pj.clone());
+                        }
+                        self.add_tool_call_delta(&Value::Object(tc));
+                    }
+                    _ => {}
+                }
+            }
+            Some("message_delta") => {
+                self.set_finish_reason(o.get("delta").and_then(|d| d.get("stop_reason")));
+            }
+            Some("content_block_stop") | Some("message_stop") | Some("ping") => {}
+            Some("message") if o.get("content").is_some_and(|c| c.is_array()) => {
+                if let Some(Value::Array(blocks)) = o.get("content") {
+                    for (i, block) in blocks.iter().enumerate() {
+                        self.add_anthropic_block(block, Some(i as u64), false);
+                    }
+                }
+                self.set_finish_reason(o.get("stop_reason"));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn add_anthropic_block(&mut self, block: &Value, index: Option<u64>, streaming: bool) {
+        match block.get("type").and_then(|v| v.as_str()) {
+            Some("text") => self.push_text(block.get("text"), Buf::Content),
+            Some("thinking") => self.push_text(block.get("thinking"), Buf::Reasoning),
+            Some("tool_use") => {
+                let b = block.as_object();
+                let id = b.and_then(|b| str_field(b, "id"));
+                let name = b.and_then(|b| str_field(b, "name"));
+                if streaming {
+                    // input arrives afterwards as input_json_delta fragments
+                    let mut tc = Map::new();
+                    if let Some(i) = index {
+                        tc.insert("index".into(), Value::from(i));
+                    }
+                    if let Some(id) = id {
+                        tc.insert("id".into(), Value::String(id));
+                    }
+                    if let Some(name) = name {
+                        tc.insert("name".into(), Value::String(name));
+                    }
+                    self.add_tool_call_delta(&Value::Object(tc));
+                } else {
+                    self.out.tool_calls.push(ToolCall {
+                        id,
+                        index,
+                        kind: "function".into(),
+                        name,
+                        arguments: arguments_text(block.get("input")),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Gemini: `candidates[].content.parts[]` with text, `thought` text and
+    // `functionCall` parts. Streamed chunks repeat the same shape.
+    fn add_gemini(&mut self, o: &Map<String, Value>) -> bool {
+        let Some(Value::Array(candidates)) = o.get("candidates") else {
+            return false;
+        };
+        if self.out.model.is_none() {
+            self.out.model = str_field(o, "modelVersion");
+        }
+        for c in candidates {
+            if let Some(Value::Array(parts)) = c.get("content").and_then(|v| v.get("parts")) {
+                for part in parts {
+                    let thought = part.get("thought").and_then(|v| v.as_bool()) == Some(true);
+                    let buf = if thought {
+                        Buf::Reasoning
+                    } else {
+                        Buf::Content
+                    };
+                    self.push_text(part.get("text"), buf);
+                    if let Some(f) = part.get("functionCall").and_then(|v| v.as_object()) {
+                        self.out.tool_calls.push(ToolCall {
+                            id: str_field(f, "id"),
+                            kind: "function".into(),
+                            name: str_field(f, "name"),
+                            arguments: arguments_text(f.get("args")),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            self.set_finish_reason(c.get("finishReason"));
+        }
+        true
+    }
+
+    // OpenAI Responses API (/v1/responses): typed `response.*` stream events, or a
+    // whole `"object":"response"` body. The `*.done` events repeat text already
+    // delivered as deltas and are skipped.
+    fn add_responses_api(&mut self, o: &Map<String, Value>) -> bool {
+        let kind = o.get("type").and_then(|v| v.as_str()).unwrap_or_default();
+        if o.get("object").and_then(|v| v.as_str()) == Some("response") {
+            self.add_response_object(o);
+            return true;
+        }
+        if !kind.starts_with("response.") {
+            return false;
+        }
+        match kind {
+            "response.output_text.delta" | "response.refusal.delta" => {
+                self.push_text(o.get("delta"), Buf::Content)
+            }
+            "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+                self.push_text(o.get("delta"), Buf::Reasoning)
+            }
+            "response.output_item.added" => {
+                if let Some(item) = o.get("item").and_then(|v| v.as_object())
+                    && item.get("type").and_then(|v| v.as_str()) == Some("function_call")
+                {
+                    let mut tc = Map::new();
+                    if let Some(i) = o.get("output_index") {
+                        tc.insert("index".into(), i.clone());
+                    }
+                    if let Some(id) = item.get("call_id").or_else(|| item.get("id")) {
+                        tc.insert("id".into(), id.clone());
+                    }
+                    if let Some(name) = item.get("name") {
+                        tc.insert("name".into(), name.clone());
+                    }
+                    self.add_tool_call_delta(&Value::Object(tc));
+                }
+            }
+            "response.function_call_arguments.delta" => {
+                let mut tc = Map::new();
+                if let Some(i) = o.get("output_index") {
+                    tc.insert("index".into(), i.clone());
+                }
+                if let Some(d) = o.get("delta") {
+                    tc.insert("arguments".into(), d.clone());
+                }
+                self.add_tool_call_delta(&Value::Object(tc));
+            }
+            _ => {
+                // created / in_progress / completed carry the response object
+                if let Some(r) = o.get("response").and_then(|v| v.as_object()) {
+                    if self.out.model.is_none() {
+                        self.out.model = str_field(r, "model");
+                    }
+                    if kind != "response.created" && kind != "response.in_progress" {
+                        self.set_response_status(r);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn set_response_status(&mut self, r: &Map<String, Value>) {
+        let reason = r
+            .get("incomplete_details")
+            .and_then(|d| d.get("reason"))
+            .or_else(|| r.get("status"));
+        self.set_finish_reason(reason);
+    }
+
+    fn add_response_object(&mut self, r: &Map<String, Value>) {
+        if self.out.model.is_none() {
+            self.out.model = str_field(r, "model");
+        }
+        if let Some(Value::Array(items)) = r.get("output") {
+            for (i, item) in items.iter().enumerate() {
+                match item.get("type").and_then(|v| v.as_str()) {
+                    Some("message") => self.push_text(item.get("content"), Buf::Content),
+                    Some("reasoning") => self.push_text(item.get("summary"), Buf::Reasoning),
+                    Some("function_call") => {
+                        let f = item.as_object();
+                        self.out.tool_calls.push(ToolCall {
+                            id: f.and_then(|f| str_field(f, "call_id")),
+                            index: Some(i as u64),
+                            kind: "function".into(),
+                            name: f.and_then(|f| str_field(f, "name")),
+                            arguments: arguments_text(item.get("arguments")),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.set_response_status(r);
+    }
+
+    fn add_json_text(&mut self, text: &str) -> bool {
+        match serde_json::from_str::<Value>(text) {
+            // Gemini without `alt=sse` streams one JSON array of chunks
+            Ok(Value::Array(items)) => {
+                for v in &items {
+                    self.add_from_obj(v);
+                }
+                true
+            }
+            Ok(v) => {
+                self.add_from_obj(&v);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn finish(mut self) -> LlmResponse {
+        let mut deltas: Vec<ToolCall> = self.deltas.into_iter().map(|(_, tc)| tc).collect();
+        deltas.sort_by_key(|tc| tc.index.unwrap_or(0));
+        self.out.tool_calls.extend(deltas);
+        self.out
+    }
+}
+
+// `data:` payload of an SSE line, optionally prefixed by a chunk-size token when the
+// body still carries chunked transfer framing.
+fn sse_data(line: &str) -> Option<&str> {
+    if let Some(rest) = line.strip_prefix("data:") {
+        return Some(rest);
+    }
+    let (head, rest) = line.split_once(char::is_whitespace)?;
+    if !is_hex(head) {
+        return None;
+    }
+    rest.trim_start().strip_prefix("data:")
+}
+
+impl ResponseAcc {
+    // One line of an SSE or NDJSON body; also used to follow a stream chunk by chunk.
+    fn feed_line(&mut self, raw: &str) {
+        let line = raw.trim();
+        if line.is_empty() || is_hex(line) {
+            return;
+        }
+        let Some(payload) = sse_data(line) else {
+            self.add_json_text(line);
+            return;
+        };
+        let mut payload = payload.trim();
+        if let Some(end) = payload.rfind('}') {
+            payload = &payload[..=end];
+        }
+        if !payload.is_empty() && payload != "[DONE]" {
+            self.add_json_text(payload);
+        }
+    }
+
+    // Amount of decoded text so far; grows whenever a chunk carries tokens.
+    fn progress(&self) -> usize {
+        self.out.reasoning.len()
+            + self.out.content.len()
+            + self
+                .out
+                .tool_calls
+                .iter()
+                .chain(self.deltas.iter().map(|(_, tc)| tc))
+                .map(|tc| tc.arguments.len() + tc.name.as_ref().map_or(0, |n| n.len()))
+                .sum::<usize>()
+    }
+}
+
+pub fn parse_response_body(text: &str) -> LlmResponse {
+    let mut acc = ResponseAcc::default();
+    let t = text.replace('\r', "");
+    let trimmed = t.trim_start();
+    let parsed_whole = !t.contains("data:")
+        && (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && acc.add_json_text(&t);
+    if !parsed_whole {
+        for raw in t.split('\n') {
+            acc.feed_line(raw);
+        }
+    }
+    acc.finish()
+}
+
+// ---- request ----
+
+fn message_text(v: Option<&Value>) -> String {
+    let mut acc = ResponseAcc::default();
+    acc.push_text(v, Buf::Content);
+    acc.out.content
+}
+
+fn parse_message(m: &Value) -> Option<LlmMessage> {
+    let o = m.as_object()?;
+    let mut tool_acc = ResponseAcc::default();
+    if let Some(Value::Array(tcs)) = o.get("tool_calls") {
+        for tc in tcs {
+            tool_acc.add_tool_call_full(tc);
+        }
+    }
+    let mut tool_call_id = str_field(o, "tool_call_id");
+    // Anthropic puts tool_use / tool_result blocks inside `content`
+    if let Some(Value::Array(blocks)) = o.get("content") {
+        for (i, block) in blocks.iter().enumerate() {
+            match block.get("type").and_then(|v| v.as_str()) {
+                Some("tool_use") => tool_acc.add_anthropic_block(block, Some(i as u64), false),
+                Some("tool_result") if tool_call_id.is_none() => {
+                    tool_call_id = block
+                        .get("tool_use_id")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+    Some(LlmMessage {
+        role: str_field(o, "role").unwrap_or_else(|| "user".into()),
+        content: message_text(o.get("content")),
+        tool_calls: tool_acc.out.tool_calls,
+        tool_call_id,
+    })
+}
+
+// Responses API input items: messages plus function_call / function_call_output.
+fn parse_responses_input(item: &Value) -> Option<LlmMessage> {
+    let o = item.as_object()?;
+    match o.get("type").and_then(|v| v.as_str()) {
+        Some("function_call") => Some(LlmMessage {
+            role: "assistant".into(),
+            tool_calls: vec![ToolCall {
+                id: str_field(o, "call_id"),
+                kind: "function".into(),
+                name: str_field(o, "name"),
+                arguments: arguments_text(o.get("arguments")),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }),
+        Some("function_call_output") => Some(LlmMessage {
+            role: "tool".into(),
+            content: arguments_text(o.get("output")),
+            tool_call_id: str_field(o, "call_id"),
+            ..Default::default()
+        }),
+        Some("message") | None => parse_message(item),
+        _ => None,
+    }
+}
+
+fn parse_gemini_content(c: &Value) -> Option<LlmMessage> {
+    let o = c.as_object()?;
+    let mut msg = LlmMessage {
+        role: match o.get("role").and_then(|v| v.as_str()) {
+            Some("model") => "assistant".into(),
+            Some(r) => r.to_string(),
+            None => "user".into(),
+        },
+        ..Default::default()
+    };
+    let Some(Value::Array(parts)) = o.get("parts") else {
+        return Some(msg);
+    };
+    for part in parts {
+        if let Some(t) = part.get("text").and_then(|v| v.as_str()) {
+            msg.content.push_str(t);
+        }
+        if let Some(f) = part.get("functionCall").and_then(|v| v.as_object()) {
+            msg.tool_calls.push(ToolCall {
+                id: str_field(f, "id"),
+                kind: "function".into(),
+                name: str_field(f, "name"),
+                arguments: arguments_text(f.get("args")),
+                ..Default::default()
+            });
+        }
+        if let Some(r) = part.get("functionResponse").and_then(|v| v.as_object()) {
+            msg.role = "tool".into();
+            msg.tool_call_id = str_field(r, "id").or_else(|| str_field(r, "name"));
+            msg.content.push_str(&arguments_text(r.get("response")));
+        }
+    }
+    Some(msg)
+}
+
+// Gemini and Vertex name the model in the path: `.../models/{model}:generateContent`.
+fn model_from_path(path: &str) -> Option<String> {
+    let rest = &path[path.find("/models/")? + "/models/".len()..];
+    let end = rest.find([':', '/', '?']).unwrap_or(rest.len());
+    (end > 0).then(|| rest[..end].to_string())
+}
+
+fn f64_field(objs: &[Option<&Map<String, Value>>], keys: &[&str]) -> Option<f64> {
+    objs.iter()
+        .flatten()
+        .find_map(|o| keys.iter().find_map(|k| o.get(*k).and_then(|v| v.as_f64())))
+}
+
+fn u64_field(objs: &[Option<&Map<String, Value>>], keys: &[&str]) -> Option<u64> {
+    objs.iter()
+        .flatten()
+        .find_map(|o| keys.iter().find_map(|k| o.get(*k).and_then(|v| v.as_u64())))
+}
+
+pub fn parse_request_body(body: &[u8]) -> Option<LlmRequest> {
+    let v: Value = serde_json::from_slice(body).ok()?;
+    let o = v.as_object()?;
+    // Ollama nests sampling options under `options`, Gemini under `generationConfig`
+    let scopes = [
+        Some(o),
+        o.get("options").and_then(|v| v.as_object()),
+        o.get("generationConfig").and_then(|v| v.as_object()),
+    ];
+    let mut messages = Vec::new();
+    let system = o
+        .get("system")
+        .or_else(|| o.get("instructions"))
+        .or_else(|| o.get("systemInstruction").and_then(|v| v.get("parts")));
+    if let Some(system) = system {
+        let content = message_text(Some(system));
+        if !content.is_empty() {
+            messages.push(LlmMessage {
+                role: "system".into(),
+                content,
+                ..Default::default()
+            });
+        }
+    }
+    if let Some(Value::Array(ms)) = o.get("messages") {
+        messages.extend(ms.iter().filter_map(parse_message));
+    }
+    if let Some(Value::Array(contents)) = o.get("contents") {
+        messages.extend(contents.iter().filter_map(parse_gemini_content));
+    }
+    match o.get("input") {
+        Some(Value::String(text)) => messages.push(LlmMessage {
+            role: "user".into(),
+            content: text.clone(),
+            ..Default::default()
+        }),
+        Some(Value::Array(items)) => {
+            messages.extend(items.iter().filter_map(parse_responses_input))
+        }
+        _ => {}
+    }
+    if let Some(prompt) = o.get("prompt") {
+        messages.push(LlmMessage {
+            role: "user".into(),
+            content: message_text(Some(prompt)),
+            ..Default::default()
+        });
+    }
+    let stop = scopes
+        .iter()
+        .flatten()
+        .find_map(|s| {
+            ["stop", "stop_sequences", "stopSequences"]
+                .iter()
+                .find_map(|k| s.get(*k))
+        })
+        .map(|v| match v {
+            Value::String(s) => vec![s.clone()],
+            Value::Array(items) => items
+                .iter()
+                .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        })
+        .unwrap_or_default();
+    let params = SamplingParams {
+        temperature: f64_field(&scopes, &["temperature"]),
+        top_p: f64_field(&scopes, &["top_p", "topP"]),
+        top_k: u64_field(&scopes, &["top_k", "topK"]),
+        max_tokens: u64_field(
+            &scopes,
+            &[
+                "max_tokens",
+                "max_completion_tokens",
+                "num_predict",
+                "maxOutputTokens",
+                "max_output_tokens",
+            ],
+        ),
+        stop,
+        seed: scopes
+            .iter()
+            .flatten()
+            .find_map(|s| s.get("seed").and_then(|v| v.as_i64())),
+        frequency_penalty: f64_field(&scopes, &["frequency_penalty", "frequencyPenalty"]),
+        presence_penalty: f64_field(&scopes, &["presence_penalty", "presencePenalty"]),
+    };
+    let mut tools = Vec::new();
+    if let Some(Value::Array(ts)) = o.get("tools") {
+        for t in ts {
+            // Gemini groups declarations: tools[].functionDeclarations[]
+            if let Some(Value::Array(decls)) = t.get("functionDeclarations") {
+                tools.extend(
+                    decls
+                        .iter()
+                        .filter_map(|d| d.get("name").and_then(|n| n.as_str()))
+                        .map(|s| s.to_string()),
+                );
+                continue;
+            }
+            if let Some(name) = t
+                .get("function")
+                .and_then(|f| f.get("name"))
+                .or_else(|| t.get("name"))
+                .and_then(|n| n.as_str())
+            {
+                tools.push(name.to_string());
+            }
+        }
+    }
+    Some(LlmRequest {
+        model: str_field(o, "model"),
+        messages,
+        params,
+        stream: o.get("stream").and_then(|v| v.as_bool()).unwrap_or(false),
+        tools,
+    })
+}
+
+fn decode_body(b64: &Option<String>) -> Vec<u8> {
+    b64.as_deref()
+        .and_then(|s| general_purpose::STANDARD.decode(s).ok())
+        .unwrap_or_default()
+}
+
+pub fn build_exchange(
+    request: Option<&HttpRequestEvent>,
+    response: Option<&HttpResponseEvent>,
+    response_body: &[u8],
+    completed_at: &str,
+) -> LlmExchange {
+    let req = request
+        .and_then(|r| parse_request_body(&decode_body(&r.body_base64)))
+        .unwrap_or_default();
+    let resp = parse_response_body(&String::from_utf8_lossy(response_body));
+    let pid = request
+        .and_then(|r| r.pid)
+        .or_else(|| response.and_then(|r| r.pid));
+    LlmExchange {
+        id: request
+            .map(|r| r.id.clone())
+            .or_else(|| response.map(|r| r.id.clone()))
+            .unwrap_or_default(),
+        provider: request
+            .and_then(|r| r.llm_provider.clone())
+            .or_else(|| response.and_then(|r| r.llm_provider.clone())),
+        model: req
+            .model
+            .or_else(|| request.and_then(|r| model_from_path(&r.path)))
+            .or(resp.model),
+        request_timestamp: request.map(|r| r.timestamp.clone()),
+        completed_at: completed_at.to_string(),
+        status_code: response.map(|r| r.status_code),
+        process_name: request
+            .and_then(|r| r.process_name.clone())
+            .or_else(|| response.and_then(|r| r.process_name.clone())),
+        pid,
+        // 进程查询是异步的，早到的请求事件可能还没带上详情
+        process: request
+            .and_then(|r| r.process.clone())
+            .or_else(|| response.and_then(|r| r.process.clone()))
+            .or_else(|| cached_process_info(pid)),
+        host: request
+            .and_then(|r| {
+                r.headers
+                    .iter()
+                    .find(|h| h.name.eq_ignore_ascii_case("host"))
+                    .map(|h| h.value.clone())
+                    .or_else(|| Some(r.dst_ip.clone()))
+            })
+            .or_else(|| response.map(|r| r.src_ip.clone()))
+            .filter(|h| !h.is_empty()),
+        messages: req.messages,
+        params: req.params,
+        stream: req.stream || request.is_some_and(|r| r.path.contains(":streamGenerateContent")),
+        tools: req.tools,
+        reasoning: resp.reasoning,
+        content: resp.content,
+        tool_calls: resp.tool_calls,
+        finish_reason: resp.finish_reason,
+        usage: resp.usage,
+        timings: None,
+    }
+}
+
+// ---- tracking ----
+
+const MAX_PENDING: usize = 1024;
+const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Default)]
+struct Pending {
+    is_llm: bool,
+    request: Option<HttpRequestEvent>,
+    // first response event: status and headers
+    response: Option<HttpResponseEvent>,
+    marks: TimingMarks,
+    // LLM only: raw body plus an incremental parse to spot token-bearing chunks
+    body: Vec<u8>,
+    line_buf: Vec<u8>,
+    probe: ResponseAcc,
+}
+
+impl Pending {
+    fn feed(&mut self, chunk: &[u8], at: Option<u64>) {
+        if self.body.len() >= MAX_BODY_BYTES {
+            return;
+        }
+        self.body.extend_from_slice(chunk);
+        self.line_buf.extend_from_slice(chunk);
+        let before = self.probe.progress();
+        while let Some(pos) = self.line_buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.line_buf.drain(..=pos).collect();
+            self.probe.feed_line(&String::from_utf8_lossy(&line));
+        }
+        if self.probe.progress() > before
+            && let Some(t) = at
+        {
+            self.marks.token_us.push(t);
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingTable {
+    map: HashMap<String, Pending>,
+    order: VecDeque<String>,
+}
+
+impl PendingTable {
+    fn entry(&mut self, id: &str) -> &mut Pending {
+        if !self.map.contains_key(id) {
+            // bounded: exchanges that never complete are dropped oldest first
+            while self.order.len() >= MAX_PENDING {
+                if let Some(old) = self.order.pop_front() {
+                    self.map.remove(&old);
+                }
+            }
+            self.order.push_back(id.to_string());
+        }
+        self.map.entry(id.to_string()).or_default()
+    }
+
+    fn take(&mut self, id: &str) -> Option<Pending> {
+        let p = self.map.remove(id)?;
+        self.order.retain(|x| x != id);
+        Some(p)
+    }
+}
+
+/// Decorator sink: forwards every event, attaches usage and timings to
+/// completion events and, for LLM responses, also emits the paired `LlmExchange`.
+pub struct ExchangeTracker {
+    inner: SharedSink,
+    pending: Mutex<PendingTable>,
+}
+
+impl ExchangeTracker {
+    pub fn new(inner: SharedSink) -> Self {
+        Self {
+            inner,
+            pending: Mutex::new(PendingTable::default()),
+        }
+    }
+
+    fn on_complete(
+        &self,
+        done: &ResponseCompleteEvent,
+    ) -> (Option<ExchangeTimings>, Option<LlmExchange>) {
+        let Some(mut p) = self.pending.lock().unwrap().take(&done.id) else {
+            return (None, None);
+        };
+        if !p.is_llm {
+            return (p.marks.compute(None), None);
+        }
+        let mut exchange = build_exchange(
+            p.request.as_ref(),
+            p.response.as_ref(),
+            &p.body,
+            &done.timestamp,
+        );
+        // non-streamed bodies only become readable once the last byte is in
+        let has_output = !exchange.content.is_empty()
+            || !exchange.reasoning.is_empty()
+            || !exchange.tool_calls.is_empty();
+        if p.marks.token_us.is_empty()
+            && has_output
+            && let Some(last) = p.marks.last_us
+        {
+            p.marks.token_us.push(last);
+        }
+        let output_tokens = exchange.usage.as_ref().and_then(|u| u.output_tokens);
+        exchange.timings = p.marks.compute(output_tokens);
+        (exchange.timings.clone(), Some(exchange))
+    }
+}
+
+impl EventSink for ExchangeTracker {
+    fn emit(&self, event: &CaptureEvent) {
+        if let CaptureEvent::ResponseComplete(done) = event {
+            let (timings, exchange) = self.on_complete(done);
+            let mut done = done.clone();
+            done.usage = exchange.as_ref().and_then(|e| e.usage.clone());
+            done.timings = timings;
+            self.inner.emit(&CaptureEvent::ResponseComplete(done));
+            if let Some(exchange) = exchange {
+                self.inner
+                    .emit(&CaptureEvent::LlmExchange(Box::new(exchange)));
+            }
+            return;
+        }
+        self.inner.emit(event);
+        match event {
+            CaptureEvent::Request(evt) => {
+                let mut table = self.pending.lock().unwrap();
+                let p = table.entry(&evt.id);
+                p.marks.request_us = evt.mono_us;
+                if evt.is_llm {
+                    p.is_llm = true;
+                    p.request = Some(evt.clone());
+                }
+            }
+            CaptureEvent::Response(evt) => {
+                let mut table = self.pending.lock().unwrap();
+                let p = table.entry(&evt.id);
+                p.is_llm |= evt.is_llm;
+                if evt.mono_us.is_some() {
+                    p.marks.last_us = evt.mono_us;
+                }
+                if p.response.is_none() {
+                    p.marks.head_us = evt.mono_us;
+                    let mut head = evt.clone();
+                    head.body_base64 = None;
+                    p.response = Some(head);
+                }
+                if p.is_llm {
+                    p.feed(&decode_body(&evt.body_base64), evt.mono_us);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamed_tool_call_deltas_are_merged() {
+        let body = concat!(
+            "data: {\"model\":\"gpt-4o\",\"choices\":[{\"delta\":{\"reasoning_content\":\"think \"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"{\\\"ci\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"ty\\\":1}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let r = parse_response_body(body);
+        assert_eq!(r.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(r.reasoning, "think ");
+        assert_eq!(r.content, "Hello");
+        assert_eq!(r.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(r.tool_calls.len(), 1);
+        assert_eq!(r.tool_calls[0].id.as_deref(), Some("call_1"));
+        assert_eq!(r.tool_calls[0].name.as_deref(), Some("get_weather"));
+        assert_eq!(r.tool_calls[0].arguments, "{\"city\":1}");
+    }
+
+    #[test]
+    fn ollama_ndjson_with_chunk_framing() {
+        let body = "3a\r\n{\"message\":{\"role\":\"assistant\",\"thinking\":\"hm\",\"content\":\"Hi\"}}\r\n\
+                    40\r\n{\"message\":{\"content\":\" there\"},\"done\":true,\"done_reason\":\"stop\"}\r\n0\r\n\r\n";
+        let r = parse_response_body(body);
+        assert_eq!(r.reasoning, "hm");
+        assert_eq!(r.content, "Hi there");
+        assert_eq!(r.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn ollama_generate_ndjson() {
+        let body = "{\"model\":\"qwen3\",\"response\":\"\",\"thinking\":\"let me\",\"done\":false}\n\
+                    {\"model\":\"qwen3\",\"response\":\"Hello\",\"done\":false}\n\
+                    {\"model\":\"qwen3\",\"response\":\" world\",\"done\":false}\n\
+                    {\"model\":\"qwen3\",\"response\":\"\",\"done\":true,\"done_reason\":\"stop\",\"prompt_eval_count\":5,\"eval_count\":3}\n";
+        let r = parse_response_body(body);
+        assert_eq!(r.model.as_deref(), Some("qwen3"));
+        assert_eq!(r.reasoning, "let me");
+        assert_eq!(r.content, "Hello world");
+        assert_eq!(r.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(r.usage.and_then(|u| u.output_tokens), Some(3));
+    }
+
+    #[test]
+    fn anthropic_stream_blocks() {
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude-sonnet-4\",\"role\":\"assistant\",\"content\":[],\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"check weather\"}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Let me look.\"}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":2,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"get_weather\",\"input\":{}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"city\\\":\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"\\\"Paris\\\"}\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":42}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+        let r = parse_response_body(body);
+        assert_eq!(r.model.as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(r.reasoning, "check weather");
+        assert_eq!(r.content, "Let me look.");
+        assert_eq!(r.finish_reason.as_deref(), Some("tool_use"));
+        assert_eq!(r.tool_calls.len(), 1);
+        assert_eq!(r.tool_calls[0].id.as_deref(), Some("toolu_1"));
+        assert_eq!(r.tool_calls[0].name.as_deref(), Some("get_weather"));
+        assert_eq!(r.tool_calls[0].arguments, "{\"city\":\"Paris\"}");
+        let u = r.usage.unwrap();
+        assert_eq!((u.input_tokens, u.output_tokens), (Some(25), Some(42)));
+
+        let whole = r#"{"type":"message","model":"claude","content":[{"type":"text","text":"Hi"},{"type":"tool_use","id":"t","name":"f","input":{"a":1}}],"stop_reason":"end_turn"}"#;
+        let r = parse_response_body(whole);
+        assert_eq!(r.content, "Hi");
+        assert_eq!(r.tool_calls[0].arguments, "{\"a\":1}");
+        assert_eq!(r.finish_reason.as_deref(), Some("end_turn"));
+    }
+
+    #[test]
+    fn gemini_stream_and_request() {
+        let body = concat!(
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"plan\",\"thought\":true}],\"role\":\"model\"}}],\"modelVersion\":\"gemini-2.5-flash\"}\r\n\r\n",
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Sunny\"},{\"functionCall\":{\"name\":\"get_weather\",\"args\":{\"city\":\"Paris\"}}}],\"role\":\"model\"},\"finishReason\":\"STOP\"}],",
+            "\"usageMetadata\":{\"promptTokenCount\":10,\"candidatesTokenCount\":5,\"totalTokenCount\":19,\"thoughtsTokenCount\":4}}\r\n\r\n",
+        );
+        let r = parse_response_body(body);
+        assert_eq!(r.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(r.reasoning, "plan");
+        assert_eq!(r.content, "Sunny");
+        assert_eq!(r.finish_reason.as_deref(), Some("STOP"));
+        assert_eq!(r.tool_calls[0].name.as_deref(), Some("get_weather"));
+        assert_eq!(r.tool_calls[0].arguments, "{\"city\":\"Paris\"}");
+        let u = r.usage.unwrap();
+        assert_eq!(
+            (u.input_tokens, u.reasoning_tokens, u.total()),
+            (Some(10), Some(4), 19)
+        );
+
+        let req = parse_request_body(
+            br#"{"systemInstruction":{"parts":[{"text":"be brief"}]},
+                 "contents":[{"role":"user","parts":[{"text":"weather?"}]},
+                             {"role":"model","parts":[{"functionCall":{"name":"get_weather","args":{}}}]}],
+                 "generationConfig":{"temperature":0.5,"maxOutputTokens":256},
+                 "tools":[{"functionDeclarations":[{"name":"get_weather"}]}]}"#,
+        )
+        .unwrap();
+        let roles: Vec<&str> = req.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant"]);
+        assert_eq!(req.params.max_tokens, Some(256));
+        assert_eq!(req.tools, vec!["get_weather".to_string()]);
+        assert_eq!(
+            model_from_path("/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse")
+                .as_deref(),
+            Some("gemini-2.5-flash")
+        );
+    }
+
+    #[test]
+    fn responses_api_typed_events() {
+        let body = concat!(
+            "event: response.created\n",
+            "data: {\"type\":\"response.created\",\"response\":{\"id\":\"resp_1\",\"object\":\"response\",\"model\":\"gpt-5\",\"status\":\"in_progress\",\"output\":[]}}\n\n",
+            "event: response.reasoning_summary_text.delta\n",
+            "data: {\"type\":\"response.reasoning_summary_text.delta\",\"output_index\":0,\"delta\":\"thinking\"}\n\n",
+            "event: response.output_text.delta\n",
+            "data: {\"type\":\"response.output_text.delta\",\"output_index\":1,\"delta\":\"Hi\"}\n\n",
+            "event: response.output_text.done\n",
+            "data: {\"type\":\"response.output_text.done\",\"output_index\":1,\"text\":\"Hi\"}\n\n",
+            "event: response.output_item.added\n",
+            "data: {\"type\":\"response.output_item.added\",\"output_index\":2,\"item\":{\"type\":\"function_call\",\"id\":\"fc_1\",\"call_id\":\"call_1\",\"name\":\"lookup\",\"arguments\":\"\"}}\n\n",
+            "event: response.function_call_arguments.delta\n",
+            "data: {\"type\":\"response.function_call_arguments.delta\",\"output_index\":2,\"delta\":\"{\\\"q\\\":1}\"}\n\n",
+            "event: response.completed\n",
+            "data: {\"type\":\"response.completed\",\"response\":{\"object\":\"response\",\"model\":\"gpt-5\",\"status\":\"completed\",\"usage\":{\"input_tokens\":8,\"output_tokens\":6,\"total_tokens\":14,\"output_tokens_details\":{\"reasoning_tokens\":3}}}}\n\n",
+        );
+        let r = parse_response_body(body);
+        assert_eq!(r.model.as_deref(), Some("gpt-5"));
+        assert_eq!(r.reasoning, "thinking");
+        assert_eq!(r.content, "Hi");
+        assert_eq!(r.finish_reason.as_deref(), Some("completed"));
+        assert_eq!(r.tool_calls[0].id.as_deref(), Some("call_1"));
+        assert_eq!(r.tool_calls[0].name.as_deref(), Some("lookup"));
+        assert_eq!(r.tool_calls[0].arguments, "{\"q\":1}");
+        let u = r.usage.unwrap();
+        assert_eq!((u.total_tokens, u.reasoning_tokens), (Some(14), Some(3)));
+
+        let req = parse_request_body(
+            br#"{"model":"gpt-5","instructions":"be brief","max_output_tokens":100,
+                 "input":[{"role":"user","content":[{"type":"input_text","text":"hi"}]},
+                          {"type":"function_call_output","call_id":"call_1","output":"42"}]}"#,
+        )
+        .unwrap();
+        let roles: Vec<&str> = req.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "tool"]);
+        assert_eq!(req.messages[1].content, "hi");
+        assert_eq!(req.messages[2].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(req.params.max_tokens, Some(100));
+    }
+
+    #[test]
+    fn request_messages_and_params() {
+        let body = br#"{"model":"llama3","system":"be brief","stream":true,
+            "messages":[{"role":"user","content":[{"type":"text","text":"hi"}]},
+                        {"role":"assistant","content":null,"tool_calls":[{"id":"c1","type":"function","function":{"name":"f","arguments":{"a":1}}}]},
+                        {"role":"tool","tool_call_id":"c1","content":"ok"}],
+            "options":{"temperature":0.2,"num_predict":64,"stop":["\n"]},
+            "tools":[{"type":"function","function":{"name":"f"}}]}"#;
+        let req = parse_request_body(body).unwrap();
+        assert_eq!(req.model.as_deref(), Some("llama3"));
+        assert!(req.stream);
+        let roles: Vec<&str> = req.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "tool"]);
+        assert_eq!(req.messages[1].content, "hi");
+        assert_eq!(req.messages[2].tool_calls[0].arguments, "{\"a\":1}");
+        assert_eq!(req.messages[3].tool_call_id.as_deref(), Some("c1"));
+        assert_eq!(req.params.temperature, Some(0.2));
+        assert_eq!(req.params.max_tokens, Some(64));
+        assert_eq!(req.params.stop, vec!["\n".to_string()]);
+        assert_eq!(req.tools, vec!["f".to_string()]);
+    }
+
+    #[test]
+    fn tracker_emits_exchange_with_timings() {
+        use crate::events::tests::CollectSink;
+        use std::sync::Arc;
+
+        let collected = Arc::new(CollectSink::default());
+        let tracker = ExchangeTracker::new(collected.clone());
+        let body = br#"{"model":"m","stream":true,"messages":[{"role":"user","content":"hi"}]}"#;
+        let req = HttpRequestEvent {
+            id: "x".into(),
+            timestamp: "2025-01-01T00:00:00Z".into(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 5000,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 11434,
+            method: "POST".into(),
+            path: "/v1/chat/completions".into(),
+            version: "1.1".into(),
+            headers: vec![],
+            body_base64: Some(general_purpose::STANDARD.encode(body)),
+            body_len: body.len(),
+            process_name: None,
+            pid: None,
+            process: None,
+            is_llm: true,
+            llm_provider: Some("openai_compatible".into()),
+            mono_us: Some(0),
+            conn_id: None,
+            conn_seq: None,
+        };
+        tracker.emit(&CaptureEvent::Request(req));
+        let chunk = |data: &str, at: u64| {
+            CaptureEvent::Response(HttpResponseEvent {
+                id: "x".into(),
+                timestamp: "2025-01-01T00:00:01Z".into(),
+                src_ip: "127.0.0.1".into(),
+                src_port: 11434,
+                dst_ip: "127.0.0.1".into(),
+                dst_port: 5000,
+                status_code: 200,
+                reason: None,
+                version: "1.1".into(),
+                headers: vec![],
+                body_base64: Some(general_purpose::STANDARD.encode(data)),
+                body_len: data.len(),
+                process_name: None,
+                pid: None,
+                process: None,
+                is_llm: true,
+                llm_provider: Some("openai_compatible".into()),
+                mono_us: Some(at),
+            })
+        };
+        tracker.emit(&chunk("", 100_000));
+        tracker.emit(&chunk(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"He\"}}]}\n\n",
+            300_000,
+        ));
+        tracker.emit(&chunk(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"y\"}}]}\n\ndata: [DONE]\n\n",
+            400_000,
+        ));
+        tracker.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+            "x",
+        )));
+
+        let events = collected.0.lock().unwrap();
+        let names: Vec<&str> = events.iter().map(|e| e.name()).collect();
+        assert_eq!(
+            names[names.len() - 2..],
+            ["onHttpResponseComplete", "onLlmExchange"]
+        );
+        let CaptureEvent::LlmExchange(ex) = events.last().unwrap() else {
+            panic!("expected exchange");
+        };
+        assert_eq!(ex.model.as_deref(), Some("m"));
+        assert_eq!(ex.messages[0].content, "hi");
+        assert_eq!(ex.content, "Hey");
+        let t = ex.timings.as_ref().unwrap();
+        assert_eq!(
+            (t.ttfb_ms, t.ttft_ms, t.total_ms),
+            (Some(100.0), Some(300.0), Some(400.0))
+        );
+        // no usage in the stream: chunks are counted, tokens are not guessed
+        assert_eq!((t.output_tokens, t.token_chunks), (None, 2));
+        assert_eq!(t.tokens_per_sec, None);
+    }
+
+    #[test]
+    fn tracker_times_ollama_generate() {
+        use crate::events::tests::CollectSink;
+        use std::sync::Arc;
+
+        let collected = Arc::new(CollectSink::default());
+        let tracker = ExchangeTracker::new(collected.clone());
+        let body = br#"{"model":"qwen3","prompt":"hi"}"#;
+        tracker.emit(&CaptureEvent::Request(HttpRequestEvent {
+            id: "g".into(),
+            timestamp: "2025-01-01T00:00:00Z".into(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 5000,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 11434,
+            method: "POST".into(),
+            path: "/api/generate".into(),
+            version: "1.1".into(),
+            headers: vec![],
+            body_base64: Some(general_purpose::STANDARD.encode(body)),
+            body_len: body.len(),
+            process_name: None,
+            pid: None,
+            process: None,
+            is_llm: true,
+            llm_provider: Some("ollama".into()),
+            mono_us: Some(0),
+            conn_id: None,
+            conn_seq: None,
+        }));
+        for (data, at) in [
+            ("{\"response\":\"Hel\",\"done\":false}\n", 200_000),
+            ("{\"response\":\"lo\",\"done\":false}\n", 300_000),
+            (
+                "{\"response\":\"\",\"done\":true,\"eval_count\":2}\n",
+                500_000,
+            ),
+        ] {
+            tracker.emit(&CaptureEvent::Response(HttpResponseEvent {
+                id: "g".into(),
+                timestamp: "2025-01-01T00:00:01Z".into(),
+                src_ip: "127.0.0.1".into(),
+                src_port: 11434,
+                dst_ip: "127.0.0.1".into(),
+                dst_port: 5000,
+                status_code: 200,
+                reason: None,
+                version: "1.1".into(),
+                headers: vec![],
+                body_base64: Some(general_purpose::STANDARD.encode(data)),
+                body_len: data.len(),
+                process_name: None,
+                pid: None,
+                process: None,
+                is_llm: true,
+                llm_provider: Some("ollama".into()),
+                mono_us: Some(at),
+            }));
+        }
+        tracker.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+            "g",
+        )));
+
+        let events = collected.0.lock().unwrap();
+        let CaptureEvent::LlmExchange(ex) = events.last().unwrap() else {
+            panic!("expected exchange");
+        };
+        assert_eq!(ex.content, "Hello");
+        let t = ex.timings.as_ref().unwrap();
+        assert_eq!(t.ttft_ms, Some(200.0));
+        assert_eq!((t.output_tokens, t.token_chunks), (Some(2), 2));
+        assert_eq!(t.tokens_per_sec, Some(20.0));
+    }
+}
@@ -120,8 +120,9 @@ fn headers_match(compiled: &RuleSideCompiled, headers: &Vec<Header>) -> bool {
     }
     'rules: for hr in compiled.headers.iter() {
         for h in headers.iter() {
+            // 头名不区分大小写，规则按小写书写
             let name_ok = match &hr.name {
-                Some(rx) => rx.is_match(&h.name),
+                Some(rx) => rx.is_match(&h.name.to_ascii_lowercase()),
                 None => true,
             };
             let val_ok = match &hr.value {
@@ -174,6 +175,16 @@ pub fn load_llm_rules_from_json_str(s: &str) -> Option<LlmRules> {
     Some(compile_rules(raw))
 }
 
+// 当前生效的规则文本：工作目录下可解析的 llm_rules.json 优先，否则为内置规则
+pub fn effective_llm_rules_json() -> String {
+    if let Ok(s) = std::fs::read_to_string("llm_rules.json")
+        && load_llm_rules_from_json_str(&s).is_some()
+    {
+        return s;
+    }
+    DEFAULT_LLM_RULES_JSON.to_string()
+}
+
 pub fn load_llm_rules() -> LlmRules {
     if let Ok(s) = std::fs::read_to_string("llm_rules.json") {
         if let Some(r) = load_llm_rules_from_json_str(&s) {
@@ -184,6 +195,20 @@ pub fn load_llm_rules() -> LlmRules {
 }
 
 impl LlmRules {
+    pub fn providers(&self) -> Vec<&str> {
+        self.rules.iter().map(|r| r.provider.as_str()).collect()
+    }
+    /// Server ports named in `provider_by_port`, sorted.
+    pub fn ports(&self) -> Vec<u16> {
+        let mut ports: Vec<u16> = self
+            .rules
+            .iter()
+            .flat_map(|r| r.provider_by_port.keys().copied())
+            .collect();
+        ports.sort_unstable();
+        ports.dedup();
+        ports
+    }
     pub fn match_request(&self, evt: &HttpRequestEvent) -> Option<String> {
         for r in &self.rules {
             if let Some(side) = &r.request {
@@ -1,11 +1,76 @@
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
 
-static PROCESS_CACHE: Lazy<DashMap<u16, (Option<String>, Option<i32>, Instant)>> =
-    Lazy::new(|| DashMap::new());
-static PROCESS_LOOKUP_INFLIGHT: Lazy<DashMap<u16, ()>> = Lazy::new(|| DashMap::new());
+/// 连接四元组，`local` 是要查找的进程自己这一端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnKey {
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+}
+
+impl ConnKey {
+    pub fn new(local: SocketAddr, remote: SocketAddr) -> Self {
+        Self {
+            local: canonical(local),
+            remote: canonical(remote),
+        }
+    }
+
+    pub fn parse(
+        local_ip: &str,
+        local_port: u16,
+        remote_ip: &str,
+        remote_port: u16,
+    ) -> Option<Self> {
+        let local = SocketAddr::new(local_ip.parse().ok()?, local_port);
+        let remote = SocketAddr::new(remote_ip.parse().ok()?, remote_port);
+        Some(Self::new(local, remote))
+    }
+}
+
+// 双栈 socket 上的 IPv4 连接以 ::ffff:a.b.c.d 出现，统一成 IPv4
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4),
+        v4 => v4,
+    };
+    SocketAddr::new(ip, addr.port())
+}
+
+/// 进程详情，按 pid 缓存
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub exe: Option<String>,
+    pub argv: Vec<String>,
+    pub cwd: Option<String>,
+    pub uid: Option<u32>,
+    pub user: Option<String>,
+    /// 父进程链，由近及远（直到 pid 1）
+    pub parents: Vec<ParentProcess>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParentProcess {
+    pub pid: i32,
+    pub name: Option<String>,
+}
+
+// 连接四元组 -> (进程名, pid, 写入时间)
+type ProcessCache = DashMap<ConnKey, (Option<String>, Option<i32>, Instant)>;
+
+static PROCESS_CACHE: Lazy<ProcessCache> = Lazy::new(|| DashMap::new());
+static PROCESS_LOOKUP_INFLIGHT: Lazy<DashMap<ConnKey, ()>> = Lazy::new(|| DashMap::new());
 const PROCESS_CACHE_TTL: Duration = Duration::from_secs(10);
+// 每条客户端连接一个键，长时间运行时需要定期清理
+const MAX_CACHE_ENTRIES: usize = 4096;
+static PROCESS_INFO_CACHE: Lazy<DashMap<i32, (Option<ProcessInfo>, Instant)>> =
+    Lazy::new(DashMap::new);
+// pid 在此时间内被复用的概率很低
+const PROCESS_INFO_TTL: Duration = Duration::from_secs(60);
+const MAX_PARENT_DEPTH: usize = 32;
 
 // Debug switch for process lookup path
 static PROC_DEBUG: Lazy<bool> = Lazy::new(|| match std::env::var("PROCESS_LOOKUP_DEBUG") {
@@ -14,69 +79,48 @@ static PROC_DEBUG: Lazy<bool> = Lazy::new(|| match std::env::var("PROCESS_LOOKUP
 });
 macro_rules! plog { ($($arg:tt)*) => {{ if *PROC_DEBUG { eprintln!($($arg)*); } }}; }
 
-#[cfg(target_os = "macos")]
-pub fn try_lookup_process(port: u16, is_server_side: bool) -> (Option<String>, Option<i32>) {
-    if let Some(entry) = PROCESS_CACHE.get(&port) {
+// 写入前调用：超过上限时丢弃过期项，仍超限则整体清空（只是缓存）
+fn make_room<K: Eq + std::hash::Hash, V>(
+    map: &DashMap<K, V>,
+    ttl: Duration,
+    inserted_at: impl Fn(&V) -> Instant,
+) {
+    if map.len() < MAX_CACHE_ENTRIES {
+        return;
+    }
+    map.retain(|_, v| inserted_at(v).elapsed() < ttl);
+    if map.len() >= MAX_CACHE_ENTRIES {
+        map.clear();
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub fn try_lookup_process(key: ConnKey, is_server_side: bool) -> (Option<String>, Option<i32>) {
+    if let Some(entry) = PROCESS_CACHE.get(&key) {
         let (name, pid, ts) = (&entry.0, &entry.1, &entry.2);
         if ts.elapsed() < PROCESS_CACHE_TTL {
             return (name.clone(), *pid);
         }
     }
     // 若缓存没有，触发一次异步查询
-    let spawned = PROCESS_LOOKUP_INFLIGHT.insert(port, ()).is_none();
+    let spawned = PROCESS_LOOKUP_INFLIGHT.insert(key, ()).is_none();
     if spawned {
         std::thread::spawn(move || {
-            use std::process::Command;
-            let mut best: Option<(String, i32, i32)> = None; // (pname, pid, score)
-            let candidates = ["/usr/sbin/lsof", "lsof"];
-            for bin in candidates.iter() {
-                if let Ok(output) = Command::new(bin)
-                    .arg("-n")
-                    .arg("-P")
-                    .arg(format!("-iTCP:{}", port))
-                    .output()
-                {
-                    if output.status.success() {
-                        let s = String::from_utf8_lossy(&output.stdout);
-                        for (idx, line) in s.lines().enumerate() {
-                            if idx == 0 {
-                                continue;
-                            }
-                            let parts: Vec<&str> = line.split_whitespace().collect();
-                            if parts.len() < 2 {
-                                continue;
-                            }
-                            let pname = parts[0].to_string();
-                            let pid = match parts[1].parse::<i32>() {
-                                Ok(v) => v,
-                                Err(_) => continue,
-                            };
-                            let score = if line.contains(&format!(":{}->", port)) {
-                                3
-                            } else if line.contains(&format!(":{}", port)) {
-                                1
-                            } else {
-                                0
-                            };
-                            match &best {
-                                Some((_, _, bscore)) if *bscore >= score => {}
-                                _ => {
-                                    best = Some((pname.clone(), pid, score));
-                                }
-                            }
-                        }
-                        if best.is_some() {
-                            break;
-                        }
-                    }
+            let (name_opt, pid_opt) = match find_socket_owner(&key) {
+                Some((p, pid)) => {
+                    // 顺带预热 pid 缓存；lsof COMMAND 与 comm 都会截断，优先用可执行文件名
+                    let exe_name = process_info(pid).and_then(|i| i.exe).and_then(|e| {
+                        std::path::Path::new(&e)
+                            .file_name()
+                            .map(|n| n.to_string_lossy().into_owned())
+                    });
+                    (Some(exe_name.unwrap_or(p)), Some(pid))
                 }
-            }
-            let (name_opt, pid_opt) = match best {
-                Some((p, pid, _)) => (Some(p), Some(pid)),
                 None => (None, None),
             };
-            PROCESS_CACHE.insert(port, (name_opt, pid_opt, Instant::now()));
-            PROCESS_LOOKUP_INFLIGHT.remove(&port);
+            make_room(&PROCESS_CACHE, PROCESS_CACHE_TTL, |v| v.2);
+            PROCESS_CACHE.insert(key, (name_opt, pid_opt, Instant::now()));
+            PROCESS_LOOKUP_INFLIGHT.remove(&key);
         });
     }
     // 等待策略：
@@ -92,40 +136,432 @@ pub fn try_lookup_process(port: u16, is_server_side: bool) -> (Option<String>, O
             .unwrap_or(0)
     };
     if wait_ms == 0 {
-        plog!(
-            "[proc] scheduled lookup for port {}, return immediately",
-            port
-        );
+        plog!("[proc] scheduled lookup for {:?}, return immediately", key);
         return (None, None);
     }
     let soft_deadline = Instant::now() + Duration::from_millis(wait_ms);
     while Instant::now() < soft_deadline {
-        if let Some(entry) = PROCESS_CACHE.get(&port) {
+        if let Some(entry) = PROCESS_CACHE.get(&key) {
             let (name, pid, ts) = (&entry.0, &entry.1, &entry.2);
             if ts.elapsed() < PROCESS_CACHE_TTL {
                 plog!(
-                    "[proc] cache hit after wait: port={} name={:?} pid={:?}",
-                    port,
+                    "[proc] cache hit after wait: {:?} name={:?} pid={:?}",
+                    key,
                     name,
                     pid
                 );
                 return (name.clone(), *pid);
             } else {
-                PROCESS_CACHE.remove(&port);
+                PROCESS_CACHE.remove(&key);
             }
         }
         std::thread::sleep(Duration::from_millis(5));
     }
-    plog!("[proc] wait timeout for port {}", port);
+    plog!("[proc] wait timeout for {:?}", key);
     (None, None)
 }
 
-#[cfg(not(target_os = "macos"))]
-pub fn try_lookup_process(_port: u16, _is_server_side: bool) -> (Option<String>, Option<i32>) {
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn try_lookup_process(_key: ConnKey, _is_server_side: bool) -> (Option<String>, Option<i32>) {
     (None, None)
 }
 
+#[cfg(target_os = "macos")]
+fn find_socket_owner(key: &ConnKey) -> Option<(String, i32)> {
+    use std::process::Command;
+    let port = key.local.port();
+    // lsof 的 NAME 列形如 127.0.0.1:5000->127.0.0.1:38080，完全匹配四元组优先
+    let exact = format!("{}->{}", key.local, key.remote);
+    let mut best: Option<(String, i32, i32)> = None; // (pname, pid, score)
+    let candidates = ["/usr/sbin/lsof", "lsof"];
+    for bin in candidates.iter() {
+        if let Ok(output) = Command::new(bin)
+            .arg("-n")
+            .arg("-P")
+            .arg(format!("-iTCP:{}", port))
+            .output()
+        {
+            if output.status.success() {
+                let s = String::from_utf8_lossy(&output.stdout);
+                for (idx, line) in s.lines().enumerate() {
+                    if idx == 0 {
+                        continue;
+                    }
+                    let parts: Vec<&str> = line.split_whitespace().collect();
+                    if parts.len() < 2 {
+                        continue;
+                    }
+                    let pname = parts[0].to_string();
+                    let pid = match parts[1].parse::<i32>() {
+                        Ok(v) => v,
+                        Err(_) => continue,
+                    };
+                    let score = if line.contains(&exact) {
+                        5
+                    } else if line.contains(&format!(":{}->", port)) {
+                        3
+                    } else if line.contains(&format!(":{}", port)) {
+                        1
+                    } else {
+                        0
+                    };
+                    match &best {
+                        Some((_, _, bscore)) if *bscore >= score => {}
+                        _ => {
+                            best = Some((pname.clone(), pid, score));
+                        }
+                    }
+                }
+                if best.is_some() {
+                    break;
+                }
+            }
+        }
+    }
+    best.map(|(p, pid, _)| (p, pid))
+}
+
+#[cfg(target_os = "linux")]
+fn find_socket_owner(key: &ConnKey) -> Option<(String, i32)> {
+    find_socket_owner_in(std::path::Path::new("/proc"), key)
+}
+
+// /proc/net/tcp{,6} 找到四元组对应的 socket inode，再扫描 /proc/*/fd 找持有者
+#[cfg(target_os = "linux")]
+fn find_socket_owner_in(proc_root: &std::path::Path, key: &ConnKey) -> Option<(String, i32)> {
+    let inode = ["net/tcp", "net/tcp6"].iter().find_map(|f| {
+        let text = std::fs::read_to_string(proc_root.join(f)).ok()?;
+        text.lines()
+            .skip(1)
+            .find_map(|line| socket_inode(line, key))
+    })?;
+    let target = format!("socket:[{}]", inode);
+    for entry in std::fs::read_dir(proc_root).ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let owns = fds.flatten().any(|fd| {
+            std::fs::read_link(fd.path()).is_ok_and(|l| l.as_os_str() == target.as_str())
+        });
+        if owns {
+            let name = std::fs::read_to_string(entry.path().join("comm"))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default();
+            plog!("[proc] {:?} -> inode {} pid {} ({})", key, inode, pid, name);
+            return Some((name, pid));
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn socket_inode(line: &str, key: &ConnKey) -> Option<u64> {
+    // sl local_address rem_address st tx:rx tr:when retrnsmt uid timeout inode
+    let cols: Vec<&str> = line.split_whitespace().collect();
+    if cols.len() < 10 {
+        return None;
+    }
+    let local = parse_proc_addr(cols[1])?;
+    let remote = parse_proc_addr(cols[2])?;
+    if ConnKey::new(local, remote) != *key {
+        return None;
+    }
+    cols[9].parse::<u64>().ok().filter(|i| *i != 0)
+}
+
+// 地址按本机字节序以 32 位分组打印，端口为普通十六进制
+#[cfg(target_os = "linux")]
+fn parse_proc_addr(s: &str) -> Option<SocketAddr> {
+    let (ip_hex, port_hex) = s.split_once(':')?;
+    let port = u16::from_str_radix(port_hex, 16).ok()?;
+    let ip = match ip_hex.len() {
+        8 => IpAddr::from(u32::from_str_radix(ip_hex, 16).ok()?.to_ne_bytes()),
+        32 => {
+            let mut b = [0u8; 16];
+            for i in 0..4 {
+                let w = u32::from_str_radix(&ip_hex[i * 8..i * 8 + 8], 16).ok()?;
+                b[i * 4..i * 4 + 4].copy_from_slice(&w.to_ne_bytes());
+            }
+            IpAddr::from(b)
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// 读取（并缓存）进程详情；会访问 /proc 或调用 ps，勿在 async 热路径上对未缓存的 pid 调用
+pub fn process_info(pid: i32) -> Option<ProcessInfo> {
+    if let Some(entry) = PROCESS_INFO_CACHE.get(&pid)
+        && entry.1.elapsed() < PROCESS_INFO_TTL
+    {
+        return entry.0.clone();
+    }
+    let info = read_process_info(pid);
+    make_room(&PROCESS_INFO_CACHE, PROCESS_INFO_TTL, |v| v.1);
+    PROCESS_INFO_CACHE.insert(pid, (info.clone(), Instant::now()));
+    info
+}
+
+/// 仅查缓存，不触发查询；事件构造路径使用
+pub fn cached_process_info(pid: Option<i32>) -> Option<ProcessInfo> {
+    let entry = PROCESS_INFO_CACHE.get(&pid?)?;
+    (entry.1.elapsed() < PROCESS_INFO_TTL)
+        .then(|| entry.0.clone())
+        .flatten()
+}
+
+fn parent_chain(
+    pid: i32,
+    mut parent_of: impl FnMut(i32) -> Option<(i32, Option<String>)>,
+) -> Vec<ParentProcess> {
+    let mut chain = Vec::new();
+    let mut cur = pid;
+    // 上一轮读到的父进程信息即本轮的 (ppid, name)，每个 pid 只读一次
+    let mut next = parent_of(cur);
+    while chain.len() < MAX_PARENT_DEPTH {
+        let Some((ppid, _)) = next else {
+            break;
+        };
+        if ppid <= 0 || ppid == cur {
+            break;
+        }
+        next = parent_of(ppid);
+        let name = next.as_ref().and_then(|(_, n)| n.clone());
+        chain.push(ParentProcess { pid: ppid, name });
+        if ppid == 1 {
+            break;
+        }
+        cur = ppid;
+    }
+    chain
+}
+
+#[cfg(target_os = "linux")]
+fn read_process_info(pid: i32) -> Option<ProcessInfo> {
+    let mut info = read_process_info_in(std::path::Path::new("/proc"), pid)?;
+    info.user = info.uid.and_then(|uid| {
+        std::fs::read_to_string("/etc/passwd")
+            .ok()
+            .and_then(|p| passwd_user(&p, uid))
+    });
+    Some(info)
+}
+
+#[cfg(target_os = "linux")]
+fn read_process_info_in(proc_root: &std::path::Path, pid: i32) -> Option<ProcessInfo> {
+    let dir = proc_root.join(pid.to_string());
+    let status = std::fs::read_to_string(dir.join("status")).ok()?;
+    let uid = status_field(&status, "Uid")
+        .and_then(|v| v.split_whitespace().next())
+        .and_then(|v| v.parse().ok());
+    let argv = std::fs::read(dir.join("cmdline"))
+        .map(|b| {
+            b.split(|c| *c == 0)
+                .filter(|a| !a.is_empty())
+                .map(|a| String::from_utf8_lossy(a).into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let link = |name: &str| {
+        std::fs::read_link(dir.join(name))
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned())
+    };
+    Some(ProcessInfo {
+        exe: link("exe"),
+        argv,
+        cwd: link("cwd"),
+        uid,
+        user: None,
+        parents: parent_chain(pid, |p| {
+            let st = std::fs::read_to_string(proc_root.join(p.to_string()).join("status")).ok()?;
+            let ppid = status_field(&st, "PPid")?.parse().ok()?;
+            Some((ppid, status_field(&st, "Name").map(str::to_string)))
+        }),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn status_field<'a>(status: &'a str, key: &str) -> Option<&'a str> {
+    status.lines().find_map(|l| {
+        let (k, v) = l.split_once(':')?;
+        (k == key).then(|| v.trim())
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn passwd_user(passwd: &str, uid: u32) -> Option<String> {
+    passwd.lines().find_map(|l| {
+        let mut cols = l.split(':');
+        let name = cols.next()?;
+        let id: u32 = cols.nth(1)?.parse().ok()?;
+        (id == uid).then(|| name.to_string())
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn ps_field(pid: i32, field: &str) -> Option<String> {
+    let out = std::process::Command::new("/bin/ps")
+        .args(["-ww", "-o", field, "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let s = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !s.is_empty()).then_some(s)
+}
+
+#[cfg(target_os = "macos")]
+fn read_process_info(pid: i32) -> Option<ProcessInfo> {
+    // macOS 的 comm= 为完整可执行文件路径
+    let exe = ps_field(pid, "comm=")?;
+    let uid = ps_field(pid, "uid=").and_then(|v| v.parse().ok());
+    // args= 以空格拼接，含空格的参数无法还原
+    let argv = ps_field(pid, "args=")
+        .map(|a| a.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+    let cwd = std::process::Command::new("/usr/sbin/lsof")
+        .args(["-a", "-p", &pid.to_string(), "-d", "cwd", "-Fn"])
+        .output()
+        .ok()
+        .and_then(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .find_map(|l| l.strip_prefix('n').map(str::to_string))
+        });
+    Some(ProcessInfo {
+        exe: Some(exe),
+        argv,
+        cwd,
+        uid,
+        user: ps_field(pid, "user="),
+        parents: parent_chain(pid, |p| {
+            let ppid = ps_field(p, "ppid=")?.parse().ok()?;
+            let name = ps_field(p, "comm=").map(|c| match c.rsplit_once('/') {
+                Some((_, base)) => base.to_string(),
+                None => c,
+            });
+            Some((ppid, name))
+        }),
+    })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn read_process_info(_pid: i32) -> Option<ProcessInfo> {
+    None
+}
+
 pub fn clear_process_lookup() {
     PROCESS_CACHE.clear();
     PROCESS_LOOKUP_INFLIGHT.clear();
+    PROCESS_INFO_CACHE.clear();
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_net_tcp_matches_full_tuple() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let hex_v4 = |ip: [u8; 4]| format!("{:08X}", u32::from_ne_bytes(ip));
+        let lo = hex_v4([127, 0, 0, 1]);
+        std::fs::create_dir_all(root.join("net")).unwrap();
+        std::fs::write(
+            root.join("net/tcp"),
+            format!(
+                "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+                 \x20  0: {lo}:1388 {lo}:9696 01 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0 20 4 30 10 -1\n"
+            ),
+        )
+        .unwrap();
+        std::fs::write(root.join("net/tcp6"), "  sl  local_address\n").unwrap();
+        let pid_dir = root.join("321");
+        std::fs::create_dir_all(pid_dir.join("fd")).unwrap();
+        std::fs::write(pid_dir.join("comm"), "curl\n").unwrap();
+        std::os::unix::fs::symlink("socket:[4242]", pid_dir.join("fd/3")).unwrap();
+
+        let key = ConnKey::parse("127.0.0.1", 5000, "127.0.0.1", 38550).unwrap();
+        assert_eq!(
+            find_socket_owner_in(root, &key),
+            Some(("curl".to_string(), 321))
+        );
+        // 同一端口、不同对端不算
+        let other = ConnKey::parse("127.0.0.1", 5000, "127.0.0.1", 80).unwrap();
+        assert_eq!(find_socket_owner_in(root, &other), None);
+        // 双栈 listener 看到的 v4-mapped 地址归一化后等价
+        let mapped = ConnKey::parse("::ffff:127.0.0.1", 5000, "127.0.0.1", 38550).unwrap();
+        assert_eq!(mapped, key);
+    }
+
+    #[test]
+    fn cache_drops_expired_entries_when_full() {
+        let map = DashMap::new();
+        let old = Instant::now() - Duration::from_secs(60);
+        for i in 0..MAX_CACHE_ENTRIES {
+            map.insert(i, if i == 0 { Instant::now() } else { old });
+        }
+        make_room(&map, Duration::from_secs(10), |t| *t);
+        assert_eq!(map.len(), 1, "only the fresh entry survives");
+    }
+
+    #[test]
+    fn process_info_from_proc() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let proc = |pid: i32, name: &str, ppid: i32| {
+            let dir = root.join(pid.to_string());
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("status"),
+                format!("Name:\t{name}\nPPid:\t{ppid}\nUid:\t1000\t1000\t1000\t1000\n"),
+            )
+            .unwrap();
+            dir
+        };
+        proc(1, "systemd", 0);
+        proc(40, "zsh", 1);
+        let dir = proc(321, "python3", 40);
+        std::fs::write(dir.join("cmdline"), b"python3\0agent.py\0--model\0gpt\0").unwrap();
+        std::os::unix::fs::symlink("/usr/bin/python3.12", dir.join("exe")).unwrap();
+        std::os::unix::fs::symlink("/home/dev/agent", dir.join("cwd")).unwrap();
+
+        let info = read_process_info_in(root, 321).unwrap();
+        assert_eq!(info.exe.as_deref(), Some("/usr/bin/python3.12"));
+        assert_eq!(info.argv, ["python3", "agent.py", "--model", "gpt"]);
+        assert_eq!(info.cwd.as_deref(), Some("/home/dev/agent"));
+        assert_eq!(info.uid, Some(1000));
+        let chain: Vec<_> = info
+            .parents
+            .iter()
+            .map(|p| (p.pid, p.name.as_deref().unwrap()))
+            .collect();
+        assert_eq!(chain, [(40, "zsh"), (1, "systemd")]);
+        // each ancestor is read once
+        let mut reads = Vec::new();
+        parent_chain(321, |p| {
+            reads.push(p);
+            let ppid = match p {
+                321 => 40,
+                40 => 1,
+                _ => 0,
+            };
+            Some((ppid, None))
+        });
+        assert_eq!(reads, [321, 40, 1]);
+        assert_eq!(
+            passwd_user(
+                "root:x:0:0::/root:/bin/sh\ndev:x:1000:1000::/home/dev:/bin/zsh\n",
+                1000
+            )
+            .as_deref(),
+            Some("dev")
+        );
+    }
 }
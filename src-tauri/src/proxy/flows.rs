@@ -1,20 +1,24 @@
 use base64::Engine as _;
 use base64::engine::general_purpose;
-use bytes::Bytes;
-use memchr::{memchr, memmem};
+use memchr::memmem;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
-use crate::http_shared::{Header, HttpResponseEvent, now_rfc3339};
-use crate::process_lookup::try_lookup_process;
+use crate::capture::framing::{BodyDecoder, BodyFraming, request_framing, response_framing};
+use crate::events::{CaptureEvent, SharedSink};
+use crate::http_shared::{HttpResponseEvent, ResponseCompleteEvent, mono_us, now_rfc3339};
+use crate::process_lookup::{ConnKey, cached_process_info, try_lookup_process};
 use crate::proxy::{
-    CONN_SEQ, ConnectTarget, InitialPacket, build_https_client, build_mitm_acceptor,
-    build_plain_http_forward, connect_via_upstream, current_upstream_proxy, looks_like_http,
-    now_millis, parse_connect_target, parse_plain_http_request, resolve_mitm_flags,
-    run_mitm_session, tunnel_with_eager_close,
+    CONN_SEQ, ConnectTarget, InitialPacket, PlainHttpRequest, UpstreamProxy, UpstreamStream,
+    build_https_client, build_mitm_acceptor, build_plain_http_forward, connect_via_upstream,
+    looks_like_http, now_millis, parse_connect_target, parse_plain_http_request,
+    parse_response_head, resolve_mitm_flags, route_for, run_mitm_session, tunnel_with_eager_close,
 };
 use crate::proxy_log;
 
+use super::mitm_service::MitmRequestContext;
+
 pub(crate) async fn read_initial_packet(
     inbound: &mut TcpStream,
 ) -> Result<Option<InitialPacket>, String> {
@@ -27,11 +31,8 @@ pub(crate) async fn read_initial_packet(
     Ok(Some(InitialPacket::parse(buf)))
 }
 
-pub(crate) async fn handle_client<
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
->(
-    app: &E,
+pub(crate) async fn handle_client(
+    sink: &SharedSink,
     llm_rules: &crate::llm_rules::LlmRules,
     inbound: &mut TcpStream,
     peer: std::net::SocketAddr,
@@ -64,9 +65,9 @@ pub(crate) async fn handle_client<
             Some(t) => t,
             None => return Err("invalid CONNECT request".into()),
         };
-        handle_connect_flow::<R, E>(app, llm_rules, inbound, peer, target).await
+        handle_connect_flow(sink, llm_rules, inbound, peer, target).await
     } else {
-        handle_plain_http_flow::<R, E>(app, llm_rules, inbound, peer, packet).await
+        handle_plain_http_flow(sink, llm_rules, inbound, peer, packet).await
     }
 }
 
@@ -76,22 +77,28 @@ pub(crate) async fn handle_connect_tunnel(
     conn_id: u64,
     target: &ConnectTarget,
 ) -> Result<(), String> {
-    let use_upstream = { current_upstream_proxy() };
-    let upstream = if let Some(proxy_url) = use_upstream {
-        eprintln!(
-            "[proxy][conn={}] tunneling via upstream proxy {}",
-            conn_id, proxy_url
+    let route = route_for(&target.host, target.port);
+    let upstream = if let Some(proxy_url) = route.upstream {
+        proxy_log!(
+            "[proxy][conn={}] tunneling via upstream proxy {} ({})",
+            conn_id,
+            proxy_url,
+            route.matched
         );
         connect_via_upstream(&proxy_url, &target.host, target.port)
             .await
             .map_err(|e| e.to_string())?
     } else {
-        eprintln!(
-            "[proxy][conn={}] tunneling direct to {}:{}",
-            conn_id, target.host, target.port
+        proxy_log!(
+            "[proxy][conn={}] tunneling direct to {}:{} ({})",
+            conn_id,
+            target.host,
+            target.port,
+            route.matched
         );
         TcpStream::connect(format!("{}:{}", target.host, target.port))
             .await
+            .map(UpstreamStream::Tcp)
             .map_err(|e| e.to_string())?
     };
     if let Err(e) = tunnel_with_eager_close(inbound, upstream).await {
@@ -106,22 +113,24 @@ pub(crate) async fn handle_connect_tunnel(
     Ok(())
 }
 
-pub(crate) async fn handle_connect_flow<R, E>(
-    app: &E,
+pub(crate) async fn handle_connect_flow(
+    sink: &SharedSink,
     llm_rules: &crate::llm_rules::LlmRules,
     inbound: &mut TcpStream,
     peer: std::net::SocketAddr,
     target: ConnectTarget,
-) -> Result<(), String>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+) -> Result<(), String> {
     let ConnectTarget { host, port } = target;
     let conn_id = CONN_SEQ.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-    eprintln!(
-        "[proxy][conn={}] CONNECT from {} => {}:{}",
-        conn_id, peer, host, port
+    let route = route_for(&host, port);
+    proxy_log!(
+        "[proxy][conn={}] CONNECT from {} => {}:{} via {} ({})",
+        conn_id,
+        peer,
+        host,
+        port,
+        route.upstream.as_deref().unwrap_or("direct"),
+        route.matched
     );
 
     inbound
@@ -181,193 +190,497 @@ where
         port
     );
 
-    run_mitm_session::<R, E>(
-        app,
-        llm_rules,
+    let local = tls_stream
+        .get_ref()
+        .0
+        .local_addr()
+        .map_err(|e| e.to_string())?;
+    let ctx = MitmRequestContext {
+        sink: sink.clone(),
+        llm_rules: llm_rules.clone(),
+        client: client_base,
         peer,
+        local,
         host,
         port,
         conn_id,
-        tls_stream,
-        client_base,
-    )
-    .await
+        last_activity: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(now_millis())),
+        inflight: Default::default(),
+        requests: Default::default(),
+        upgrade: Default::default(),
+    };
+    run_mitm_session(ctx, tls_stream).await
+}
+
+// 客户端 keep-alive 连接的空闲上限
+const PLAIN_KEEP_ALIVE_IDLE: Duration = Duration::from_secs(60);
+const UPSTREAM_READ_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REQUEST_HEAD: usize = 256 * 1024;
+// 请求体在转发前完整缓存，超过上限回 413
+const MAX_REQUEST_BODY: usize = 64 * 1024 * 1024;
+const MAX_RESPONSE_HEAD: usize = 256 * 1024;
+
+// Reads more client bytes into `buf`; false on EOF, error or keep-alive idle timeout.
+async fn fill_from_client(inbound: &mut TcpStream, buf: &mut Vec<u8>) -> bool {
+    let mut tmp = vec![0u8; 65536];
+    match tokio::time::timeout(PLAIN_KEEP_ALIVE_IDLE, inbound.read(&mut tmp)).await {
+        Ok(Ok(n)) if n > 0 => {
+            buf.extend_from_slice(&tmp[..n]);
+            true
+        }
+        _ => false,
+    }
 }
 
-pub(crate) async fn handle_plain_http_flow<R, E>(
-    app: &E,
+/// Takes the next complete request (head plus Content-Length or chunked body) off
+/// `buf`, reading more from the client as needed. `None` when the client is done.
+pub(crate) async fn read_plain_http_request(
+    inbound: &mut TcpStream,
+    buf: &mut Vec<u8>,
+) -> Result<Option<PlainHttpRequest>, String> {
+    loop {
+        // stray CRLFs between requests are allowed (RFC 9112 §2.2)
+        let blank = buf
+            .iter()
+            .take_while(|b| matches!(b, b'\r' | b'\n'))
+            .count();
+        buf.drain(..blank);
+        if let Some(head_end) = memmem::find(buf, b"\r\n\r\n") {
+            let packet = InitialPacket::parse(buf[..head_end + 4].to_vec());
+            if !looks_like_http(packet.first_line()) {
+                return Err("non-http request on keep-alive connection".into());
+            }
+            let mut request = parse_plain_http_request(&packet)?;
+            let framing = request_framing(&request.headers);
+            if matches!(framing, BodyFraming::Length(n) if n > MAX_REQUEST_BODY) {
+                write_error_response(inbound, "413 Content Too Large").await;
+                return Err("request body too large".into());
+            }
+            let mut decoder = BodyDecoder::new(framing);
+            let mut pos = head_end + 4;
+            let mut continued = false;
+            loop {
+                pos += decoder
+                    .decode(&buf[pos..], &mut request.body)
+                    .map_err(|_| "invalid chunked request body".to_string())?;
+                if decoder.is_done() {
+                    buf.drain(..pos);
+                    return Ok(Some(request));
+                }
+                // chunked bodies have no declared size; the undecoded tail is a partial chunk line
+                if request.body.len() > MAX_REQUEST_BODY || buf.len() - pos > MAX_REQUEST_HEAD {
+                    write_error_response(inbound, "413 Content Too Large").await;
+                    return Err("request body too large".into());
+                }
+                // we buffer the whole body before forwarding, so answer the expectation here
+                if !continued
+                    && request
+                        .header("expect")
+                        .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"))
+                {
+                    continued = true;
+                    inbound
+                        .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                if !fill_from_client(inbound, buf).await {
+                    return Err("client closed during request body".into());
+                }
+            }
+        }
+        if buf.len() > MAX_REQUEST_HEAD {
+            write_error_response(inbound, "431 Request Header Fields Too Large").await;
+            return Err("request head too large".into());
+        }
+        if !fill_from_client(inbound, buf).await {
+            return Ok(None);
+        }
+    }
+}
+
+// Upstream connection kept across requests of one client connection.
+struct UpstreamConn {
+    host: String,
+    port: u16,
+    stream: UpstreamStream,
+    // set when talking to an HTTP proxy, which takes absolute-form requests
+    via: Option<UpstreamProxy>,
+    // bytes read from upstream but not yet relayed
+    buf: Vec<u8>,
+    reused: bool,
+}
+
+impl UpstreamConn {
+    async fn connect(request: &PlainHttpRequest) -> Result<Self, String> {
+        let proxy = route_for(&request.host, request.port)
+            .upstream
+            .map(|u| UpstreamProxy::parse(&u))
+            .transpose()?;
+        let (stream, via) = match proxy {
+            Some(p) if p.is_http() => {
+                proxy_log!("[proxy] HTTP via upstream proxy {}:{}", p.host, p.port);
+                (p.connect().await?, Some(p))
+            }
+            Some(p) => {
+                proxy_log!(
+                    "[proxy] HTTP tunnel via socks upstream {}:{} to {}:{}",
+                    p.host,
+                    p.port,
+                    request.host,
+                    request.port
+                );
+                (p.tunnel(&request.host, request.port).await?, None)
+            }
+            None => {
+                let upstream_addr = format!("{}:{}", request.host, request.port);
+                proxy_log!("[proxy] HTTP direct connect upstream {}", upstream_addr);
+                let tcp = TcpStream::connect(&upstream_addr)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                (UpstreamStream::Tcp(tcp), None)
+            }
+        };
+        Ok(Self {
+            host: request.host.clone(),
+            port: request.port,
+            stream,
+            via,
+            buf: Vec::new(),
+            reused: false,
+        })
+    }
+
+    async fn fill(&mut self) -> Result<usize, String> {
+        let mut tmp = vec![0u8; 65536];
+        match tokio::time::timeout(UPSTREAM_READ_TIMEOUT, self.stream.read(&mut tmp)).await {
+            Ok(Ok(n)) => {
+                self.buf.extend_from_slice(&tmp[..n]);
+                Ok(n)
+            }
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("upstream read timeout".into()),
+        }
+    }
+}
+
+enum RelayOutcome {
+    // both connections can carry another exchange
+    KeepAlive,
+    Close,
+    // 101 Switching Protocols: the connections now carry the new protocol
+    Upgraded,
+}
+
+enum RelayError {
+    // reused upstream connection closed before answering; safe to retry on a new one
+    Stale,
+    // nothing was sent to the client yet
+    NoResponse(String),
+    // failed mid-response; the client connection has to be closed
+    Broken(String),
+}
+
+pub(crate) async fn handle_plain_http_flow(
+    sink: &SharedSink,
     llm_rules: &crate::llm_rules::LlmRules,
     inbound: &mut TcpStream,
     peer: std::net::SocketAddr,
     packet: InitialPacket,
-) -> Result<(), String>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
-    let request = parse_plain_http_request(&packet)?;
-    let mut req_evt = request.build_event(peer, llm_rules);
-    let (pname_http, pid_http) = try_lookup_process(peer.port(), false);
-    if pname_http.is_some() || pid_http.is_some() {
-        req_evt.process_name = pname_http;
-        req_evt.pid = pid_http;
+) -> Result<(), String> {
+    let proc_key = ConnKey::new(peer, inbound.local_addr().map_err(|e| e.to_string())?);
+    let conn_id = CONN_SEQ.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    let mut client_buf = packet.data;
+    let mut upstream: Option<UpstreamConn> = None;
+    let mut served = 0u64;
+    while let Some(request) = read_plain_http_request(inbound, &mut client_buf).await? {
+        served += 1;
+        let mut req_evt = request.build_event(peer, llm_rules);
+        req_evt.conn_id = Some(conn_id);
+        req_evt.conn_seq = Some(served);
+        let (pname_http, pid_http) = try_lookup_process(proc_key, false);
+        if pname_http.is_some() || pid_http.is_some() {
+            req_evt.process_name = pname_http;
+            req_evt.pid = pid_http;
+            req_evt.process = cached_process_info(req_evt.pid);
+        }
+        sink.emit(&CaptureEvent::Request(req_evt.clone()));
+
+        let mut conn = match upstream.take() {
+            Some(c) if c.host == request.host && c.port == request.port => c,
+            _ => match UpstreamConn::connect(&request).await {
+                Ok(c) => c,
+                Err(e) => {
+                    write_error_response(inbound, "502 Bad Gateway").await;
+                    return Err(e);
+                }
+            },
+        };
+        let result = loop {
+            match relay_plain_http_exchange(sink, inbound, &mut conn, peer, &request, &req_evt)
+                .await
+            {
+                Err(RelayError::Stale) if conn.reused => {
+                    proxy_log!("[proxy] reused upstream connection was closed, reconnecting");
+                    conn = match UpstreamConn::connect(&request).await {
+                        Ok(c) => c,
+                        Err(e) => {
+                            write_error_response(inbound, "502 Bad Gateway").await;
+                            return Err(e);
+                        }
+                    };
+                }
+                other => break other,
+            }
+        };
+        match result {
+            Ok(RelayOutcome::Upgraded) => {
+                proxy_log!(
+                    "[proxy][conn={}][#{}] switching protocols, tunneling {}:{}",
+                    conn_id,
+                    served,
+                    conn.host,
+                    conn.port
+                );
+                if let Err(e) = relay_upgraded(inbound, conn, &client_buf).await {
+                    proxy_log!("[proxy][conn={}] upgraded tunnel error: {}", conn_id, e);
+                }
+                break;
+            }
+            Ok(outcome) => {
+                let reusable = matches!(outcome, RelayOutcome::KeepAlive);
+                proxy_log!(
+                    "[proxy][conn={}][#{}] HTTP request from {} done, upstream reusable={}",
+                    conn_id,
+                    served,
+                    peer,
+                    reusable
+                );
+                if !reusable || !request.wants_keep_alive() {
+                    break;
+                }
+                conn.reused = true;
+                upstream = Some(conn);
+            }
+            Err(RelayError::Stale) => {
+                write_error_response(inbound, "502 Bad Gateway").await;
+                break;
+            }
+            Err(RelayError::NoResponse(e)) => {
+                eprintln!("[proxy] upstream error: {}", e);
+                write_error_response(inbound, "502 Bad Gateway").await;
+                break;
+            }
+            Err(RelayError::Broken(e)) => {
+                eprintln!("[proxy] HTTP relay aborted: {}", e);
+                break;
+            }
+        }
     }
-    let _ = app.emit("onHttpRequest", req_evt.clone());
+    proxy_log!(
+        "[proxy][conn={}] plain HTTP connection from {} closed after {} requests",
+        conn_id,
+        peer,
+        served
+    );
+    Ok(())
+}
 
-    let forward = build_plain_http_forward(&request);
-    let upstream_addr = format!("{}:{}", request.host, request.port);
-    eprintln!("[proxy] HTTP direct connect upstream {}", upstream_addr);
-    let mut upstream = TcpStream::connect(&upstream_addr)
-        .await
-        .map_err(|e| e.to_string())?;
-    upstream
-        .write_all(&forward)
-        .await
-        .map_err(|e| e.to_string())?;
-    eprintln!("[proxy] HTTP forwarded {} bytes", forward.len());
+// `status` is e.g. "502 Bad Gateway"; the reason phrase doubles as the body.
+async fn write_error_response(inbound: &mut TcpStream, status: &str) {
+    let body = status.split_once(' ').map_or(status, |(_, reason)| reason);
+    let resp = format!(
+        "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = inbound.write_all(resp.as_bytes()).await;
+}
 
-    stream_plain_http_response::<R, E>(app, inbound, &mut upstream, peer, &request, &req_evt).await
+// After a 101 both sides speak the new protocol; bytes either side already sent
+// past the exchange go first, then the raw streams are spliced.
+async fn relay_upgraded(
+    inbound: &mut TcpStream,
+    mut conn: UpstreamConn,
+    client_buf: &[u8],
+) -> Result<(), std::io::Error> {
+    if !client_buf.is_empty() {
+        conn.stream.write_all(client_buf).await?;
+    }
+    if !conn.buf.is_empty() {
+        inbound.write_all(&conn.buf).await?;
+    }
+    tunnel_with_eager_close(inbound, conn.stream).await
 }
 
-pub(crate) async fn stream_plain_http_response<R, E>(
-    app: &E,
+/// Sends one request upstream and relays the response verbatim, emitting events with
+/// the decoded body.
+async fn relay_plain_http_exchange(
+    sink: &SharedSink,
     inbound: &mut TcpStream,
-    upstream: &mut TcpStream,
+    conn: &mut UpstreamConn,
     peer: std::net::SocketAddr,
-    request: &crate::proxy::PlainHttpRequest,
+    request: &PlainHttpRequest,
     req_evt: &crate::http_shared::HttpRequestEvent,
-) -> Result<(), String>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
-    let mut first_chunk = true;
-    let mut total = 0usize;
-    let mut resp_headers = Vec::<Header>::new();
-    let mut scode: u16 = 200;
-    let mut version_str = "1.1".to_string();
-    let mut resp_buf = vec![0u8; 65536];
+) -> Result<RelayOutcome, RelayError> {
+    let proc_key = ConnKey::new(
+        peer,
+        inbound
+            .local_addr()
+            .map_err(|e| RelayError::NoResponse(e.to_string()))?,
+    );
+    let forward = build_plain_http_forward(request, conn.via.as_ref());
+    if conn.stream.write_all(&forward).await.is_err() {
+        return Err(if conn.reused {
+            RelayError::Stale
+        } else {
+            RelayError::NoResponse("upstream write failed".into())
+        });
+    }
+    proxy_log!("[proxy] HTTP forwarded {} bytes", forward.len());
+    conn.buf.clear();
+
+    // interim 1xx responses are passed through before the final head
     let mut sent_any = false;
+    let head = loop {
+        if let Some(head) = parse_response_head(&conn.buf) {
+            if (100..200).contains(&head.status) && head.status != 101 {
+                inbound
+                    .write_all(&conn.buf[..head.len])
+                    .await
+                    .map_err(|e| RelayError::Broken(e.to_string()))?;
+                conn.buf.drain(..head.len);
+                sent_any = true;
+                continue;
+            }
+            break head;
+        }
+        if conn.buf.len() > MAX_RESPONSE_HEAD {
+            let e = "upstream response head too large".to_string();
+            return Err(if sent_any {
+                RelayError::Broken(e)
+            } else {
+                RelayError::NoResponse(e)
+            });
+        }
+        match conn.fill().await {
+            Ok(n) if n > 0 => {}
+            Ok(_) if conn.buf.is_empty() && !sent_any && conn.reused => {
+                return Err(RelayError::Stale);
+            }
+            res => {
+                let e = res.err().unwrap_or_else(|| "upstream closed".into());
+                return Err(if sent_any {
+                    RelayError::Broken(e)
+                } else {
+                    RelayError::NoResponse(e)
+                });
+            }
+        }
+    };
 
-    loop {
-        let m = match tokio::time::timeout(
-            tokio::time::Duration::from_secs(30),
-            upstream.read(&mut resp_buf),
-        )
+    let framing = response_framing(Some(&request.method), head.status, &head.headers);
+    let mut decoder = BodyDecoder::new(framing);
+    let mut body = Vec::new();
+    let used = decoder
+        .decode(&conn.buf[head.len..], &mut body)
+        .map_err(|_| RelayError::Broken("invalid chunked response".into()))?;
+    inbound
+        .write_all(&conn.buf[..head.len + used])
         .await
-        {
-            Ok(Ok(v)) => v,
-            _ => {
-                eprintln!("[proxy] upstream read timeout/error");
+        .map_err(|e| RelayError::Broken(e.to_string()))?;
+    conn.buf.drain(..head.len + used);
+
+    let response_event = |body: &[u8], reason: Option<String>| {
+        let mut evt = HttpResponseEvent {
+            id: req_evt.id.clone(),
+            timestamp: now_rfc3339(),
+            src_ip: request.host.clone(),
+            src_port: request.port,
+            dst_ip: peer.ip().to_string(),
+            dst_port: peer.port(),
+            status_code: head.status,
+            reason,
+            version: head.version.clone(),
+            headers: head.headers.clone(),
+            body_base64: if body.is_empty() {
+                None
+            } else {
+                Some(general_purpose::STANDARD.encode(body))
+            },
+            body_len: body.len(),
+            process_name: None,
+            pid: None,
+            process: None,
+            // 继承请求的 LLM 标记，确保 UI 显示 raw/pretty/markdown 选项
+            is_llm: req_evt.is_llm,
+            llm_provider: req_evt.llm_provider.clone(),
+            mono_us: Some(mono_us()),
+        };
+        let (pname, pid) = try_lookup_process(proc_key, true);
+        if pname.is_some() || pid.is_some() {
+            evt.process_name = pname;
+            evt.pid = pid;
+            evt.process = cached_process_info(evt.pid);
+        }
+        evt
+    };
+    sink.emit(&CaptureEvent::Response(response_event(
+        &body,
+        head.reason.clone(),
+    )));
+
+    let mut total = body.len();
+    let mut eof = false;
+    while !decoder.is_done() {
+        match conn.fill().await {
+            Ok(n) if n > 0 => {}
+            Ok(_) if framing == BodyFraming::Close => {
+                eof = true;
                 break;
             }
-        };
-        if m == 0 {
-            eprintln!("[proxy] upstream closed, total={} bytes", total);
-            if !sent_any {
-                let body = b"Bad Gateway";
-                let resp = format!(
-                    "HTTP/1.1 502 Bad Gateway\r\nConnection: close\r\nContent-Length: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
-                    body.len()
-                );
-                let _ = inbound.write_all(resp.as_bytes()).await;
-                let _ = inbound.write_all(body).await;
+            res => {
+                let e = res
+                    .err()
+                    .unwrap_or_else(|| "upstream closed mid-body".into());
+                sink.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+                    &req_evt.id,
+                )));
+                return Err(RelayError::Broken(e));
             }
-            break;
         }
-        total += m;
+        body.clear();
+        let used = decoder
+            .decode(&conn.buf, &mut body)
+            .map_err(|_| RelayError::Broken("invalid chunked response".into()))?;
         inbound
-            .write_all(&resp_buf[..m])
+            .write_all(&conn.buf[..used])
             .await
-            .map_err(|e| e.to_string())?;
-        sent_any = true;
-
-        let data = &resp_buf[..m];
-        if first_chunk {
-            let head_end = memmem::find(data, b"\r\n\r\n").unwrap_or(m);
-            let first_line_end = memchr(b'\n', data).unwrap_or(m);
-            let first = String::from_utf8_lossy(&data[..first_line_end]).to_string();
-            resp_headers.clear();
-            for line in String::from_utf8_lossy(&data[..head_end])
-                .split("\r\n")
-                .skip(1)
-            {
-                if line.is_empty() {
-                    break;
-                }
-                if let Some((name, val)) = line.split_once(':') {
-                    resp_headers.push(Header {
-                        name: name.trim().to_string(),
-                        value: val.trim().to_string(),
-                    });
-                }
-            }
-            if first.starts_with("HTTP/") {
-                let mut it = first.split_whitespace();
-                if let Some(v) = it.next() {
-                    version_str = v.trim_start_matches("HTTP/").to_string();
-                }
-                if let Some(c) = it.nth(0) {
-                    scode = c.parse::<u16>().unwrap_or(200);
-                }
-            }
-            let body_slice = if head_end < data.len() {
-                &data[head_end + 4..]
-            } else {
-                &[]
-            };
-            let first_evt = HttpResponseEvent {
-                id: req_evt.id.clone(),
-                timestamp: now_rfc3339(),
-                src_ip: request.host.clone(),
-                src_port: request.port,
-                dst_ip: peer.ip().to_string(),
-                dst_port: peer.port(),
-                status_code: scode,
-                reason: None,
-                version: version_str.clone(),
-                headers: resp_headers.clone(),
-                body_base64: if body_slice.is_empty() {
-                    None
-                } else {
-                    Some(general_purpose::STANDARD.encode(body_slice))
-                },
-                body_len: body_slice.len(),
-                process_name: None,
-                pid: None,
-                // 继承请求的 LLM 标记，确保 UI 显示 raw/pretty/markdown 选项
-                is_llm: req_evt.is_llm,
-                llm_provider: req_evt.llm_provider.clone(),
-            };
-            let _ = app.emit("onHttpResponse", first_evt);
-            first_chunk = false;
-        } else {
-            let mut chunk_evt = HttpResponseEvent {
-                id: req_evt.id.clone(),
-                timestamp: now_rfc3339(),
-                src_ip: request.host.clone(),
-                src_port: request.port,
-                dst_ip: peer.ip().to_string(),
-                dst_port: peer.port(),
-                status_code: scode,
-                reason: None,
-                version: version_str.clone(),
-                headers: resp_headers.clone(),
-                body_base64: Some(general_purpose::STANDARD.encode(&resp_buf[..m])),
-                body_len: m,
-                process_name: None,
-                pid: None,
-                // 同样继承请求的 LLM 标记
-                is_llm: req_evt.is_llm,
-                llm_provider: req_evt.llm_provider.clone(),
-            };
-            let (pname3, pid3) = try_lookup_process(peer.port(), true);
-            if pname3.is_some() || pid3.is_some() {
-                chunk_evt.process_name = pname3;
-                chunk_evt.pid = pid3;
-            }
-            let _ = app.emit("onHttpResponse", chunk_evt);
+            .map_err(|e| RelayError::Broken(e.to_string()))?;
+        conn.buf.drain(..used);
+        // only a partial chunk-size or trailer line can stay buffered
+        if conn.buf.len() > MAX_RESPONSE_HEAD {
+            sink.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+                &req_evt.id,
+            )));
+            return Err(RelayError::Broken("upstream chunk line too long".into()));
+        }
+        if !body.is_empty() {
+            total += body.len();
+            sink.emit(&CaptureEvent::Response(response_event(&body, None)));
         }
     }
-    Ok(())
+    proxy_log!("[proxy] upstream response done, body={} bytes", total);
+    sink.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent {
+        trailers: std::mem::take(&mut decoder.trailers),
+        ..ResponseCompleteEvent::now(&req_evt.id)
+    }));
+    Ok(if head.status == 101 {
+        RelayOutcome::Upgraded
+    } else if eof || head.wants_close() {
+        RelayOutcome::Close
+    } else {
+        RelayOutcome::KeepAlive
+    })
 }
@@ -10,24 +10,28 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 
-use crate::http_shared::{Header, HttpResponseEvent, now_rfc3339};
-use crate::process_lookup::try_lookup_process;
-use crate::proxy::{connect_via_upstream, http_version_label, now_millis, read_http_response_head};
+use crate::events::CaptureEvent;
+use crate::http_shared::{Header, HttpResponseEvent, ResponseCompleteEvent, mono_us, now_rfc3339};
+use crate::process_lookup::{cached_process_info, try_lookup_process};
+use crate::proxy::{
+    connect_upstream_tls, connect_via_upstream, http_version_label, now_millis,
+    read_http_response_head,
+};
 use crate::proxy_log;
 
 use super::mitm_service::{
     MitmResponse, MitmShared, ParsedClientRequest, build_empty_response, build_outgoing_request,
 };
+use super::websocket::{handle_websocket, is_websocket_upgrade};
 
-pub(crate) async fn handle_via_upstream_proxy<R, E>(
-    shared: &MitmShared<E>,
+pub(crate) async fn handle_via_upstream_proxy(
+    shared: &MitmShared,
     parsed: ParsedClientRequest,
     proxy_url: String,
-) -> Result<MitmResponse, hyper::Error>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+) -> Result<MitmResponse, hyper::Error> {
+    if is_websocket_upgrade(&parsed) {
+        return Ok(handle_websocket(shared, parsed, Some(proxy_url)).await);
+    }
     let ParsedClientRequest {
         id,
         method,
@@ -43,6 +47,7 @@ where
     let port = shared.port;
     let peer_ip = shared.peer.ip().to_string();
     let peer_port = shared.peer.port();
+    let proc_key = shared.process_key();
 
     let upstream_tcp = match connect_via_upstream(&proxy_url, &host, port).await {
         Ok(s) => s,
@@ -52,22 +57,7 @@ where
         }
     };
 
-    let mut roots = rustls::RootCertStore::empty();
-    if let Ok(certs) = rustls_native_certs::load_native_certs() {
-        for c in certs {
-            let _ = roots.add(c);
-        }
-    }
-    let client_cfg = std::sync::Arc::new(
-        rustls::ClientConfig::builder()
-            .with_root_certificates(roots)
-            .with_no_client_auth(),
-    );
-    let tls_conn = tokio_rustls::TlsConnector::from(client_cfg);
-    let sni_leaked: &'static str = Box::leak(host.clone().into_boxed_str());
-    let server_name = rustls::pki_types::ServerName::try_from(sni_leaked)
-        .unwrap_or_else(|_| rustls::pki_types::ServerName::try_from("localhost").unwrap());
-    let mut upstream_tls = match tls_conn.connect(server_name, upstream_tcp).await {
+    let mut upstream_tls = match connect_upstream_tls(upstream_tcp, &host).await {
         Ok(v) => v,
         Err(_) => {
             proxy_log!("[proxy] upstream TLS connect failed");
@@ -133,7 +123,7 @@ where
     }
 
     let (tx, rx) = mpsc::channel::<Result<Frame<Bytes>, hyper::Error>>(16);
-    let app_clone = shared.app.clone();
+    let sink = shared.sink.clone();
     let id_clone = id.clone();
     let peer_ip_clone = peer_ip.clone();
     let host_clone = host.clone();
@@ -181,19 +171,22 @@ where
         body_len: first_body_slice.len(),
         process_name: None,
         pid: None,
+        process: None,
         is_llm: false,
         llm_provider: None,
+        mono_us: Some(mono_us()),
     };
-    let (pname2, pid2) = try_lookup_process(peer_port, true);
+    let (pname2, pid2) = try_lookup_process(proc_key, true);
     if pname2.is_some() || pid2.is_some() {
         head_evt.process_name = pname2;
         head_evt.pid = pid2;
+        head_evt.process = cached_process_info(head_evt.pid);
     }
     if req_event.is_llm {
         head_evt.is_llm = true;
         head_evt.llm_provider = req_event.llm_provider.clone();
     }
-    let _ = app_clone.emit("onHttpResponse", head_evt);
+    sink.emit(&CaptureEvent::Response(head_evt));
     shared
         .last_activity
         .store(now_millis(), std::sync::atomic::Ordering::Relaxed);
@@ -243,25 +236,31 @@ where
                         body_len: chunk.len(),
                         process_name: None,
                         pid: None,
+                        process: None,
                         is_llm: false,
                         llm_provider: None,
+                        mono_us: Some(mono_us()),
                     };
-                    let (pname3, pid3) = try_lookup_process(peer_port, true);
+                    let (pname3, pid3) = try_lookup_process(proc_key, true);
                     if pname3.is_some() || pid3.is_some() {
                         chunk_evt.process_name = pname3;
                         chunk_evt.pid = pid3;
+                        chunk_evt.process = cached_process_info(chunk_evt.pid);
                     }
                     if req_is_llm_spawn {
                         chunk_evt.is_llm = true;
                         chunk_evt.llm_provider = req_provider_spawn.clone();
                     }
-                    let _ = app_clone.emit("onHttpResponse", chunk_evt);
+                    sink.emit(&CaptureEvent::Response(chunk_evt));
                     last_activity_spawn.store(now_millis(), std::sync::atomic::Ordering::Relaxed);
                 }
                 Ok(Ok(_)) => break,
                 _ => break,
             }
         }
+        sink.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+            &id_clone,
+        )));
     });
 
     let mut rb = Response::builder().status(scode);
@@ -291,14 +290,13 @@ where
     Ok(rb.body(body_stream).unwrap())
 }
 
-pub(crate) async fn handle_direct_upstream<R, E>(
-    shared: &MitmShared<E>,
+pub(crate) async fn handle_direct_upstream(
+    shared: &MitmShared,
     parsed: ParsedClientRequest,
-) -> Result<MitmResponse, hyper::Error>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+) -> Result<MitmResponse, hyper::Error> {
+    if is_websocket_upgrade(&parsed) {
+        return Ok(handle_websocket(shared, parsed, None).await);
+    }
     let out_req = match build_outgoing_request(&parsed) {
         Ok(r) => r,
         Err(_) => return Ok(build_empty_response(400).await),
@@ -359,6 +357,7 @@ where
     }
 
     let peer_port = shared.peer.port();
+    let proc_key = shared.process_key();
     proxy_log!(
         "[proxy][conn={}][req={}] direct-upstream resp-head: {} http/{} headers_cnt={}",
         shared.conn_id,
@@ -382,13 +381,16 @@ where
         body_len: 0,
         process_name: None,
         pid: None,
+        process: None,
         is_llm: false,
         llm_provider: None,
+        mono_us: Some(mono_us()),
     };
-    let (pname2, pid2) = try_lookup_process(peer_port, true);
+    let (pname2, pid2) = try_lookup_process(proc_key, true);
     if pname2.is_some() || pid2.is_some() {
         head_evt.process_name = pname2;
         head_evt.pid = pid2;
+        head_evt.process = cached_process_info(head_evt.pid);
     }
     if let Some(provider) = shared.llm_rules.match_response(&head_evt) {
         head_evt.is_llm = true;
@@ -398,14 +400,14 @@ where
         head_evt.is_llm = true;
         head_evt.llm_provider = req_event.llm_provider.clone();
     }
-    let _ = shared.app.emit("onHttpResponse", head_evt);
+    shared.sink.emit(&CaptureEvent::Response(head_evt));
     shared
         .last_activity
         .store(now_millis(), std::sync::atomic::Ordering::Relaxed);
 
     let (tx, rx) = mpsc::channel::<Result<Frame<Bytes>, hyper::Error>>(16);
     let mut upstream_body = resp.into_body();
-    let app_clone = shared.app.clone();
+    let sink = shared.sink.clone();
     let resp_headers_clone = resp_headers.clone();
     let id_clone = id.clone();
     let peer_ip_clone = shared.peer.ip().to_string();
@@ -446,19 +448,22 @@ where
                             body_len: bytes.len(),
                             process_name: None,
                             pid: None,
+                            process: None,
                             is_llm: false,
                             llm_provider: None,
+                            mono_us: Some(mono_us()),
                         };
-                        let (pname3, pid3) = try_lookup_process(peer_port, true);
+                        let (pname3, pid3) = try_lookup_process(proc_key, true);
                         if pname3.is_some() || pid3.is_some() {
                             chunk_evt.process_name = pname3;
                             chunk_evt.pid = pid3;
+                            chunk_evt.process = cached_process_info(chunk_evt.pid);
                         }
                         if req_is_llm_spawn {
                             chunk_evt.is_llm = true;
                             chunk_evt.llm_provider = req_provider_spawn.clone();
                         }
-                        let _ = app_clone.emit("onHttpResponse", chunk_evt);
+                        sink.emit(&CaptureEvent::Response(chunk_evt));
                         last_activity_spawn
                             .store(now_millis(), std::sync::atomic::Ordering::Relaxed);
                     } else if frame.is_trailers() {
@@ -473,6 +478,9 @@ where
                 }
             }
         }
+        sink.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+            &id_clone,
+        )));
     });
 
     let mut rb = Response::builder().status(status);
@@ -12,30 +12,39 @@ use hyper_util::client::legacy::connect::HttpConnector;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 
-use crate::http_shared::{Header, HttpRequestEvent, gen_id, now_rfc3339};
+use crate::events::{CaptureEvent, SharedSink};
+use crate::http_shared::{Header, HttpRequestEvent, gen_id, mono_us, now_rfc3339};
 // use crate::llm_rules::load_llm_rules;
-use crate::process_lookup::try_lookup_process;
-use crate::proxy::{current_upstream_proxy, now_millis};
+use crate::process_lookup::{ConnKey, cached_process_info, try_lookup_process};
+use crate::proxy::{now_millis, route_for};
 use crate::proxy_log;
 
+use super::websocket::UpgradeSlot;
+
 pub(crate) type ProxyBody = Full<Bytes>;
 pub(crate) type MitmStreamBody = StreamBody<ReceiverStream<Result<Frame<Bytes>, hyper::Error>>>;
 pub(crate) type MitmResponse = Response<MitmStreamBody>;
 
 #[derive(Clone)]
-pub(crate) struct MitmRequestContext<E> {
-    pub(crate) app: E,
+pub(crate) struct MitmRequestContext {
+    pub(crate) sink: SharedSink,
     pub(crate) llm_rules: crate::llm_rules::LlmRules,
     pub(crate) client: Client<hyper_rustls::HttpsConnector<HttpConnector>, ProxyBody>,
     pub(crate) peer: std::net::SocketAddr,
+    // 代理监听 socket 的本端地址，即客户端连接的对端
+    pub(crate) local: std::net::SocketAddr,
     pub(crate) host: String,
     pub(crate) port: u16,
     pub(crate) conn_id: u64,
     pub(crate) last_activity: std::sync::Arc<std::sync::atomic::AtomicU64>,
     pub(crate) inflight: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    // 本会话已收到的请求数（keep-alive 下一个连接承载多个请求）
+    pub(crate) requests: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    // 握手成功的 WebSocket 上游连接，由会话在 101 之后接管
+    pub(crate) upgrade: UpgradeSlot,
 }
 
-impl<E> MitmRequestContext<E> {
+impl MitmRequestContext {
     pub(crate) fn touch_activity(&self) {
         self.last_activity
             .store(now_millis(), std::sync::atomic::Ordering::Relaxed);
@@ -58,15 +67,26 @@ impl Drop for InflightGuard {
     }
 }
 
-pub(crate) struct MitmShared<E> {
-    pub(crate) app: E,
+pub(crate) struct MitmShared {
+    pub(crate) sink: SharedSink,
     pub(crate) llm_rules: crate::llm_rules::LlmRules,
     pub(crate) client: Client<hyper_rustls::HttpsConnector<HttpConnector>, ProxyBody>,
     pub(crate) peer: std::net::SocketAddr,
+    // 代理监听 socket 的本端地址，即客户端连接的对端
+    pub(crate) local: std::net::SocketAddr,
     pub(crate) host: String,
     pub(crate) port: u16,
     pub(crate) conn_id: u64,
     pub(crate) last_activity: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    pub(crate) upgrade: UpgradeSlot,
+    // 1-based index of this request within the session
+    pub(crate) request_seq: u64,
+}
+
+impl MitmShared {
+    pub(crate) fn process_key(&self) -> ConnKey {
+        ConnKey::new(self.peer, self.local)
+    }
 }
 
 pub(crate) struct ParsedClientRequest {
@@ -80,8 +100,8 @@ pub(crate) struct ParsedClientRequest {
     pub(crate) req_event: HttpRequestEvent,
 }
 
-pub(crate) async fn parse_client_request<E>(
-    shared: &MitmShared<E>,
+pub(crate) async fn parse_client_request(
+    shared: &MitmShared,
     parts: http::request::Parts,
     body_in: IncomingBody,
 ) -> Result<ParsedClientRequest, hyper::Error> {
@@ -183,8 +203,12 @@ pub(crate) async fn parse_client_request<E>(
         body_len: body_bytes.len(),
         process_name: None,
         pid: None,
+        process: None,
         is_llm: false,
         llm_provider: None,
+        mono_us: Some(mono_us()),
+        conn_id: Some(shared.conn_id),
+        conn_seq: Some(shared.request_seq),
     };
     proxy_log!(
         "[proxy][conn={}] build req_event done: {} {}",
@@ -215,10 +239,11 @@ pub(crate) async fn parse_client_request<E>(
         method_str,
         path_q
     );
-    let (pname, pid) = try_lookup_process(shared.peer.port(), false);
+    let (pname, pid) = try_lookup_process(shared.process_key(), false);
     if pname.is_some() || pid.is_some() {
         req_evt.process_name = pname;
         req_evt.pid = pid;
+        req_evt.process = cached_process_info(req_evt.pid);
     }
     proxy_log!(
         "[proxy][conn={}] proc lookup done: {} {}",
@@ -245,23 +270,20 @@ pub(crate) async fn parse_client_request<E>(
     })
 }
 
-pub(crate) fn emit_request_event<R, E>(
-    app: &E,
+pub(crate) fn emit_request_event(
+    sink: &SharedSink,
     event: &HttpRequestEvent,
     last_activity: &std::sync::Arc<std::sync::atomic::AtomicU64>,
-) where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+) {
     // 先更新活动时间，避免事件通道阻塞导致 idle 判定提前触发
     last_activity.store(
         crate::proxy::now_millis(),
         std::sync::atomic::Ordering::Relaxed,
     );
-    let app_clone = app.clone();
+    let sink = sink.clone();
     let ev = event.clone();
     tokio::spawn(async move {
-        let _ = app_clone.emit("onHttpRequest", ev);
+        sink.emit(&CaptureEvent::Request(ev));
     });
 }
 
@@ -304,66 +326,62 @@ pub(crate) async fn build_empty_response(status: u16) -> MitmResponse {
     Response::builder().status(status).body(body).unwrap()
 }
 
-pub(crate) fn build_mitm_service<R, E>(
-    ctx: MitmRequestContext<E>,
+pub(crate) fn build_mitm_service(
+    ctx: MitmRequestContext,
 ) -> impl hyper::service::Service<
     Request<IncomingBody>,
     Response = MitmResponse,
     Error = hyper::Error,
-    Future = impl std::future::Future<Output = Result<MitmResponse, hyper::Error>> + Send,
+    Future = impl std::future::Future<Output = Result<MitmResponse, hyper::Error>> + Send + Unpin,
 > + Clone
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
++ Unpin {
+    // boxed so the HTTP/1 connection can run `without_shutdown` (needs Unpin futures)
     hyper::service::service_fn(move |req: Request<IncomingBody>| {
         let ctx_for_request = ctx.clone();
-        async move { crate::proxy::handle_mitm_request::<R, E>(ctx_for_request, req).await }
+        Box::pin(async move { crate::proxy::handle_mitm_request(ctx_for_request, req).await })
     })
 }
 
-pub(crate) async fn handle_mitm_request<R, E>(
-    ctx: MitmRequestContext<E>,
+pub(crate) async fn handle_mitm_request(
+    ctx: MitmRequestContext,
     req: Request<IncomingBody>,
-) -> Result<MitmResponse, hyper::Error>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+) -> Result<MitmResponse, hyper::Error> {
     ctx.touch_activity();
     let _guard = InflightGuard::new(ctx.inflight.clone());
-    process_mitm_request::<R, E>(ctx, req).await
+    process_mitm_request(ctx, req).await
 }
 
-pub(crate) async fn process_mitm_request<R, E>(
-    ctx: MitmRequestContext<E>,
+pub(crate) async fn process_mitm_request(
+    ctx: MitmRequestContext,
     req: Request<IncomingBody>,
-) -> Result<MitmResponse, hyper::Error>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+) -> Result<MitmResponse, hyper::Error> {
     let shared = {
         let MitmRequestContext {
-            app,
+            sink,
             llm_rules,
             client,
             peer,
+            local,
             host,
             port,
             conn_id,
             last_activity,
             inflight: _,
+            requests,
+            upgrade,
         } = ctx;
         MitmShared {
-            app,
+            sink,
             llm_rules,
             client,
             peer,
+            local,
             host,
             port,
             conn_id,
             last_activity,
+            upgrade,
+            request_seq: requests.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1,
         }
     };
 
@@ -395,8 +413,9 @@ where
             .collect::<Vec<_>>()
             .join(" | ");
         proxy_log!(
-            "[proxy][conn={}] pre-received: {} {} http/{} headers={} [{}]",
+            "[proxy][conn={}][#{}] pre-received: {} {} http/{} headers={} [{}]",
             shared.conn_id,
+            shared.request_seq,
             method_str,
             path_q,
             crate::proxy::http_version_label(parts.version),
@@ -426,8 +445,9 @@ where
             .collect::<Vec<_>>()
             .join(" | ");
         proxy_log!(
-            "[proxy][conn={}][req={}] received: {} {} http/{} headers={} [{}] body_len={}",
+            "[proxy][conn={}][#{}][req={}] received: {} {} http/{} headers={} [{}] body_len={}",
             shared.conn_id,
+            shared.request_seq,
             parsed.id,
             parsed.method,
             parsed.path,
@@ -439,17 +459,20 @@ where
     }
 
     // 异步派发事件，防止在此处阻塞请求处理
-    emit_request_event::<R, _>(&shared.app, &parsed.req_event, &shared.last_activity);
+    emit_request_event(&shared.sink, &parsed.req_event, &shared.last_activity);
 
-    if let Some(proxy_url) = current_upstream_proxy() {
+    // evaluated per request so routing table edits apply to open sessions
+    let route = route_for(&shared.host, shared.port);
+    if let Some(proxy_url) = route.upstream {
         proxy_log!(
-            "[proxy] using upstream {} for {}:{}",
+            "[proxy] using upstream {} for {}:{} ({})",
             proxy_url,
             shared.host,
-            shared.port
+            shared.port,
+            route.matched
         );
-        crate::proxy::handle_via_upstream_proxy::<R, _>(&shared, parsed, proxy_url).await
+        crate::proxy::handle_via_upstream_proxy(&shared, parsed, proxy_url).await
     } else {
-        crate::proxy::handle_direct_upstream::<R, _>(&shared, parsed).await
+        crate::proxy::handle_direct_upstream(&shared, parsed).await
     }
 }
@@ -1,46 +1,20 @@
 use hyper::server::conn::http1;
 use hyper_util::rt::TokioIo;
-use std::net::SocketAddr;
-use std::sync::Arc;
 use std::time::Instant;
 use tokio::net::TcpStream;
 use tokio_rustls::server::TlsStream;
 
-use crate::proxy::{now_millis, wait_idle};
+use crate::proxy::wait_idle;
 use crate::proxy_log;
 
-use super::mitm_service::{MitmRequestContext, ProxyBody, build_mitm_service};
+use super::mitm_service::{MitmRequestContext, build_mitm_service};
+use super::websocket::relay_websocket;
 
-pub(crate) async fn run_mitm_session<'a, R, E>(
-    app: &E,
-    llm_rules: &crate::llm_rules::LlmRules,
-    peer: SocketAddr,
-    host: String,
-    port: u16,
-    conn_id: u64,
-    tls_stream: TlsStream<&'a mut TcpStream>,
-    client_base: hyper_util::client::legacy::Client<
-        hyper_rustls::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>,
-        ProxyBody,
-    >,
-) -> Result<(), String>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
-    let last_activity = Arc::new(std::sync::atomic::AtomicU64::new(now_millis()));
-    let inflight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
-    let ctx = MitmRequestContext {
-        app: app.clone(),
-        llm_rules: llm_rules.clone(),
-        client: client_base,
-        peer,
-        host: host.clone(),
-        port,
-        conn_id,
-        last_activity: last_activity.clone(),
-        inflight: inflight.clone(),
-    };
+pub(crate) async fn run_mitm_session(
+    ctx: MitmRequestContext,
+    tls_stream: TlsStream<&mut TcpStream>,
+) -> Result<(), String> {
+    let (host, port, conn_id) = (ctx.host.clone(), ctx.port, ctx.conn_id);
 
     let negotiated_h2 = {
         let (_s, conn) = tls_stream.get_ref();
@@ -59,7 +33,7 @@ where
     );
 
     if negotiated_h2 {
-        let service = build_mitm_service::<R, E>(ctx.clone());
+        let service = build_mitm_service(ctx.clone());
         let io = TokioIo::new(tls_stream);
         use hyper::server::conn::http2;
         proxy_log!(
@@ -121,30 +95,73 @@ where
             }
         }
     } else {
-        let service = build_mitm_service::<R, E>(ctx.clone());
+        let service = build_mitm_service(ctx.clone());
         let io = TokioIo::new(tls_stream);
+        // keep-alive 空闲关闭时间（秒），0 表示不因空闲自动关闭
+        let h1_idle_secs = std::env::var("PROXY_H1_IDLE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60);
         proxy_log!(
-            "[proxy][conn={}] serving HTTP/1.1 for {}:{} (keep_alive=false)",
+            "[proxy][conn={}] serving HTTP/1.1 for {}:{} (keep_alive=true idle={}s)",
             conn_id,
             host,
-            port
+            port,
+            h1_idle_secs
         );
         let mut builder = http1::Builder::new();
-        builder.keep_alive(false);
-        if let Err(e) = builder.serve_connection(io, service).await {
-            proxy_log!(
-                "[proxy][conn={}] http1 serve_connection error: {}",
-                conn_id,
-                e
-            );
+        builder.keep_alive(true);
+        let mut conn = builder.serve_connection(io, service);
+        let idle = async {
+            if h1_idle_secs == 0 {
+                std::future::pending::<()>().await;
+            }
+            let idle = tokio::time::Duration::from_secs(h1_idle_secs);
+            wait_idle(ctx.last_activity.clone(), ctx.inflight.clone(), idle).await;
+        };
+        // poll without shutdown to keep the client stream: a 101 hands it over to the WebSocket relay
+        let finished = tokio::select! {
+            res = std::future::poll_fn(|cx| conn.poll_without_shutdown(cx)) => Some(res),
+            _ = idle => None,
+        };
+        let served = match finished {
+            Some(res) => res,
+            None => {
+                proxy_log!(
+                    "[proxy][conn={}] h1 idle {}s; closing session",
+                    conn_id,
+                    h1_idle_secs
+                );
+                std::pin::Pin::new(&mut conn).graceful_shutdown();
+                std::future::poll_fn(|cx| conn.poll_without_shutdown(cx)).await
+            }
+        };
+        match served.map(|_| conn.into_parts()) {
+            Ok(parts) => {
+                let mut client = parts.io.into_inner();
+                let upgraded = ctx.upgrade.lock().unwrap().take();
+                if let Some(ws) = upgraded {
+                    relay_websocket(client, parts.read_buf, ws).await;
+                } else {
+                    let _ = tokio::io::AsyncWriteExt::shutdown(&mut client).await;
+                }
+            }
+            Err(e) => {
+                proxy_log!(
+                    "[proxy][conn={}] http1 serve_connection error: {}",
+                    conn_id,
+                    e
+                );
+            }
         }
     }
 
     proxy_log!(
-        "[proxy][conn={}] CONNECT session ended for {}:{}",
+        "[proxy][conn={}] CONNECT session ended for {}:{} after {} requests",
         conn_id,
         host,
-        port
+        port,
+        ctx.requests.load(std::sync::atomic::Ordering::Relaxed)
     );
     Ok(())
 }
@@ -3,6 +3,7 @@ use std::sync::Mutex;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use tokio::net::TcpListener;
 
+use crate::events::SharedSink;
 use crate::llm_rules::load_llm_rules;
 
 mod flows;
@@ -10,8 +11,10 @@ mod mitm_handlers;
 mod mitm_service;
 mod mitm_session;
 mod parse;
+mod routing;
 mod tls;
 mod upstream;
+mod websocket;
 
 #[cfg(test)]
 mod tests;
@@ -84,11 +87,14 @@ pub(crate) fn current_upstream_proxy() -> Option<String> {
 // (StartProxyArgs removed; not used within this module)
 
 // Public API
-pub async fn start_proxy<R, E>(app: E, addr: String, upstream: Option<String>) -> Result<(), String>
-where
-    R: tauri::Runtime,
-    E: tauri::Emitter<R> + Clone + Send + Sync + 'static,
-{
+pub async fn start_proxy(
+    sink: SharedSink,
+    addr: String,
+    upstream: Option<String>,
+) -> Result<(), String> {
+    if let Some(u) = &upstream {
+        UpstreamProxy::parse(u)?;
+    }
     if PROXY_RUNNING.swap(true, Ordering::SeqCst) {
         return Ok(());
     }
@@ -104,7 +110,14 @@ where
             None => proxy_log!("[proxy] no upstream proxy configured"),
         }
     }
-    let listener = TcpListener::bind(&addr).await.map_err(|e| e.to_string())?;
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            // 只回退本次调用置上的运行标记
+            PROXY_RUNNING.store(false, Ordering::SeqCst);
+            return Err(e.to_string());
+        }
+    };
     let llm_rules = load_llm_rules();
     tokio::spawn(async move {
         loop {
@@ -114,16 +127,11 @@ where
             match listener.accept().await {
                 Ok((mut inbound, peer)) => {
                     proxy_log!("[proxy] accepted connection from {}", peer);
-                    let app_handle = app.clone();
+                    let sink = sink.clone();
                     let llm_rules_cloned = llm_rules.clone();
                     tokio::spawn(async move {
-                        if let Err(_e) = flows::handle_client::<R, E>(
-                            &app_handle,
-                            &llm_rules_cloned,
-                            &mut inbound,
-                            peer,
-                        )
-                        .await
+                        if let Err(_e) =
+                            flows::handle_client(&sink, &llm_rules_cloned, &mut inbound, peer).await
                         {
                             // swallow errors
                         }
@@ -143,13 +151,23 @@ pub fn stop_proxy() {
     PROXY_RUNNING.store(false, Ordering::SeqCst);
 }
 
+pub fn is_proxy_running() -> bool {
+    PROXY_RUNNING.load(Ordering::SeqCst)
+}
+
 // Expose commonly used items to submodules via crate::proxy path
 pub(crate) use parse::{
     ConnectTarget, InitialPacket, PlainHttpRequest, build_plain_http_forward, looks_like_http,
-    parse_connect_target, parse_plain_http_request,
+    parse_connect_target, parse_plain_http_request, parse_response_head,
+};
+pub use routing::{RouteDecision, RoutingTable, route_for, routing_table, set_routing_table};
+pub(crate) use tls::{
+    build_https_client, build_mitm_acceptor, connect_upstream_tls, resolve_mitm_flags,
+};
+pub(crate) use upstream::{
+    UpstreamProxy, UpstreamStream, connect_via_upstream, read_http_response_head,
+    tunnel_with_eager_close,
 };
-pub(crate) use tls::{build_https_client, build_mitm_acceptor, resolve_mitm_flags};
-pub(crate) use upstream::{connect_via_upstream, read_http_response_head, tunnel_with_eager_close};
 // only re-export the symbols actually referenced across modules to avoid unused warnings
 pub(crate) use mitm_handlers::{handle_direct_upstream, handle_via_upstream_proxy};
 pub(crate) use mitm_service::handle_mitm_request;
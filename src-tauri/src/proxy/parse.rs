@@ -1,7 +1,8 @@
 use memchr::{memchr, memmem};
 use std::net::SocketAddr;
 
-use crate::http_shared::{Header, HttpRequestEvent, gen_id, now_rfc3339};
+use crate::http_shared::{Header, HttpRequestEvent, gen_id, mono_us, now_rfc3339};
+use crate::proxy::UpstreamProxy;
 use base64::Engine as _;
 use base64::engine::general_purpose;
 
@@ -76,6 +77,30 @@ impl PlainHttpRequest {
         }
     }
 
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+
+    /// Whether the client expects the connection to stay open after this exchange.
+    pub(crate) fn wants_keep_alive(&self) -> bool {
+        let conn = self
+            .header("proxy-connection")
+            .or(self.header("connection"))
+            .unwrap_or("");
+        let has = |token: &str| {
+            conn.split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        };
+        if self.version == "1.0" {
+            has("keep-alive")
+        } else {
+            !has("close")
+        }
+    }
+
     pub(crate) fn build_event(
         &self,
         peer: SocketAddr,
@@ -101,8 +126,12 @@ impl PlainHttpRequest {
             body_len: self.body.len(),
             process_name: None,
             pid: None,
+            process: None,
             is_llm: false,
             llm_provider: None,
+            mono_us: Some(mono_us()),
+            conn_id: None,
+            conn_seq: None,
         };
 
         if let Some(provider) = llm_rules.match_request(&event) {
@@ -188,21 +217,104 @@ pub(crate) fn parse_plain_http_request(packet: &InitialPacket) -> Result<PlainHt
     })
 }
 
-pub(crate) fn build_plain_http_forward(req: &PlainHttpRequest) -> Vec<u8> {
+// `req.body` is already de-chunked, so the body is re-framed with Content-Length;
+// the upstream connection is left keep-alive for reuse, or asks to upgrade when
+// the client did. Through an HTTP proxy (`via`) the target is sent in
+// absolute-form with the proxy's credentials.
+pub(crate) fn build_plain_http_forward(
+    req: &PlainHttpRequest,
+    via: Option<&UpstreamProxy>,
+) -> Vec<u8> {
+    let target = match via {
+        Some(_) if req.port == 80 => format!("http://{}{}", req.host, req.origin_form_path()),
+        Some(_) => format!("http://{}:{}{}", req.host, req.port, req.origin_form_path()),
+        None => req.origin_form_path(),
+    };
     let mut forward = Vec::<u8>::new();
-    forward.extend_from_slice(
-        format!("{} {} HTTP/1.1\r\n", req.method, req.origin_form_path()).as_bytes(),
-    );
+    forward.extend_from_slice(format!("{} {} HTTP/1.1\r\n", req.method, target).as_bytes());
+    if let Some(auth) = via.and_then(UpstreamProxy::basic_auth) {
+        forward.extend_from_slice(format!("Proxy-Authorization: {}\r\n", auth).as_bytes());
+    }
+    let mut had_length = false;
     for header in req.headers.iter() {
         let lname = header.name.to_ascii_lowercase();
-        if lname == "proxy-connection" || lname == "proxy-authorization" {
+        if matches!(lname.as_str(), "content-length" | "transfer-encoding") {
+            had_length = true;
+            continue;
+        }
+        if matches!(
+            lname.as_str(),
+            "proxy-connection" | "proxy-authorization" | "connection" | "keep-alive" | "expect"
+        ) {
             continue;
         }
         forward.extend_from_slice(format!("{}: {}\r\n", header.name, header.value).as_bytes());
     }
-    forward.extend_from_slice(b"Connection: close\r\n\r\n");
+    if req.header("upgrade").is_some() {
+        forward.extend_from_slice(b"Connection: Upgrade\r\n");
+    }
+    if had_length || !req.body.is_empty() {
+        forward.extend_from_slice(format!("Content-Length: {}\r\n", req.body.len()).as_bytes());
+    }
+    forward.extend_from_slice(b"\r\n");
     if !req.body.is_empty() {
         forward.extend_from_slice(&req.body);
     }
     forward
 }
+
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseHead {
+    pub(crate) status: u16,
+    pub(crate) version: String,
+    pub(crate) reason: Option<String>,
+    pub(crate) headers: Vec<Header>,
+    // bytes up to and including the blank line
+    pub(crate) len: usize,
+}
+
+impl ResponseHead {
+    pub(crate) fn wants_close(&self) -> bool {
+        let conn = self
+            .headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("connection"))
+            .map(|h| h.value.as_str())
+            .unwrap_or("");
+        let has = |token: &str| {
+            conn.split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        };
+        if self.version == "1.0" {
+            !has("keep-alive")
+        } else {
+            has("close")
+        }
+    }
+}
+
+/// Parses a complete response head at the start of `data`; `None` until the blank line arrives.
+pub(crate) fn parse_response_head(data: &[u8]) -> Option<ResponseHead> {
+    let head_end = memmem::find(data, b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&data[..head_end]);
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or("");
+    let mut it = status_line.splitn(3, ' ');
+    let version = it.next()?.strip_prefix("HTTP/")?.to_string();
+    let status = it.next()?.parse::<u16>().ok()?;
+    let reason = it.next().map(str::trim).filter(|r| !r.is_empty());
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| Header {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+        .collect();
+    Some(ResponseHead {
+        status,
+        version,
+        reason: reason.map(str::to_string),
+        headers,
+        len: head_end + 4,
+    })
+}
@@ -0,0 +1,384 @@
+// Per-destination upstream selection (PAC-like): a NO_PROXY-style bypass list,
+// then ordered rules over host globs, CIDRs and ports, each picking `direct` or
+// a named upstream. Destinations no rule covers use the global upstream.
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::proxy::{UpstreamProxy, current_upstream_proxy};
+
+// 路由表持久化在数据目录下（与 sessions 同级）
+const ROUTES_FILE: &str = "proxy_routes.json";
+const DIRECT: &str = "direct";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingTable {
+    /// Named upstream proxy URLs, referenced by `RouteRule::via`.
+    #[serde(default)]
+    pub upstreams: BTreeMap<String, String>,
+    /// First match wins.
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+    /// NO_PROXY-style entries that always go direct: `*`, `localhost`,
+    /// `.corp.example` / `corp.example` (domain and subdomains), IPs, CIDRs,
+    /// and any of those with a `:port` suffix.
+    #[serde(default)]
+    pub bypass: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// Host globs, `*` matching any run of characters, e.g. `*.openai.com`.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Matched against IP-literal destinations only; names are not resolved.
+    #[serde(default)]
+    pub cidrs: Vec<String>,
+    /// Empty means any port.
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// `direct` or a key of `RoutingTable::upstreams`.
+    pub via: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteDecision {
+    /// Upstream proxy URL; `None` connects directly.
+    pub upstream: Option<String>,
+    /// What decided it: `bypass <entry>`, `rule <n>` or `default`.
+    pub matched: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cidr {
+    net: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(s: &str) -> Option<Self> {
+        let (ip, prefix) = match s.split_once('/') {
+            Some((ip, p)) => (ip.parse::<IpAddr>().ok()?, p.parse::<u8>().ok()?),
+            None => {
+                let ip = s.parse::<IpAddr>().ok()?;
+                (ip, if ip.is_ipv4() { 32 } else { 128 })
+            }
+        };
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        (prefix <= max).then_some(Cidr { net: ip, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        // v4-mapped v6 addresses compare as v4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        let (net, addr, bits) = match (self.net, ip) {
+            (IpAddr::V4(n), IpAddr::V4(a)) => (u32::from(n) as u128, u32::from(a) as u128, 32),
+            (IpAddr::V6(n), IpAddr::V6(a)) => (u128::from(n), u128::from(a), 128),
+            _ => return false,
+        };
+        if self.prefix == 0 {
+            return true;
+        }
+        let shift = bits - self.prefix as u32;
+        (net >> shift) == (addr >> shift)
+    }
+}
+
+// Lowercase, no IPv6 brackets, no trailing root dot.
+fn normalize_host(host: &str) -> String {
+    host.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t) = (pattern.as_bytes(), text.as_bytes());
+    let (mut pi, mut ti) = (0, 0);
+    // position of the last `*` and the text index it is currently matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && p[pi] == b'*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if pi < p.len() && p[pi].eq_ignore_ascii_case(&t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == b'*')
+}
+
+// Splits an optional `:port` off a bypass entry; bare IPv6 addresses keep their colons.
+fn split_entry_port(entry: &str) -> (&str, Option<u16>) {
+    if let Some(rest) = entry.strip_prefix('[') {
+        return match rest.split_once(']') {
+            Some((host, tail)) => (host, tail.strip_prefix(':').and_then(|p| p.parse().ok())),
+            None => (entry, None),
+        };
+    }
+    match entry.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(p) => (host, Some(p)),
+            Err(_) => (entry, None),
+        },
+        _ => (entry, None),
+    }
+}
+
+fn bypass_matches(entry: &str, host: &str, ip: Option<IpAddr>, port: u16) -> bool {
+    let entry = entry.trim();
+    if entry == "*" {
+        return true;
+    }
+    let (pattern, want_port) = split_entry_port(entry);
+    if want_port.is_some_and(|p| p != port) {
+        return false;
+    }
+    if let Some(cidr) = Cidr::parse(pattern) {
+        return ip.is_some_and(|ip| cidr.contains(ip));
+    }
+    let domain = normalize_host(pattern.trim_start_matches("*.").trim_start_matches('.'));
+    !domain.is_empty()
+        && (host == domain
+            || host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|sub| sub.ends_with('.')))
+}
+
+impl RouteRule {
+    fn matches(&self, host: &str, ip: Option<IpAddr>, port: u16) -> bool {
+        if !self.ports.is_empty() && !self.ports.contains(&port) {
+            return false;
+        }
+        if self.hosts.is_empty() && self.cidrs.is_empty() {
+            return true;
+        }
+        self.hosts
+            .iter()
+            .any(|g| glob_match(&normalize_host(g), host))
+            || ip.is_some_and(|ip| {
+                self.cidrs
+                    .iter()
+                    .filter_map(|c| Cidr::parse(c.trim()))
+                    .any(|c| c.contains(ip))
+            })
+    }
+}
+
+impl RoutingTable {
+    /// Rejects unknown upstream names, bad proxy URLs and malformed CIDRs.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, url) in &self.upstreams {
+            if name.eq_ignore_ascii_case(DIRECT) {
+                return Err(format!("upstream name '{}' is reserved", name));
+            }
+            UpstreamProxy::parse(url).map_err(|e| format!("upstream '{}': {}", name, e))?;
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            let n = i + 1;
+            if !rule.via.eq_ignore_ascii_case(DIRECT) && !self.upstreams.contains_key(&rule.via) {
+                return Err(format!("rule {}: unknown upstream '{}'", n, rule.via));
+            }
+            if let Some(c) = rule.cidrs.iter().find(|c| Cidr::parse(c.trim()).is_none()) {
+                return Err(format!("rule {}: invalid CIDR '{}'", n, c));
+            }
+        }
+        for entry in &self.bypass {
+            let (pattern, _) = split_entry_port(entry.trim());
+            if pattern.contains('/') && Cidr::parse(pattern).is_none() {
+                return Err(format!("bypass: invalid CIDR '{}'", entry));
+            }
+        }
+        Ok(())
+    }
+
+    /// `fallback` is the proxy's global upstream, used when nothing matches.
+    pub fn resolve(&self, host: &str, port: u16, fallback: Option<&str>) -> RouteDecision {
+        let host = normalize_host(host);
+        let ip = host.parse::<IpAddr>().ok();
+        if let Some(entry) = self
+            .bypass
+            .iter()
+            .find(|e| bypass_matches(e, &host, ip, port))
+        {
+            return RouteDecision {
+                upstream: None,
+                matched: format!("bypass {}", entry.trim()),
+            };
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.matches(&host, ip, port) {
+                let upstream = if rule.via.eq_ignore_ascii_case(DIRECT) {
+                    None
+                } else {
+                    self.upstreams.get(&rule.via).cloned()
+                };
+                return RouteDecision {
+                    upstream,
+                    matched: format!("rule {}", i + 1),
+                };
+            }
+        }
+        RouteDecision {
+            upstream: fallback.map(str::to_string),
+            matched: "default".into(),
+        }
+    }
+}
+
+fn routes_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(ROUTES_FILE)
+}
+
+fn load_routing_table(data_dir: &Path) -> RoutingTable {
+    let path = routes_path(data_dir);
+    let Ok(s) = std::fs::read_to_string(&path) else {
+        return RoutingTable::default();
+    };
+    match serde_json::from_str::<RoutingTable>(&s) {
+        Ok(t) if t.validate().is_ok() => t,
+        _ => {
+            eprintln!("[proxy] ignoring invalid {}", path.display());
+            RoutingTable::default()
+        }
+    }
+}
+
+fn save_routing_table(data_dir: &Path, table: &RoutingTable) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(table).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+    let path = routes_path(data_dir);
+    std::fs::write(&path, json).map_err(|e| format!("write {}: {}", path.display(), e))
+}
+
+static ROUTING: Lazy<RwLock<RoutingTable>> =
+    Lazy::new(|| RwLock::new(load_routing_table(&crate::store::data_dir())));
+
+pub fn routing_table() -> RoutingTable {
+    ROUTING.read().unwrap().clone()
+}
+
+/// Validates, persists and applies `table`; running connections pick it up on
+/// their next upstream connect.
+pub fn set_routing_table(table: RoutingTable) -> Result<(), String> {
+    table.validate()?;
+    save_routing_table(&crate::store::data_dir(), &table)?;
+    *ROUTING.write().unwrap() = table;
+    Ok(())
+}
+
+pub fn route_for(host: &str, port: u16) -> RouteDecision {
+    let fallback = current_upstream_proxy();
+    ROUTING
+        .read()
+        .unwrap()
+        .resolve(host, port, fallback.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> RoutingTable {
+        serde_json::from_str(
+            r#"{
+                "upstreams": {"corp": "http://proxy.corp:3128", "socks": "socks5h://10.0.0.9"},
+                "rules": [
+                    {"cidrs": ["192.168.0.0/16", "fd00::/8"], "via": "direct"},
+                    {"hosts": ["*.openai.com", "api.anthropic.com"], "ports": [443], "via": "corp"},
+                    {"hosts": ["*.internal"], "via": "socks"}
+                ],
+                "bypass": ["localhost", "127.0.0.0/8", "[::1]", ".lan", "gpu-box:11434"]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_bypass_rules_and_default() {
+        let t = table();
+        t.validate().unwrap();
+        let fallback = Some("http://global:8080");
+        let via = |host: &str, port| t.resolve(host, port, fallback).upstream;
+
+        for (host, port) in [
+            ("127.0.0.1", 11434),
+            ("LOCALHOST.", 80),
+            ("[::1]", 8080),
+            ("box.lan", 443),
+            ("gpu-box", 11434),
+            ("192.168.1.20", 443),
+            ("::ffff:192.168.3.4", 443),
+            ("fd12::1", 80),
+        ] {
+            assert_eq!(via(host, port), None, "{host}:{port} goes direct");
+        }
+        assert_eq!(
+            t.resolve("gpu-box", 11434, fallback).matched,
+            "bypass gpu-box:11434"
+        );
+        assert_eq!(
+            via("api.openai.com", 443).as_deref(),
+            Some("http://proxy.corp:3128")
+        );
+        assert_eq!(
+            via("api.anthropic.com", 443).as_deref(),
+            Some("http://proxy.corp:3128")
+        );
+        assert_eq!(
+            via("git.internal", 22).as_deref(),
+            Some("socks5h://10.0.0.9")
+        );
+        // port and glob mismatches fall through to the global upstream
+        for (host, port) in [("api.openai.com", 80), ("openai.com", 443), ("gpu-box", 80)] {
+            let d = t.resolve(host, port, fallback);
+            assert_eq!(d.matched, "default");
+            assert_eq!(d.upstream.as_deref(), fallback);
+        }
+    }
+
+    #[test]
+    fn validation_and_globs() {
+        let mut t = table();
+        t.rules[1].via = "missing".into();
+        assert!(t.validate().unwrap_err().contains("unknown upstream"));
+        let mut t = table();
+        t.rules[0].cidrs.push("10.0.0.0/33".into());
+        assert!(t.validate().is_err());
+        let mut t = table();
+        t.upstreams.insert("bad".into(), "proxy:3128".into());
+        assert!(t.validate().is_err());
+
+        assert!(glob_match("*.openai.*", "eu.api.openai.com"));
+        assert!(glob_match("ollama-*", "ollama-7"));
+        assert!(!glob_match("*.openai.com", "openai.com"));
+        assert!(!glob_match("a*c", "abcd"));
+    }
+
+    #[test]
+    fn table_is_persisted_under_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("app-data");
+        assert_eq!(load_routing_table(&data_dir), RoutingTable::default());
+        save_routing_table(&data_dir, &table()).unwrap();
+        assert!(data_dir.join("proxy_routes.json").exists());
+        assert_eq!(load_routing_table(&data_dir), table());
+
+        std::fs::write(routes_path(&data_dir), "{\"rules\":[{\"via\":\"nope\"}]}").unwrap();
+        assert_eq!(load_routing_table(&data_dir), RoutingTable::default());
+    }
+}
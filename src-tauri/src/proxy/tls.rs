@@ -2,8 +2,10 @@ use hyper_rustls::HttpsConnectorBuilder;
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::rt::TokioExecutor;
+use once_cell::sync::Lazy;
 use rustls::ServerConfig as RustlsServerConfig;
-use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_rustls::TlsAcceptor;
 
 // use proxy_log! macro directly if needed
@@ -42,6 +44,35 @@ pub(crate) fn build_mitm_acceptor(host: &str) -> Result<TlsAcceptor, String> {
     Ok(TlsAcceptor::from(std::sync::Arc::new(server_cfg)))
 }
 
+// 系统信任库只读一次，所有上游 TLS 连接共用
+static UPSTREAM_TLS_CONFIG: Lazy<std::sync::Arc<rustls::ClientConfig>> = Lazy::new(|| {
+    let mut roots = rustls::RootCertStore::empty();
+    if let Ok(certs) = rustls_native_certs::load_native_certs() {
+        for c in certs {
+            let _ = roots.add(c);
+        }
+    }
+    std::sync::Arc::new(
+        rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+});
+
+// TLS to the real server over an already connected socket (direct or tunnelled), HTTP/1.1 only.
+pub(crate) async fn connect_upstream_tls<S: AsyncRead + AsyncWrite + Unpin>(
+    io: S,
+    host: &str,
+) -> Result<tokio_rustls::client::TlsStream<S>, String> {
+    let name = host.trim_start_matches('[').trim_end_matches(']');
+    let server_name = ServerName::try_from(name.to_string())
+        .map_err(|e| format!("invalid TLS server name {:?}: {}", host, e))?;
+    tokio_rustls::TlsConnector::from(UPSTREAM_TLS_CONFIG.clone())
+        .connect(server_name, io)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 pub(crate) fn build_https_client()
 -> Client<hyper_rustls::HttpsConnector<HttpConnector>, crate::proxy::ProxyBody> {
     let https = HttpsConnectorBuilder::new()
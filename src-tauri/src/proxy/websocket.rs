@@ -0,0 +1,572 @@
+// WebSocket over a MITM session: we forward the upgrade handshake ourselves, then
+// relay frames untouched in both directions while decoding them into events.
+use base64::Engine as _;
+use base64::engine::general_purpose;
+use bytes::Bytes;
+use http::{HeaderName, HeaderValue};
+use http_body::Frame;
+use http_body_util::StreamBody;
+use hyper::Response;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::capture::framing::{BodyDecoder, response_framing};
+use crate::events::{CaptureEvent, SharedSink};
+use crate::http_shared::{
+    Header, HttpResponseEvent, ResponseCompleteEvent, WebSocketMessageEvent, WsDirection, mono_us,
+    now_rfc3339,
+};
+use crate::process_lookup::{cached_process_info, try_lookup_process};
+use crate::proxy::{
+    UpstreamStream, connect_upstream_tls, connect_via_upstream, now_millis, read_http_response_head,
+};
+use crate::proxy_log;
+
+use super::mitm_service::{MitmResponse, MitmShared, ParsedClientRequest, build_empty_response};
+
+// 单条消息（分片合并后）的解码上限；超过后该方向只转发不解码
+const MAX_MESSAGE_BYTES: usize = 16 << 20;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+/// Upstream side of an accepted handshake, parked until the session takes the
+/// client stream back from hyper.
+pub(crate) struct WebSocketUpgrade {
+    upstream: tokio_rustls::client::TlsStream<UpstreamStream>,
+    // bytes the server sent right after the 101 head
+    leftover: Bytes,
+    id: String,
+    sink: SharedSink,
+    conn_id: u64,
+    last_activity: Arc<AtomicU64>,
+}
+
+pub(crate) type UpgradeSlot = Arc<Mutex<Option<WebSocketUpgrade>>>;
+
+fn header<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+pub(crate) fn is_websocket_upgrade(parsed: &ParsedClientRequest) -> bool {
+    // HTTP/1.1 Upgrade only; h2 extended CONNECT is not intercepted
+    parsed.req_event.version == "1.1"
+        && parsed.method.eq_ignore_ascii_case("GET")
+        && header(&parsed.headers, "upgrade").is_some_and(|v| {
+            v.split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case("websocket"))
+        })
+}
+
+fn handshake_request(parsed: &ParsedClientRequest) -> Vec<u8> {
+    let mut out = format!("{} {} HTTP/1.1\r\n", parsed.method, parsed.path).into_bytes();
+    for h in parsed.headers.iter() {
+        // no permessage-deflate, so frames stay readable
+        if matches!(
+            h.name.to_ascii_lowercase().as_str(),
+            "proxy-connection" | "proxy-authorization" | "sec-websocket-extensions"
+        ) {
+            continue;
+        }
+        out.extend_from_slice(format!("{}: {}\r\n", h.name, h.value).as_bytes());
+    }
+    out.extend_from_slice(b"\r\n");
+    out.extend_from_slice(&parsed.body);
+    out
+}
+
+// Body of a refused handshake, framed like any other response; bounded by a read timeout.
+async fn read_refusal_body<R: AsyncRead + Unpin>(
+    upstream: &mut R,
+    status: u16,
+    headers: &[Header],
+    leftover: &[u8],
+) -> Bytes {
+    let mut decoder = BodyDecoder::new(response_framing(Some("GET"), status, headers));
+    let (mut pending, mut body) = (leftover.to_vec(), Vec::new());
+    let mut buf = vec![0u8; 16384];
+    while let Ok(used) = decoder.decode(&pending, &mut body) {
+        pending.drain(..used);
+        if decoder.is_done() || body.len() > MAX_MESSAGE_BYTES {
+            break;
+        }
+        match tokio::time::timeout(std::time::Duration::from_secs(10), upstream.read(&mut buf))
+            .await
+        {
+            Ok(Ok(n)) if n > 0 => pending.extend_from_slice(&buf[..n]),
+            _ => break,
+        }
+    }
+    body.into()
+}
+
+/// Sends the upgrade request upstream (directly or through `proxy_url`) and answers
+/// the client with the server's response. On 101 the upstream stream is left in
+/// `shared.upgrade` for [`relay_websocket`].
+pub(crate) async fn handle_websocket(
+    shared: &MitmShared,
+    parsed: ParsedClientRequest,
+    proxy_url: Option<String>,
+) -> MitmResponse {
+    let id = parsed.id.clone();
+    let tcp = match &proxy_url {
+        Some(url) => connect_via_upstream(url, &shared.host, shared.port).await,
+        None => TcpStream::connect((shared.host.as_str(), shared.port))
+            .await
+            .map(UpstreamStream::Tcp)
+            .map_err(|e| e.to_string()),
+    };
+    let connected = match tcp {
+        Ok(tcp) => connect_upstream_tls(tcp, &shared.host).await,
+        Err(e) => Err(e),
+    };
+    let mut upstream = match connected {
+        Ok(s) => s,
+        Err(e) => {
+            proxy_log!(
+                "[proxy][conn={}][req={}] websocket upstream connect failed: {}",
+                shared.conn_id,
+                id,
+                e
+            );
+            return build_empty_response(502).await;
+        }
+    };
+    if upstream
+        .write_all(&handshake_request(&parsed))
+        .await
+        .is_err()
+        || upstream.flush().await.is_err()
+    {
+        return build_empty_response(502).await;
+    }
+    let (status, version, reason, headers, leftover) =
+        match read_http_response_head(&mut upstream).await {
+            Ok(v) => v,
+            Err(e) => {
+                proxy_log!(
+                    "[proxy][conn={}][req={}] websocket handshake failed: {}",
+                    shared.conn_id,
+                    id,
+                    e
+                );
+                return build_empty_response(502).await;
+            }
+        };
+    let switched = status == 101;
+    proxy_log!(
+        "[proxy][conn={}][req={}] websocket handshake: {} via={}",
+        shared.conn_id,
+        id,
+        status,
+        proxy_url.as_deref().unwrap_or("direct")
+    );
+    let body = if switched {
+        Bytes::new()
+    } else {
+        read_refusal_body(&mut upstream, status, &headers, &leftover).await
+    };
+
+    let mut head_evt = HttpResponseEvent {
+        id: id.clone(),
+        timestamp: now_rfc3339(),
+        src_ip: shared.host.clone(),
+        src_port: shared.port,
+        dst_ip: shared.peer.ip().to_string(),
+        dst_port: shared.peer.port(),
+        status_code: status,
+        reason: Some(reason).filter(|r| !r.is_empty()),
+        version,
+        headers: headers.clone(),
+        body_base64: if body.is_empty() {
+            None
+        } else {
+            Some(general_purpose::STANDARD.encode(&body))
+        },
+        body_len: body.len(),
+        process_name: None,
+        pid: None,
+        process: None,
+        is_llm: parsed.req_event.is_llm,
+        llm_provider: parsed.req_event.llm_provider.clone(),
+        mono_us: Some(mono_us()),
+    };
+    let (pname, pid) = try_lookup_process(shared.process_key(), true);
+    if pname.is_some() || pid.is_some() {
+        head_evt.process_name = pname;
+        head_evt.pid = pid;
+        head_evt.process = cached_process_info(head_evt.pid);
+    }
+    shared.sink.emit(&CaptureEvent::Response(head_evt));
+    shared.last_activity.store(now_millis(), Ordering::Relaxed);
+
+    let mut rb = Response::builder().status(status);
+    for h in headers.iter() {
+        let lname = h.name.to_ascii_lowercase();
+        // hyper frames the body itself; on 101 Connection/Upgrade must reach the client
+        let hop = matches!(
+            lname.as_str(),
+            "content-length" | "transfer-encoding" | "keep-alive" | "proxy-connection"
+        ) || (!switched && matches!(lname.as_str(), "connection" | "upgrade"));
+        if hop {
+            continue;
+        }
+        if let (Ok(name), Ok(val)) = (h.name.parse::<HeaderName>(), h.value.parse::<HeaderValue>())
+        {
+            rb = rb.header(name, val);
+        }
+    }
+    let (tx, rx) = mpsc::channel::<Result<Frame<Bytes>, hyper::Error>>(1);
+    if switched {
+        *shared.upgrade.lock().unwrap() = Some(WebSocketUpgrade {
+            upstream,
+            leftover,
+            id,
+            sink: shared.sink.clone(),
+            conn_id: shared.conn_id,
+            last_activity: shared.last_activity.clone(),
+        });
+    } else {
+        let _ = tx.send(Ok(Frame::data(body))).await;
+        shared
+            .sink
+            .emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+                &id,
+            )));
+    }
+    drop(tx);
+    rb.body(StreamBody::new(ReceiverStream::new(rx))).unwrap()
+}
+
+/// Relays an upgraded connection until both directions close, emitting one event
+/// per message. `client_buffered` is what hyper had read past the handshake.
+pub(crate) async fn relay_websocket<C: AsyncRead + AsyncWrite + Unpin>(
+    client: C,
+    client_buffered: Bytes,
+    ws: WebSocketUpgrade,
+) {
+    let WebSocketUpgrade {
+        upstream,
+        leftover,
+        id,
+        sink,
+        conn_id,
+        last_activity,
+    } = ws;
+    proxy_log!(
+        "[proxy][conn={}][req={}] websocket relay start",
+        conn_id,
+        id
+    );
+    let (client_r, client_w) = tokio::io::split(client);
+    let (upstream_r, upstream_w) = tokio::io::split(upstream);
+    let tap = |direction| Tap {
+        id: &id,
+        sink: &sink,
+        last_activity: &last_activity,
+        direction,
+    };
+    let (sent, received) = tokio::join!(
+        pump(
+            client_r,
+            upstream_w,
+            client_buffered,
+            tap(WsDirection::ClientToServer)
+        ),
+        pump(
+            upstream_r,
+            client_w,
+            leftover,
+            tap(WsDirection::ServerToClient)
+        ),
+    );
+    proxy_log!(
+        "[proxy][conn={}][req={}] websocket relay end: sent={}B received={}B",
+        conn_id,
+        id,
+        sent,
+        received
+    );
+    sink.emit(&CaptureEvent::ResponseComplete(ResponseCompleteEvent::now(
+        &id,
+    )));
+}
+
+struct Tap<'a> {
+    id: &'a str,
+    sink: &'a SharedSink,
+    last_activity: &'a AtomicU64,
+    direction: WsDirection,
+}
+
+// Copies one direction verbatim; returns the bytes forwarded.
+async fn pump<R, W>(mut from: R, mut to: W, first: Bytes, tap: Tap<'_>) -> u64
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut decoder = FrameDecoder::default();
+    let mut total = 0u64;
+    let mut chunk = first.to_vec();
+    let mut buf = vec![0u8; 65536];
+    loop {
+        if !chunk.is_empty() {
+            if to.write_all(&chunk).await.is_err() || to.flush().await.is_err() {
+                break;
+            }
+            total += chunk.len() as u64;
+            tap.last_activity.store(now_millis(), Ordering::Relaxed);
+            for msg in decoder.push(&chunk) {
+                tap.sink.emit(&CaptureEvent::WebSocketMessage(message_event(
+                    tap.id,
+                    tap.direction,
+                    msg,
+                )));
+            }
+        }
+        match from.read(&mut buf).await {
+            Ok(n) if n > 0 => {
+                chunk.clear();
+                chunk.extend_from_slice(&buf[..n]);
+            }
+            _ => break,
+        }
+    }
+    let _ = to.shutdown().await;
+    total
+}
+
+fn message_event(id: &str, direction: WsDirection, msg: WsMessage) -> WebSocketMessageEvent {
+    let WsMessage { opcode, payload } = msg;
+    let (mut text, mut body_base64, mut close_code, mut close_reason) = (None, None, None, None);
+    match opcode {
+        OP_TEXT => text = Some(String::from_utf8_lossy(&payload).into_owned()),
+        OP_CLOSE if payload.len() >= 2 => {
+            close_code = Some(u16::from_be_bytes([payload[0], payload[1]]));
+            close_reason =
+                Some(String::from_utf8_lossy(&payload[2..]).into_owned()).filter(|r| !r.is_empty());
+        }
+        _ if !payload.is_empty() => body_base64 = Some(general_purpose::STANDARD.encode(&payload)),
+        _ => {}
+    }
+    let kind = match opcode {
+        OP_TEXT => "text",
+        OP_BINARY => "binary",
+        OP_CLOSE => "close",
+        OP_PING => "ping",
+        OP_PONG => "pong",
+        _ => "reserved",
+    };
+    WebSocketMessageEvent {
+        id: id.to_string(),
+        timestamp: now_rfc3339(),
+        direction,
+        opcode,
+        kind: kind.into(),
+        text,
+        body_base64,
+        body_len: payload.len(),
+        close_code,
+        close_reason,
+        mono_us: Some(mono_us()),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct WsMessage {
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+struct RawFrame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+struct TooLarge;
+
+// RFC 6455 §5.2; Ok(None) until the whole frame is buffered.
+fn parse_frame(buf: &[u8]) -> Result<Option<(usize, RawFrame)>, TooLarge> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let (fin, opcode) = (buf[0] & 0x80 != 0, buf[0] & 0x0f);
+    let (len, mut pos) = match buf[1] & 0x7f {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()), 10),
+        126 | 127 => return Ok(None),
+        n => (n as u64, 2),
+    };
+    if len > MAX_MESSAGE_BYTES as u64 {
+        return Err(TooLarge);
+    }
+    let mask = if buf[1] & 0x80 != 0 {
+        let Some(key) = buf.get(pos..pos + 4) else {
+            return Ok(None);
+        };
+        pos += 4;
+        Some([key[0], key[1], key[2], key[3]])
+    } else {
+        None
+    };
+    let end = pos + len as usize;
+    let Some(data) = buf.get(pos..end) else {
+        return Ok(None);
+    };
+    let mut payload = data.to_vec();
+    if let Some(key) = mask {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= key[i % 4];
+        }
+    }
+    Ok(Some((
+        end,
+        RawFrame {
+            fin,
+            opcode,
+            payload,
+        },
+    )))
+}
+
+/// Incremental frame decoder for one direction. Data frames are joined across
+/// fragments; control frames come out as they arrive, even mid-message.
+#[derive(Default)]
+struct FrameDecoder {
+    buf: Vec<u8>,
+    // opcode and payload of a fragmented message still waiting for its FIN frame
+    partial: Option<(u8, Vec<u8>)>,
+    // oversized message: stop decoding, the relay keeps forwarding
+    broken: bool,
+}
+
+impl FrameDecoder {
+    fn push(&mut self, data: &[u8]) -> Vec<WsMessage> {
+        let mut out = Vec::new();
+        if self.broken {
+            return out;
+        }
+        self.buf.extend_from_slice(data);
+        let mut pos = 0;
+        while !self.broken {
+            match parse_frame(&self.buf[pos..]) {
+                Ok(Some((used, frame))) => {
+                    pos += used;
+                    out.extend(self.on_frame(frame));
+                }
+                Ok(None) => break,
+                Err(TooLarge) => self.broken = true,
+            }
+        }
+        if self.broken {
+            self.buf = Vec::new();
+        } else {
+            self.buf.drain(..pos);
+        }
+        out
+    }
+
+    fn on_frame(&mut self, frame: RawFrame) -> Option<WsMessage> {
+        if frame.opcode >= OP_CLOSE {
+            return Some(WsMessage {
+                opcode: frame.opcode,
+                payload: frame.payload,
+            });
+        }
+        let (opcode, mut payload) = match (frame.opcode, self.partial.take()) {
+            (OP_CONTINUATION, Some(started)) => started,
+            // continuation of a message we never saw the start of
+            (OP_CONTINUATION, None) => return None,
+            (op, _) => (op, Vec::new()),
+        };
+        payload.extend_from_slice(&frame.payload);
+        if payload.len() > MAX_MESSAGE_BYTES {
+            self.broken = true;
+            return None;
+        }
+        if frame.fin {
+            Some(WsMessage { opcode, payload })
+        } else {
+            self.partial = Some((opcode, payload));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(fin: bool, opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+        let mut out = vec![(fin as u8) << 7 | opcode];
+        let bit = if mask.is_some() { 0x80 } else { 0 };
+        match payload.len() {
+            n if n < 126 => out.push(bit | n as u8),
+            n => {
+                out.push(bit | 126);
+                out.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+        }
+        match mask {
+            Some(key) => {
+                out.extend_from_slice(&key);
+                out.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+            }
+            None => out.extend_from_slice(payload),
+        }
+        out
+    }
+
+    #[test]
+    fn fragmented_masked_message_and_close() {
+        let key = Some([1, 2, 3, 4]);
+        let mut wire = frame(false, OP_TEXT, "héllo ".as_bytes(), key);
+        // a ping may sit between fragments
+        wire.extend(frame(true, OP_PING, b"", key));
+        wire.extend(frame(true, OP_CONTINUATION, &[b'w'; 300], key));
+        let mut close = 1001u16.to_be_bytes().to_vec();
+        close.extend_from_slice(b"going away");
+        wire.extend(frame(true, OP_CLOSE, &close, None));
+
+        // split at every byte, like TLS records would
+        let mut dec = FrameDecoder::default();
+        let mut msgs = Vec::new();
+        for b in wire.iter() {
+            msgs.extend(dec.push(std::slice::from_ref(b)));
+        }
+        assert_eq!(
+            msgs.iter().map(|m| m.opcode).collect::<Vec<_>>(),
+            [OP_PING, OP_TEXT, OP_CLOSE]
+        );
+        let text = message_event("r1", WsDirection::ClientToServer, msgs.remove(1));
+        assert_eq!(text.kind, "text");
+        assert_eq!(text.body_len, 7 + 300);
+        assert!(text.text.unwrap().starts_with("héllo www"));
+        let close = message_event("r1", WsDirection::ServerToClient, msgs.pop().unwrap());
+        assert_eq!(close.close_code, Some(1001));
+        assert_eq!(close.close_reason.as_deref(), Some("going away"));
+        assert!(dec.buf.is_empty());
+    }
+
+    #[test]
+    fn oversized_frame_stops_decoding() {
+        let mut head = vec![0x82, 127];
+        head.extend_from_slice(&(MAX_MESSAGE_BYTES as u64 + 1).to_be_bytes());
+        let mut dec = FrameDecoder::default();
+        assert!(dec.push(&head).is_empty());
+        assert!(dec.broken);
+        assert!(dec.push(&frame(true, OP_TEXT, b"hi", None)).is_empty());
+    }
+}
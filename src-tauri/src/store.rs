@@ -0,0 +1,826 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use base64::{Engine as _, engine::general_purpose};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+
+use crate::events::{CaptureEvent, EventSink};
+use crate::http_shared::{
+    HttpRequestEvent, HttpResponseEvent, ResponseCompleteEvent, WebSocketMessageEvent, gen_id,
+    now_rfc3339,
+};
+use crate::timing::ExchangeTimings;
+use crate::usage::TokenUsage;
+
+// Each session is an append-only JSONL file: one session header line followed by
+// request/response records in arrival order. Streaming chunks are stored as-is and
+// reassembled per id when read back. A closed session also gets a small
+// `<id>.index.json` with its summary so listing doesn't parse every record.
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+pub static STORE: Lazy<SessionStore> = Lazy::new(|| SessionStore::new(data_dir().join("sessions")));
+
+/// Base directory for files the app writes: the dir given to `set_data_dir`, or
+/// the working directory when none was set.
+pub fn data_dir() -> PathBuf {
+    // 未设置时退回工作目录（命令行默认行为）
+    DATA_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+}
+
+/// Where `STORE` keeps its `sessions` directory: the app-data dir for the desktop
+/// app, `--data-dir` for the CLI. Must run before the store is first used.
+pub fn set_data_dir(dir: PathBuf) -> Result<(), String> {
+    if Lazy::get(&STORE).is_some() {
+        return Err("session store already opened".into());
+    }
+    DATA_DIR
+        .set(dir)
+        .map_err(|_| "data dir already set".to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Record {
+    Session {
+        id: String,
+        source: String,
+        started_at: String,
+    },
+    Request(HttpRequestEvent),
+    Response(HttpResponseEvent),
+    Complete(ResponseCompleteEvent),
+    WebSocket(WebSocketMessageEvent),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub source: String,
+    pub started_at: String,
+    pub exchange_count: usize,
+    pub size_bytes: u64,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExchangeSummary {
+    pub id: String,
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    pub dst_ip: String,
+    pub dst_port: u16,
+    pub status_code: Option<u16>,
+    pub request_body_len: usize,
+    pub response_body_len: usize,
+    pub process_name: Option<String>,
+    pub is_llm: bool,
+    pub llm_provider: Option<String>,
+    pub timings: Option<ExchangeTimings>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExchangePage {
+    pub total: usize,
+    pub offset: usize,
+    pub items: Vec<ExchangeSummary>,
+}
+
+/// A request paired with its response; the response body is the concatenation of
+/// every chunk recorded for the same id.
+#[derive(Debug, Clone, Serialize)]
+pub struct Exchange {
+    pub request: Option<HttpRequestEvent>,
+    pub response: Option<HttpResponseEvent>,
+    /// Timestamp of the last response record (end of the stream).
+    pub response_finished_at: Option<String>,
+    /// From the completion record, when the response was seen to finish.
+    pub usage: Option<TokenUsage>,
+    pub timings: Option<ExchangeTimings>,
+    /// Messages relayed after a WebSocket upgrade, in arrival order.
+    #[serde(default)]
+    pub websocket: Vec<WebSocketMessageEvent>,
+}
+
+// Summary of a closed session, valid while the session file keeps `size_bytes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionIndex {
+    source: String,
+    started_at: String,
+    exchange_count: usize,
+    size_bytes: u64,
+}
+
+fn index_path(session_path: &Path) -> PathBuf {
+    session_path.with_extension("index.json")
+}
+
+/// Writer for one session file. The store keeps one of these as the active session;
+/// imports and offline analyses write to their own. The index is written on drop.
+pub struct SessionWriter {
+    id: String,
+    path: PathBuf,
+    writer: BufWriter<File>,
+    source: String,
+    started_at: String,
+    exchange_count: usize,
+}
+
+impl SessionWriter {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), String> {
+        if matches!(record, Record::Request(_)) {
+            self.exchange_count += 1;
+        }
+        write_record(&mut self.writer, record)
+    }
+
+    fn summary(&self, active: bool) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            source: self.source.clone(),
+            started_at: self.started_at.clone(),
+            exchange_count: self.exchange_count,
+            size_bytes: fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0),
+            active,
+        }
+    }
+
+    pub fn record_request(&mut self, evt: &HttpRequestEvent) {
+        let _ = self.write(&Record::Request(evt.clone()));
+    }
+
+    pub fn record_response(&mut self, evt: &HttpResponseEvent) {
+        let _ = self.write(&Record::Response(evt.clone()));
+    }
+
+    pub fn record_complete(&mut self, evt: &ResponseCompleteEvent) {
+        let _ = self.write(&Record::Complete(evt.clone()));
+    }
+
+    pub fn record_websocket(&mut self, evt: &WebSocketMessageEvent) {
+        let _ = self.write(&Record::WebSocket(evt.clone()));
+    }
+}
+
+impl Drop for SessionWriter {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+        let s = self.summary(false);
+        write_index(
+            &self.path,
+            &SessionIndex {
+                source: s.source,
+                started_at: s.started_at,
+                exchange_count: s.exchange_count,
+                size_bytes: s.size_bytes,
+            },
+        );
+    }
+}
+
+pub struct SessionStore {
+    dir: PathBuf,
+    active: Mutex<Option<SessionWriter>>,
+}
+
+fn valid_session_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn new_session_id() -> String {
+    let ts = time::OffsetDateTime::now_utc()
+        .format(time::macros::format_description!(
+            "[year][month][day]-[hour][minute][second]"
+        ))
+        .unwrap_or_default();
+    format!("{}-{}", ts, &gen_id()[..6])
+}
+
+fn decode_body(b64: &Option<String>) -> Vec<u8> {
+    b64.as_ref()
+        .and_then(|s| general_purpose::STANDARD.decode(s).ok())
+        .unwrap_or_default()
+}
+
+type ResponseAcc = Option<(HttpResponseEvent, Vec<u8>, String)>;
+
+// Fold response records sharing an id into one event: head fields come from the
+// first record, the body is every chunk appended in order.
+fn merge_response(acc: &mut ResponseAcc, evt: HttpResponseEvent) {
+    let body = decode_body(&evt.body_base64);
+    match acc {
+        Some((_, buf, last_ts)) => {
+            buf.extend_from_slice(&body);
+            *last_ts = evt.timestamp;
+        }
+        None => {
+            let ts = evt.timestamp.clone();
+            *acc = Some((evt, body, ts))
+        }
+    }
+}
+
+fn finish_exchange(
+    request: Option<HttpRequestEvent>,
+    acc: ResponseAcc,
+    complete: Option<ResponseCompleteEvent>,
+    websocket: Vec<WebSocketMessageEvent>,
+) -> Exchange {
+    let (response, response_finished_at) = match acc {
+        Some((mut evt, body, last_ts)) => {
+            evt.body_len = body.len();
+            evt.body_base64 = if body.is_empty() {
+                None
+            } else {
+                Some(general_purpose::STANDARD.encode(&body))
+            };
+            (Some(evt), Some(last_ts))
+        }
+        None => (None, None),
+    };
+    let (usage, timings) = match complete {
+        Some(c) => (c.usage, c.timings),
+        None => (None, None),
+    };
+    Exchange {
+        request,
+        response,
+        response_finished_at,
+        usage,
+        timings,
+        websocket,
+    }
+}
+
+impl SessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            active: Mutex::new(None),
+        }
+    }
+
+    fn session_path(&self, id: &str) -> Result<PathBuf, String> {
+        if !valid_session_id(id) {
+            return Err(format!("invalid session id: {}", id));
+        }
+        Ok(self.dir.join(format!("{}.jsonl", id)))
+    }
+
+    /// Creates a new session file that is not the active session.
+    pub fn create_session(&self, source: &str) -> Result<SessionWriter, String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let id = new_session_id();
+        let path = self.session_path(&id)?;
+        let file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+        let mut session = SessionWriter {
+            id: id.clone(),
+            path,
+            writer: BufWriter::new(file),
+            source: source.to_string(),
+            started_at: now_rfc3339(),
+            exchange_count: 0,
+        };
+        session.write(&Record::Session {
+            id,
+            source: session.source.clone(),
+            started_at: session.started_at.clone(),
+        })?;
+        Ok(session)
+    }
+
+    /// Opens a new session unless one is already active; returns the active id.
+    pub fn begin_session(&self, source: &str) -> Result<String, String> {
+        let mut g = self.active.lock().unwrap();
+        if let Some(s) = g.as_ref() {
+            return Ok(s.id.clone());
+        }
+        let session = self.create_session(source)?;
+        let id = session.id.clone();
+        *g = Some(session);
+        Ok(id)
+    }
+
+    pub fn end_session(&self) {
+        // dropping the writer flushes it and writes the index
+        self.active.lock().unwrap().take();
+    }
+
+    pub fn active_session_id(&self) -> Option<String> {
+        self.active.lock().unwrap().as_ref().map(|s| s.id.clone())
+    }
+
+    pub fn record_request(&self, evt: &HttpRequestEvent) {
+        if let Some(s) = self.active.lock().unwrap().as_mut() {
+            s.record_request(evt);
+        }
+    }
+
+    pub fn record_response(&self, evt: &HttpResponseEvent) {
+        if let Some(s) = self.active.lock().unwrap().as_mut() {
+            s.record_response(evt);
+        }
+    }
+
+    pub fn record_complete(&self, evt: &ResponseCompleteEvent) {
+        if let Some(s) = self.active.lock().unwrap().as_mut() {
+            s.record_complete(evt);
+        }
+    }
+
+    pub fn record_websocket(&self, evt: &WebSocketMessageEvent) {
+        if let Some(s) = self.active.lock().unwrap().as_mut() {
+            s.record_websocket(evt);
+        }
+    }
+
+    fn read_records(&self, id: &str) -> Result<Vec<Record>, String> {
+        let path = self.session_path(id)?;
+        read_records_from(&path)
+    }
+
+    pub fn list_sessions(&self) -> Result<Vec<SessionSummary>, String> {
+        // the active session is counted in memory
+        let active = self
+            .active
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|w| w.summary(true));
+        let mut out: Vec<SessionSummary> = active.iter().cloned().collect();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(out),
+            Err(e) => return Err(e.to_string()),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if active.as_ref().is_some_and(|a| a.id == id) {
+                continue;
+            }
+            let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            // a missing or stale index (crash, older version) is rebuilt from the records
+            let index = match read_index(&path) {
+                Some(ix) if ix.size_bytes == size_bytes => ix,
+                _ => match scan_session(&path, size_bytes) {
+                    Some(ix) => {
+                        write_index(&path, &ix);
+                        ix
+                    }
+                    None => continue,
+                },
+            };
+            out.push(SessionSummary {
+                id: id.to_string(),
+                source: index.source,
+                started_at: index.started_at,
+                exchange_count: index.exchange_count,
+                size_bytes,
+                active: false,
+            });
+        }
+        out.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        Ok(out)
+    }
+
+    pub fn list_exchanges(
+        &self,
+        session_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ExchangePage, String> {
+        let records = self.read_records(session_id)?;
+        let mut order: Vec<String> = Vec::new();
+        let mut requests = std::collections::HashMap::<String, HttpRequestEvent>::new();
+        let mut responses = std::collections::HashMap::<String, (u16, usize)>::new();
+        let mut timings = std::collections::HashMap::<String, ExchangeTimings>::new();
+        for r in records {
+            match r {
+                Record::Request(evt) => {
+                    order.push(evt.id.clone());
+                    requests.insert(evt.id.clone(), evt);
+                }
+                Record::Response(evt) => {
+                    let e = responses
+                        .entry(evt.id.clone())
+                        .or_insert((evt.status_code, 0));
+                    e.1 += evt.body_len;
+                }
+                Record::Complete(evt) => {
+                    if let Some(t) = evt.timings {
+                        timings.insert(evt.id, t);
+                    }
+                }
+                Record::Session { .. } | Record::WebSocket(_) => {}
+            }
+        }
+        let total = order.len();
+        let items = order
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|id| {
+                let req = requests.get(id)?;
+                let resp = responses.get(id);
+                Some(ExchangeSummary {
+                    id: id.clone(),
+                    timestamp: req.timestamp.clone(),
+                    method: req.method.clone(),
+                    path: req.path.clone(),
+                    dst_ip: req.dst_ip.clone(),
+                    dst_port: req.dst_port,
+                    status_code: resp.map(|r| r.0),
+                    request_body_len: req.body_len,
+                    response_body_len: resp.map(|r| r.1).unwrap_or(0),
+                    process_name: req.process_name.clone(),
+                    is_llm: req.is_llm,
+                    llm_provider: req.llm_provider.clone(),
+                    timings: timings.get(id).cloned(),
+                })
+            })
+            .collect();
+        Ok(ExchangePage {
+            total,
+            offset,
+            items,
+        })
+    }
+
+    pub fn get_exchange(&self, session_id: &str, id: &str) -> Result<Option<Exchange>, String> {
+        let mut request = None;
+        let mut response: ResponseAcc = None;
+        let mut complete = None;
+        let mut websocket = Vec::new();
+        for r in self.read_records(session_id)? {
+            match r {
+                Record::Request(evt) if evt.id == id => request = Some(evt),
+                Record::Response(evt) if evt.id == id => merge_response(&mut response, evt),
+                Record::Complete(evt) if evt.id == id => complete = Some(evt),
+                Record::WebSocket(evt) if evt.id == id => websocket.push(evt),
+                _ => {}
+            }
+        }
+        if request.is_none() && response.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(finish_exchange(
+            request, response, complete, websocket,
+        )))
+    }
+
+    /// Every exchange of a session in request order, with reassembled response bodies.
+    pub fn get_exchanges(&self, session_id: &str) -> Result<Vec<Exchange>, String> {
+        let mut order: Vec<String> = Vec::new();
+        let mut requests = std::collections::HashMap::<String, HttpRequestEvent>::new();
+        let mut responses = std::collections::HashMap::<String, ResponseAcc>::new();
+        let mut completes = std::collections::HashMap::<String, ResponseCompleteEvent>::new();
+        let mut websocket = std::collections::HashMap::<String, Vec<WebSocketMessageEvent>>::new();
+        for r in self.read_records(session_id)? {
+            match r {
+                Record::Request(evt) => {
+                    order.push(evt.id.clone());
+                    requests.insert(evt.id.clone(), evt);
+                }
+                Record::Response(evt) => {
+                    merge_response(responses.entry(evt.id.clone()).or_default(), evt)
+                }
+                Record::Complete(evt) => {
+                    completes.insert(evt.id.clone(), evt);
+                }
+                Record::WebSocket(evt) => websocket.entry(evt.id.clone()).or_default().push(evt),
+                Record::Session { .. } => {}
+            }
+        }
+        Ok(order
+            .into_iter()
+            .map(|id| {
+                let acc = responses.remove(&id).flatten();
+                finish_exchange(
+                    requests.remove(&id),
+                    acc,
+                    completes.remove(&id),
+                    websocket.remove(&id).unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    /// Writes already-complete exchanges (e.g. from an imported file) as a new,
+    /// closed session and returns its id.
+    pub fn import_session(&self, source: &str, exchanges: &[Exchange]) -> Result<String, String> {
+        let mut w = self.create_session(source)?;
+        for ex in exchanges {
+            if let Some(req) = &ex.request {
+                w.write(&Record::Request(req.clone()))?;
+            }
+            if let Some(resp) = &ex.response {
+                w.write(&Record::Response(resp.clone()))?;
+                if ex.usage.is_some() || ex.timings.is_some() {
+                    w.write(&Record::Complete(ResponseCompleteEvent {
+                        id: resp.id.clone(),
+                        timestamp: ex
+                            .response_finished_at
+                            .clone()
+                            .unwrap_or_else(|| resp.timestamp.clone()),
+                        usage: ex.usage.clone(),
+                        timings: ex.timings.clone(),
+                        trailers: Vec::new(),
+                    }))?;
+                }
+            }
+        }
+        Ok(w.id.clone())
+    }
+
+    pub fn delete_session(&self, session_id: &str) -> Result<(), String> {
+        if self.active_session_id().as_deref() == Some(session_id) {
+            return Err("cannot delete the active session".into());
+        }
+        let path = self.session_path(session_id)?;
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+        let _ = fs::remove_file(index_path(&path));
+        Ok(())
+    }
+}
+
+fn write_record(w: &mut BufWriter<File>, record: &Record) -> Result<(), String> {
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    w.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+    w.write_all(b"\n").map_err(|e| e.to_string())?;
+    // flush per record so a crash or window close keeps everything written so far
+    w.flush().map_err(|e| e.to_string())
+}
+
+fn read_index(session_path: &Path) -> Option<SessionIndex> {
+    let s = fs::read_to_string(index_path(session_path)).ok()?;
+    serde_json::from_str(&s).ok()
+}
+
+fn write_index(session_path: &Path, index: &SessionIndex) {
+    if let Ok(s) = serde_json::to_string(index) {
+        let _ = fs::write(index_path(session_path), s);
+    }
+}
+
+fn scan_session(path: &Path, size_bytes: u64) -> Option<SessionIndex> {
+    let records = read_records_from(path).ok()?;
+    let Some(Record::Session {
+        source, started_at, ..
+    }) = records.first()
+    else {
+        return None;
+    };
+    Some(SessionIndex {
+        source: source.clone(),
+        started_at: started_at.clone(),
+        exchange_count: records
+            .iter()
+            .filter(|r| matches!(r, Record::Request(_)))
+            .count(),
+        size_bytes,
+    })
+}
+
+fn read_records_from(path: &Path) -> Result<Vec<Record>, String> {
+    let f = File::open(path).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for line in BufReader::new(f).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // a torn last line after a crash is skipped rather than failing the session
+        if let Ok(r) = serde_json::from_str::<Record>(&line) {
+            out.push(r);
+        }
+    }
+    Ok(out)
+}
+
+/// Records into whichever session is currently active in `STORE`.
+pub struct StoreSink;
+
+impl EventSink for StoreSink {
+    fn emit(&self, event: &CaptureEvent) {
+        match event {
+            CaptureEvent::Request(evt) => STORE.record_request(evt),
+            CaptureEvent::Response(evt) => STORE.record_response(evt),
+            CaptureEvent::ResponseComplete(evt) => STORE.record_complete(evt),
+            CaptureEvent::WebSocketMessage(evt) => STORE.record_websocket(evt),
+            _ => {}
+        }
+    }
+}
+
+impl EventSink for Mutex<SessionWriter> {
+    fn emit(&self, event: &CaptureEvent) {
+        let mut w = self.lock().unwrap();
+        match event {
+            CaptureEvent::Request(evt) => w.record_request(evt),
+            CaptureEvent::Response(evt) => w.record_response(evt),
+            CaptureEvent::ResponseComplete(evt) => w.record_complete(evt),
+            CaptureEvent::WebSocketMessage(evt) => w.record_websocket(evt),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_shared::Header;
+
+    fn req(id: &str) -> HttpRequestEvent {
+        HttpRequestEvent {
+            id: id.into(),
+            timestamp: now_rfc3339(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 50000,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 11434,
+            method: "POST".into(),
+            path: "/api/chat".into(),
+            version: "1.1".into(),
+            headers: vec![Header {
+                name: "content-type".into(),
+                value: "application/json".into(),
+            }],
+            body_base64: Some(general_purpose::STANDARD.encode(b"{\"model\":\"m\"}")),
+            body_len: 13,
+            process_name: None,
+            pid: None,
+            process: None,
+            is_llm: true,
+            llm_provider: Some("ollama".into()),
+            mono_us: None,
+            conn_id: None,
+            conn_seq: None,
+        }
+    }
+
+    fn resp(id: &str, body: &[u8]) -> HttpResponseEvent {
+        HttpResponseEvent {
+            id: id.into(),
+            timestamp: now_rfc3339(),
+            src_ip: "127.0.0.1".into(),
+            src_port: 11434,
+            dst_ip: "127.0.0.1".into(),
+            dst_port: 50000,
+            status_code: 200,
+            reason: None,
+            version: "1.1".into(),
+            headers: Vec::new(),
+            body_base64: Some(general_purpose::STANDARD.encode(body)),
+            body_len: body.len(),
+            process_name: None,
+            pid: None,
+            process: None,
+            is_llm: true,
+            llm_provider: Some("ollama".into()),
+            mono_us: None,
+        }
+    }
+
+    #[test]
+    fn streamed_chunks_are_reassembled_per_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(dir.path().to_path_buf());
+        let sid = store.begin_session("proxy").unwrap();
+        store.record_request(&req("a"));
+        store.record_request(&req("b"));
+        store.record_response(&resp("a", b"hello "));
+        store.record_response(&resp("b", b"other"));
+        store.record_response(&resp("a", b"world"));
+        store.end_session();
+
+        let ex = store.get_exchange(&sid, "a").unwrap().expect("exchange");
+        let body = decode_body(&ex.response.as_ref().unwrap().body_base64);
+        assert_eq!(body, b"hello world");
+        assert_eq!(ex.response.unwrap().body_len, 11);
+
+        let page = store.list_exchanges(&sid, 1, 10).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, "b");
+        assert_eq!(page.items[0].status_code, Some(200));
+    }
+
+    #[test]
+    fn sessions_are_listed_and_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(dir.path().to_path_buf());
+        let sid = store.begin_session("capture").unwrap();
+        store.record_request(&req("x"));
+        assert!(
+            store.delete_session(&sid).is_err(),
+            "active session is protected"
+        );
+        let live = store.list_sessions().unwrap();
+        assert!(live[0].active && live[0].exchange_count == 1);
+        store.end_session();
+
+        let sessions = store.list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].source, "capture");
+        assert_eq!(sessions[0].exchange_count, 1);
+        assert!(!sessions[0].active);
+
+        // the summary comes from the index written at close; a stale one is rebuilt
+        let path = store.session_path(&sid).unwrap();
+        let mut ix = read_index(&path).expect("index");
+        assert_eq!(ix.exchange_count, 1);
+        ix.size_bytes += 1;
+        ix.exchange_count = 7;
+        write_index(&path, &ix);
+        assert_eq!(store.list_sessions().unwrap()[0].exchange_count, 1);
+
+        store.delete_session(&sid).unwrap();
+        assert!(store.list_sessions().unwrap().is_empty());
+        assert!(!index_path(&path).exists());
+        assert!(store.get_exchange("../etc", "x").is_err());
+    }
+
+    #[test]
+    fn usage_ledger_is_rebuilt_from_closed_sessions() {
+        use crate::usage::{UsageGroup, UsageLedger};
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(dir.path().to_path_buf());
+        let mut done = ResponseCompleteEvent::now("a");
+        done.usage = Some(TokenUsage {
+            input_tokens: Some(5),
+            output_tokens: Some(2),
+            ..Default::default()
+        });
+        store.begin_session("proxy").unwrap();
+        store.record_request(&req("a"));
+        store.record_complete(&done);
+        store.end_session();
+        // still open: its exchanges reach the ledger live, not from disk
+        store.begin_session("proxy").unwrap();
+        store.record_request(&req("b"));
+        store.record_complete(&ResponseCompleteEvent {
+            id: "b".into(),
+            ..done.clone()
+        });
+
+        let ledger = UsageLedger::default();
+        assert_eq!(ledger.load_from_store(&store), Ok(1));
+        let totals = ledger.totals(None, None, Some(UsageGroup::Model));
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].key.model.as_deref(), Some("m"));
+        assert_eq!(totals[0].key.provider, None);
+        assert_eq!(totals[0].total_tokens, 7);
+
+        // no usable time on the record: dated by the session start, or skipped
+        let stamped = |id: &str, started_at: &str| {
+            let w = store.create_session("import").unwrap();
+            let path = store.session_path(w.id()).unwrap();
+            drop(w);
+            let mut r = req(id);
+            r.timestamp = String::new();
+            let lines = [
+                Record::Session {
+                    id: id.into(),
+                    source: "import".into(),
+                    started_at: started_at.into(),
+                },
+                Record::Request(r),
+                Record::Complete(ResponseCompleteEvent {
+                    id: id.into(),
+                    ..done.clone()
+                }),
+            ]
+            .map(|rec| serde_json::to_string(&rec).unwrap());
+            fs::write(&path, lines.join("\n") + "\n").unwrap();
+            let _ = fs::remove_file(index_path(&path));
+        };
+        stamped("old", "2024-06-01T00:00:00Z");
+        stamped("bad", "not a time");
+        let ledger = UsageLedger::default();
+        assert_eq!(ledger.load_from_store(&store), Ok(2));
+        // 2024-06-01, UTC
+        let day = ledger.totals(Some(1_717_200_000_000), Some(1_717_286_400_000), None);
+        assert_eq!(day[0].requests, 1);
+        assert_eq!(ledger.totals(None, None, None)[0].requests, 2);
+    }
+}
@@ -0,0 +1,122 @@
+// Per-exchange latency figures derived from the monotonic `mono_us` stamps on
+// request and response events.
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeTimings {
+    /// Request sent → response head.
+    pub ttfb_ms: Option<f64>,
+    /// Request sent → first chunk carrying content, reasoning or tool call text.
+    pub ttft_ms: Option<f64>,
+    /// Request sent → last response byte.
+    pub total_ms: Option<f64>,
+    /// Gaps between consecutive token-bearing chunks.
+    pub inter_token: Option<LatencyStats>,
+    /// From reported usage only; `None` when the provider sent none.
+    pub output_tokens: Option<u64>,
+    /// Output tokens over the span from first to last token-bearing chunk.
+    pub tokens_per_sec: Option<f64>,
+    /// Number of token-bearing chunks (network reads, not tokens).
+    #[serde(default)]
+    pub token_chunks: usize,
+}
+
+fn ms(from: u64, to: u64) -> f64 {
+    to.saturating_sub(from) as f64 / 1000.0
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let idx = ((sorted.len() - 1) as f64 * p / 100.0).round() as usize;
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+pub fn latency_stats(gaps_ms: &[f64]) -> Option<LatencyStats> {
+    if gaps_ms.is_empty() {
+        return None;
+    }
+    let mut sorted = gaps_ms.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Some(LatencyStats {
+        count: sorted.len(),
+        min_ms: sorted[0],
+        mean_ms: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        p50_ms: percentile(&sorted, 50.0),
+        p90_ms: percentile(&sorted, 90.0),
+        p99_ms: percentile(&sorted, 99.0),
+        max_ms: sorted[sorted.len() - 1],
+    })
+}
+
+/// Stamps of one exchange, all in `mono_us` units.
+#[derive(Debug, Clone, Default)]
+pub struct TimingMarks {
+    pub request_us: Option<u64>,
+    pub head_us: Option<u64>,
+    pub last_us: Option<u64>,
+    pub token_us: Vec<u64>,
+}
+
+impl TimingMarks {
+    pub fn compute(&self, reported_output_tokens: Option<u64>) -> Option<ExchangeTimings> {
+        let req = self.request_us?;
+        let first_tok = self.token_us.first().copied();
+        let last_tok = self.token_us.last().copied();
+        let gaps: Vec<f64> = self.token_us.windows(2).map(|w| ms(w[0], w[1])).collect();
+        let output_tokens = reported_output_tokens;
+        let tokens_per_sec = match (output_tokens, first_tok, last_tok) {
+            (Some(n), Some(a), Some(b)) if b > a => Some(n as f64 / ((b - a) as f64 / 1e6)),
+            _ => None,
+        };
+        Some(ExchangeTimings {
+            ttfb_ms: self.head_us.map(|h| ms(req, h)),
+            ttft_ms: first_tok.map(|t| ms(req, t)),
+            total_ms: self.last_us.map(|t| ms(req, t)),
+            inter_token: latency_stats(&gaps),
+            output_tokens,
+            tokens_per_sec,
+            token_chunks: self.token_us.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_to_timings() {
+        let marks = TimingMarks {
+            request_us: Some(1_000_000),
+            head_us: Some(1_200_000),
+            last_us: Some(2_600_000),
+            token_us: vec![1_500_000, 1_600_000, 1_800_000, 2_500_000],
+        };
+        let t = marks.compute(Some(8)).unwrap();
+        assert_eq!(t.ttfb_ms, Some(200.0));
+        assert_eq!(t.ttft_ms, Some(500.0));
+        assert_eq!(t.total_ms, Some(1600.0));
+        let itl = t.inter_token.unwrap();
+        assert_eq!(
+            (itl.count, itl.min_ms, itl.p50_ms, itl.max_ms),
+            (3, 100.0, 200.0, 700.0)
+        );
+        assert_eq!(t.tokens_per_sec, Some(8.0));
+
+        let no_usage = marks.compute(None).unwrap();
+        assert_eq!(no_usage.output_tokens, None);
+        assert_eq!(no_usage.tokens_per_sec, None);
+        assert_eq!(no_usage.token_chunks, 4);
+        assert!(TimingMarks::default().compute(None).is_none());
+    }
+}
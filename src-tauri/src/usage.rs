@@ -0,0 +1,408 @@
+// Token usage reported by LLM responses, and an in-memory ledger that totals it
+// per provider / model / process / host. The ledger is seeded from the closed
+// sessions on disk at startup, so totals survive a restart.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+
+use crate::events::{CaptureEvent, EventSink};
+use crate::llm::{LlmExchange, build_exchange};
+use crate::store::SessionStore;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub total_tokens: Option<u64>,
+    pub cached_input_tokens: Option<u64>,
+    pub reasoning_tokens: Option<u64>,
+}
+
+fn first_u64(obj: &Map<String, Value>, keys: &[&str]) -> Option<u64> {
+    keys.iter()
+        .find_map(|k| obj.get(*k).and_then(|v| v.as_u64()))
+}
+
+fn nested_u64(obj: &Map<String, Value>, parents: &[&str], key: &str) -> Option<u64> {
+    parents.iter().find_map(|p| {
+        obj.get(*p)
+            .and_then(|v| v.get(key))
+            .and_then(|v| v.as_u64())
+    })
+}
+
+fn from_usage_obj(u: &Map<String, Value>) -> TokenUsage {
+    TokenUsage {
+        input_tokens: first_u64(u, &["prompt_tokens", "input_tokens"]),
+        output_tokens: first_u64(u, &["completion_tokens", "output_tokens"]),
+        total_tokens: first_u64(u, &["total_tokens"]),
+        cached_input_tokens: nested_u64(
+            u,
+            &["prompt_tokens_details", "input_tokens_details"],
+            "cached_tokens",
+        )
+        .or_else(|| first_u64(u, &["cache_read_input_tokens"])),
+        reasoning_tokens: nested_u64(
+            u,
+            &["completion_tokens_details", "output_tokens_details"],
+            "reasoning_tokens",
+        ),
+    }
+}
+
+impl TokenUsage {
+    /// Usage carried by one response object or stream chunk, if any.
+    /// OpenAI: `usage`, or `response.usage` on Responses API events;
+    /// Anthropic: `usage` or `message.usage` (message_start);
+    /// Gemini: `usageMetadata`; Ollama: `prompt_eval_count` / `eval_count` on the
+    /// final object.
+    pub fn from_json(obj: &Map<String, Value>) -> Option<TokenUsage> {
+        let usage = obj.get("usage").and_then(|v| v.as_object()).or_else(|| {
+            ["message", "response"].iter().find_map(|k| {
+                obj.get(*k)
+                    .and_then(|m| m.get("usage"))
+                    .and_then(|v| v.as_object())
+            })
+        });
+        if let Some(u) = usage {
+            return Some(from_usage_obj(u));
+        }
+        if let Some(u) = obj.get("usageMetadata").and_then(|v| v.as_object()) {
+            return Some(TokenUsage {
+                input_tokens: first_u64(u, &["promptTokenCount"]),
+                output_tokens: first_u64(u, &["candidatesTokenCount"]),
+                total_tokens: first_u64(u, &["totalTokenCount"]),
+                cached_input_tokens: first_u64(u, &["cachedContentTokenCount"]),
+                reasoning_tokens: first_u64(u, &["thoughtsTokenCount"]),
+            });
+        }
+        let input = obj.get("prompt_eval_count").and_then(|v| v.as_u64());
+        let output = obj.get("eval_count").and_then(|v| v.as_u64());
+        if input.is_none() && output.is_none() {
+            return None;
+        }
+        Some(TokenUsage {
+            input_tokens: input,
+            output_tokens: output,
+            ..Default::default()
+        })
+    }
+
+    // Later chunks win field by field: Anthropic reports input tokens on
+    // message_start and the running output count on each message_delta.
+    pub fn merge(&mut self, other: TokenUsage) {
+        self.input_tokens = other.input_tokens.or(self.input_tokens);
+        self.output_tokens = other.output_tokens.or(self.output_tokens);
+        self.total_tokens = other.total_tokens.or(self.total_tokens);
+        self.cached_input_tokens = other.cached_input_tokens.or(self.cached_input_tokens);
+        self.reasoning_tokens = other.reasoning_tokens.or(self.reasoning_tokens);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total_tokens
+            .unwrap_or(self.input_tokens.unwrap_or(0) + self.output_tokens.unwrap_or(0))
+    }
+}
+
+// ---- ledger ----
+
+const MAX_RECORDS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+struct UsageRecord {
+    at_ms: i64,
+    key: UsageKey,
+    usage: TokenUsage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct UsageKey {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub process_name: Option<String>,
+    pub host: Option<String>,
+}
+
+/// Single dimension to total by; the other key fields are left empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroup {
+    Provider,
+    Model,
+    Process,
+    Host,
+}
+
+impl UsageGroup {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "provider" => Ok(Self::Provider),
+            "model" => Ok(Self::Model),
+            "process" => Ok(Self::Process),
+            "host" => Ok(Self::Host),
+            other => Err(format!(
+                "unknown group_by `{}` (provider, model, process or host)",
+                other
+            )),
+        }
+    }
+}
+
+impl UsageKey {
+    fn project(&self, group: Option<UsageGroup>) -> UsageKey {
+        let keep = |g: UsageGroup, v: &Option<String>| match group {
+            Some(x) if x != g => None,
+            _ => v.clone(),
+        };
+        UsageKey {
+            provider: keep(UsageGroup::Provider, &self.provider),
+            model: keep(UsageGroup::Model, &self.model),
+            process_name: keep(UsageGroup::Process, &self.process_name),
+            host: keep(UsageGroup::Host, &self.host),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotals {
+    #[serde(flatten)]
+    pub key: UsageKey,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub reasoning_tokens: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, u: &TokenUsage) {
+        self.requests += 1;
+        self.input_tokens += u.input_tokens.unwrap_or(0);
+        self.output_tokens += u.output_tokens.unwrap_or(0);
+        self.total_tokens += u.total();
+        self.cached_input_tokens += u.cached_input_tokens.unwrap_or(0);
+        self.reasoning_tokens += u.reasoning_tokens.unwrap_or(0);
+    }
+}
+
+#[derive(Default)]
+pub struct UsageLedger {
+    records: Mutex<VecDeque<UsageRecord>>,
+}
+
+pub static USAGE: Lazy<UsageLedger> = Lazy::new(UsageLedger::default);
+
+fn unix_ms(t: OffsetDateTime) -> i64 {
+    (t.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+fn timestamp_ms(ts: &str) -> Option<i64> {
+    OffsetDateTime::parse(ts, &time::format_description::well_known::Rfc3339)
+        .ok()
+        .map(unix_ms)
+}
+
+fn usage_record(exchange: &LlmExchange, at_ms: i64) -> Option<UsageRecord> {
+    Some(UsageRecord {
+        at_ms,
+        key: UsageKey {
+            provider: exchange.provider.clone(),
+            model: exchange.model.clone(),
+            process_name: exchange.process_name.clone(),
+            host: exchange.host.clone(),
+        },
+        usage: exchange.usage.clone()?,
+    })
+}
+
+impl UsageLedger {
+    pub fn record(&self, exchange: &LlmExchange) {
+        // live completions are stamped as they happen
+        let at_ms = timestamp_ms(&exchange.completed_at)
+            .unwrap_or_else(|| unix_ms(OffsetDateTime::now_utc()));
+        let Some(rec) = usage_record(exchange, at_ms) else {
+            return;
+        };
+        let mut records = self.records.lock().unwrap();
+        if records.len() >= MAX_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(rec);
+    }
+
+    /// Adds the usage persisted in every closed session of `store`, ahead of anything
+    /// recorded live. The active session is skipped: its exchanges arrive through
+    /// `record`. Exchanges are dated by their last response record, else the request,
+    /// else the session start; those with no parseable time are skipped. Returns how
+    /// many exchanges were loaded.
+    pub fn load_from_store(&self, store: &SessionStore) -> Result<usize, String> {
+        let mut loaded = Vec::new();
+        for session in store.list_sessions()? {
+            if session.active {
+                continue;
+            }
+            for ex in store.get_exchanges(&session.id)? {
+                if ex.usage.is_none() {
+                    continue;
+                }
+                let candidates = [
+                    ex.response_finished_at.as_deref(),
+                    ex.request.as_ref().map(|r| r.timestamp.as_str()),
+                    Some(session.started_at.as_str()),
+                ];
+                let Some((completed_at, at_ms)) = candidates
+                    .into_iter()
+                    .flatten()
+                    .find_map(|ts| Some((ts.to_string(), timestamp_ms(ts)?)))
+                else {
+                    continue;
+                };
+                // usage comes from the completion record; no need to re-parse the body
+                let mut exchange = build_exchange(
+                    ex.request.as_ref(),
+                    ex.response.as_ref(),
+                    &[],
+                    &completed_at,
+                );
+                exchange.usage = ex.usage;
+                loaded.extend(usage_record(&exchange, at_ms));
+            }
+        }
+        loaded.sort_by_key(|r| r.at_ms);
+        let count = loaded.len();
+        let mut records = self.records.lock().unwrap();
+        let room = MAX_RECORDS.saturating_sub(records.len());
+        for rec in loaded.into_iter().rev().take(room) {
+            records.push_front(rec);
+        }
+        Ok(count)
+    }
+
+    /// Totals for exchanges completed in `[from_ms, to_ms)` (unix millis), largest first.
+    /// Grouped by the full key, or by one dimension when `group` is set.
+    pub fn totals(
+        &self,
+        from_ms: Option<i64>,
+        to_ms: Option<i64>,
+        group: Option<UsageGroup>,
+    ) -> Vec<UsageTotals> {
+        let records = self.records.lock().unwrap();
+        let mut groups: HashMap<UsageKey, UsageTotals> = HashMap::new();
+        for r in records.iter() {
+            if from_ms.is_some_and(|f| r.at_ms < f) || to_ms.is_some_and(|t| r.at_ms >= t) {
+                continue;
+            }
+            let key = r.key.project(group);
+            groups
+                .entry(key.clone())
+                .or_insert_with(|| UsageTotals {
+                    key,
+                    ..Default::default()
+                })
+                .add(&r.usage);
+        }
+        let mut out: Vec<UsageTotals> = groups.into_values().collect();
+        out.sort_by_key(|t| std::cmp::Reverse(t.total_tokens));
+        out
+    }
+}
+
+/// Seeds `USAGE` from the sessions under the data dir; call once it is set.
+pub fn load_persisted_usage() {
+    if let Err(e) = USAGE.load_from_store(&crate::store::STORE) {
+        eprintln!("[usage] loading persisted sessions: {}", e);
+    }
+}
+
+/// Feeds completed exchanges into `USAGE`.
+pub struct UsageSink;
+
+impl EventSink for UsageSink {
+    fn emit(&self, event: &CaptureEvent) {
+        if let CaptureEvent::LlmExchange(exchange) = event {
+            USAGE.record(exchange);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::parse_response_body;
+
+    #[test]
+    fn usage_from_each_provider_shape() {
+        let openai = "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}],\"usage\":null}\n\n\
+                      data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":3,\"total_tokens\":15,\"prompt_tokens_details\":{\"cached_tokens\":4}}}\n\n\
+                      data: [DONE]\n\n";
+        let u = parse_response_body(openai).usage.unwrap();
+        assert_eq!(
+            (
+                u.input_tokens,
+                u.output_tokens,
+                u.total_tokens,
+                u.cached_input_tokens
+            ),
+            (Some(12), Some(3), Some(15), Some(4))
+        );
+
+        let ollama = "{\"message\":{\"content\":\"a\"},\"done\":false}\n\
+                      {\"message\":{\"content\":\"\"},\"done\":true,\"prompt_eval_count\":7,\"eval_count\":9}\n";
+        let u = parse_response_body(ollama).usage.unwrap();
+        assert_eq!(
+            (u.input_tokens, u.output_tokens, u.total()),
+            (Some(7), Some(9), 16)
+        );
+
+        let anthropic = "event: message_start\n\
+                         data: {\"type\":\"message_start\",\"message\":{\"model\":\"claude\",\"usage\":{\"input_tokens\":20,\"output_tokens\":1}}}\n\n\
+                         event: message_delta\n\
+                         data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":30}}\n\n";
+        let u = parse_response_body(anthropic).usage.unwrap();
+        assert_eq!((u.input_tokens, u.output_tokens), (Some(20), Some(30)));
+    }
+
+    #[test]
+    fn ledger_groups_within_window() {
+        let ledger = UsageLedger::default();
+        let exchange = |model: &str, at: &str, tokens: u64| LlmExchange {
+            provider: Some("openai".into()),
+            model: Some(model.into()),
+            host: Some("api.openai.com".into()),
+            completed_at: at.into(),
+            usage: Some(TokenUsage {
+                input_tokens: Some(tokens),
+                output_tokens: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        ledger.record(&exchange("a", "2025-01-01T00:00:00Z", 10));
+        ledger.record(&exchange("a", "2025-01-01T00:00:30Z", 20));
+        ledger.record(&exchange("b", "2025-01-01T00:00:40Z", 5));
+        ledger.record(&exchange("a", "2025-01-01T01:00:00Z", 100));
+
+        let from = timestamp_ms("2025-01-01T00:00:00Z").unwrap();
+        let to = timestamp_ms("2025-01-01T00:01:00Z").unwrap();
+        let totals = ledger.totals(Some(from), Some(to), None);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].key.model.as_deref(), Some("a"));
+        assert_eq!(totals[0].requests, 2);
+        assert_eq!(totals[0].input_tokens, 30);
+        assert_eq!(totals[0].total_tokens, 32);
+        assert_eq!(ledger.totals(None, None, None).len(), 2);
+
+        let by_host = ledger.totals(Some(from), Some(to), Some(UsageGroup::Host));
+        assert_eq!(by_host.len(), 1);
+        assert_eq!(by_host[0].key.host.as_deref(), Some("api.openai.com"));
+        assert_eq!(by_host[0].key.model, None);
+        assert_eq!(by_host[0].requests, 3);
+        assert_eq!(UsageGroup::parse("process"), Ok(UsageGroup::Process));
+        assert!(UsageGroup::parse("user").is_err());
+    }
+}
@@ -541,7 +541,7 @@ function App() {
                 <td className="px-2 py-1.5 align-middle text-[12px] truncate max-w-[16rem]">{r.req?.path || ""}</td>
                 <td className="px-2 py-1.5 align-middle text-[12px]">{r.resp ? formatSize(respSizeForRow(r)) : ""}</td>
                 <td className="px-2 py-1.5 align-middle text-[12px]">{(() => { const v = costForRow(r); return v != null ? formatDuration(v) : ""; })()}</td>
-                <td className="px-2 py-1.5 align-middle text-[12px]">{r.req?.process_name || r.resp?.process_name}</td>
+                <td className="px-2 py-1.5 align-middle text-[12px]" title={(r.req?.process || r.resp?.process)?.argv.join(" ")}>{r.req?.process_name || r.resp?.process_name}</td>
               </tr>
             ))}
             </tbody>
@@ -1,5 +1,14 @@
 export type HeaderKV = { name: string; value: string };
 
+export type ProcessInfo = {
+  exe?: string;
+  argv: string[];
+  cwd?: string;
+  uid?: number;
+  user?: string;
+  parents: { pid: number; name?: string }[];
+};
+
 export type HttpReq = {
   id: string;
   timestamp: string;
@@ -15,8 +24,11 @@ export type HttpReq = {
   body_len: number;
   process_name?: string;
   pid?: number;
+  process?: ProcessInfo;
   is_llm: boolean;
   llm_provider?: string;
+  conn_id?: number;
+  conn_seq?: number;
 };
 
 export type HttpResp = {
@@ -34,6 +46,7 @@ export type HttpResp = {
   body_len: number;
   process_name?: string;
   pid?: number;
+  process?: ProcessInfo;
   is_llm: boolean;
   llm_provider?: string;
 };